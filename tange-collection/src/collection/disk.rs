@@ -16,18 +16,24 @@ use std::any::Any;
 use std::io::prelude::*;
 use std::io::BufWriter;
 use std::hash::Hash;
-use std::sync::Arc;
+use std::fmt::Debug;
+use std::collections::{HashMap,HashSet};
+use std::sync::{Arc,mpsc};
+use std::thread;
 
 use self::serde::Deserialize;
 use self::serde::Serialize;
 
-use tange::deferred::{Deferred, batch_apply, tree_reduce};
+use tange::deferred::{Deferred, batch_apply, batch_join, tree_reduce, tree_reduce_ordered, collect_deferreds};
 use tange::scheduler::{Scheduler,GreedyScheduler};
 
 use collection::memory::MemoryCollection;
-use partitioned::{join_on_key as jok, partition, partition_by_key, fold_by, concat};
+use partitioned::{join_on_key as jok, left_join_on_key, diff_on_key, partition, partition_by_key, fold_by, reduce_by_key, map_reduce_by_key, concat, digest, bloom, filter_by_bloom, filter_on_keys, map_with_broadcast, group_shuffle, group_capped, interleave, merge_sorted, hash_key, mean, count_buckets, reduce, sink_single, first_by_key, window_global, zip_with_index, scan_global, prefix_while, sample_bounds, range_partition, rebalance_by_key, repartition_ordered, coalesce_balanced};
 use interfaces::*;
-use super::emit;
+use sketch::BloomFilter;
+use partition::{Partitioner,ConsistentHashPartitioner};
+use super::{StorageLevel, Change, emit, flat_map_indexed, check_partitions, TopK, reservoir, shuffle, label, explain, run_to_channel, with_offsets, first_n, map_progress, pipe, write_atomic, to_columns, non_empty, map_accumulating};
+use utils::SideAccumulator;
 
 
 /// DiskCollection struct.
@@ -37,6 +43,92 @@ pub struct DiskCollection<A: Clone + Send + Sync>  {
     partitions: Vec<Deferred<Arc<FileStore<A>>>>
 }
 
+impl <A: Any + Clone + Send + Sync> DiskCollection<A> {
+
+    /// Marks each partition of the collection for caching.  Schedulers with a cache, such
+    /// as `GreedyScheduler::with_cache_capacity`, keep the computed partitions between
+    /// runs, evicting the least recently used ones when the cache is full, so iterative
+    /// algorithms can reuse a collection without repeating its lineage.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let s = GreedyScheduler::new().with_cache_capacity(8);
+    ///   let squares = DiskCollection::from_vec("/tmp".into(), vec![1,2,3usize]).map(|x| x * x).cache();
+    ///   assert_eq!(squares.run(&s), Some(vec![1, 4, 9]));
+    ///   assert_eq!(squares.map(|x| x + 1).run(&s), Some(vec![2, 5, 10]));
+    /// ```
+    pub fn cache(&self) -> DiskCollection<A> {
+        DiskCollection { 
+            path: self.path.clone(), 
+            partitions: self.partitions.iter().map(|d| d.cache()).collect() 
+        }
+    }
+
+    /// Marks each partition of the collection for retention at the given storage level, 
+    /// for schedulers with a cache such as `GreedyScheduler::with_cache_capacity`.  The 
+    /// partitions of a DiskCollection are always stored on disk, so every level keeps 
+    /// the partitions' files between runs and is equivalent to `cache`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::StorageLevel;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let s = GreedyScheduler::new().with_cache_capacity(8);
+    ///   let squares = DiskCollection::from_vec("/tmp".into(), vec![1,2,3usize]).map(|x| x * x)
+    ///     .persist(StorageLevel::Disk);
+    ///   assert_eq!(squares.run(&s), Some(vec![1, 4, 9]));
+    ///   assert_eq!(squares.map(|x| x + 1).run(&s), Some(vec![2, 5, 10]));
+    /// ```
+    pub fn persist(&self, _level: StorageLevel) -> DiskCollection<A> {
+        self.cache()
+    }
+
+    fn named(&self, name: &str) -> DiskCollection<A> {
+        DiskCollection { path: self.path.clone(), partitions: label(&self.partitions, name) }
+    }
+
+    /// Describes the plan for computing the collection without running it: the number
+    /// of partitions and, for each partition, the chain of operations leading to it.
+    /// Where an operation combines two inputs, the chain follows the left-hand input.
+    /// ```rust
+    ///   extern crate tange_collection;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3usize]).map(|x| x + 1);
+    ///   assert!(col.explain().starts_with("DiskCollection with 1 partition(s)\n"));
+    ///   assert!(col.explain().ends_with(" -> Task<name=map>\n"));
+    /// ```
+    pub fn explain(&self) -> String {
+        explain("DiskCollection", &self.partitions)
+    }
+}
+
+impl DiskCollection<i64> {
+
+    /// Creates a DiskCollection holding the integers in `start..end`, split into
+    /// `partitions` contiguous partitions of near equal size.  Each partition generates its
+    /// numbers when the collection is computed, rather than up front.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_range("/tmp".into(), 0, 5, 2);
+    ///   assert_eq!(col.n_partitions(), 2);
+    ///   assert_eq!(col.run(&GreedyScheduler::new()), Some(vec![0, 1, 2, 3, 4]));
+    /// ```
+    pub fn from_range(path: String, start: i64, end: i64, partitions: usize) -> DiskCollection<i64> {
+        check_partitions("DiskCollection::from_range", partitions);
+        MemoryCollection::from_range(start, end, partitions).to_disk(path)
+    }
+}
+
 impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskCollection<A> {
 
     /// Create a new DiskCollection form a Vector of objects.
@@ -53,6 +145,22 @@ impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskC
         MemoryCollection::from_vec(vec).to_disk(path)
     }
 
+    /// Create a new DiskCollection from a Vector of objects, split into `partitions`
+    /// contiguous partitions of near equal size.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec_split("/tmp".into(), vec![1,2,3,4,5usize], 2);
+    ///   assert_eq!(col.n_partitions(), 2);
+    ///   assert_eq!(col.run(&GreedyScheduler::new()), Some(vec![1,2,3,4,5usize]));
+    /// ```
+    pub fn from_vec_split(path: String, vec: Vec<A>, partitions: usize) -> DiskCollection<A> {
+        MemoryCollection::from_vec_split(vec, partitions).to_disk(path)
+    }
+
     /// Converts a collection of Deferred objects into a DiskCollection
     /// This is usually best used from the `MemoryCollection`
     pub fn from_memory(path: String, mc: &Vec<Deferred<Vec<A>>>) -> DiskCollection<A> {
@@ -114,6 +222,214 @@ impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskC
 
         self.from_defs(nps)
     }
+
+    /// Combines the aligned partitions of two collections with the provided function,
+    /// which receives the full contents of each pair of partitions.  Both collections must
+    /// have the same number of partitions, but the partitions themselves can differ in 
+    /// length.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let left = DiskCollection::from_vec("/tmp".into(), vec![1,3,5usize]);
+    ///   let right = DiskCollection::from_vec("/tmp".into(), vec![2,4usize]);
+    ///   let sizes = left.zip_partitions(&right, |l, r| vec![l.len(), r.len()]);
+    ///   assert_eq!(sizes.run(&GreedyScheduler::new()), Some(vec![3, 2]));
+    /// ```
+    pub fn zip_partitions<
+        B: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>,
+        C: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>,
+        F: 'static + Sync + Send + Clone + Fn(&Vec<A>, &Vec<B>) -> Vec<C>
+    >(&self, other: &DiskCollection<B>, f: F) -> DiskCollection<C> {
+        assert_eq!(self.partitions.len(), other.partitions.len(), 
+                   "DiskCollection::zip_partitions requires equal partition counts");
+        let acc = Arc::new(FileStore::empty(self.path.clone()));
+        let new_parts = batch_join(&self.partitions, &other.partitions, move |_idx, l, r| {
+            let left: Vec<_> = l.stream().into_iter().collect();
+            let right: Vec<_> = r.stream().into_iter().collect();
+            acc.write_vec(f(&left, &right))
+        });
+        self.from_defs(new_parts)
+    }
+
+    /// Merges two collections fairly by alternating between them: within each aligned pair
+    /// of partitions, items are taken one from each side in turn until both are exhausted.
+    /// Both collections must have the same number of partitions.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let odds = DiskCollection::from_vec("/tmp".into(), vec![1, 3, 5usize]);
+    ///   let evens = DiskCollection::from_vec("/tmp".into(), vec![2, 4usize]);
+    ///   let merged = odds.interleave(&evens);
+    ///   assert_eq!(merged.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 4, 5]));
+    /// ```
+    pub fn interleave(&self, other: &DiskCollection<A>) -> DiskCollection<A> {
+        assert_eq!(self.partitions.len(), other.partitions.len(), 
+                   "DiskCollection::interleave requires equal partition counts");
+        let parts = interleave(&self.partitions, &other.partitions, Disk(self.path.clone()));
+        self.from_defs(parts).named("interleave")
+    }
+
+    /// Maps each item with a carry value computed from the preceding partitions.  The 
+    /// first partition receives `seed()`; each following partition receives the result of
+    /// `carry` applied to the previous carry and the previous partition.  Since every 
+    /// partition depends on the one before it, this serializes partition processing.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4usize]).split(2);
+    ///   // Offset each item by the sum of all prior partitions
+    ///   let offset = col.map_with_carry(|| 0, 
+    ///       |c, vs| c + vs.iter().sum::<usize>(),
+    ///       |c, x| (*c, *x));
+    ///   assert_eq!(offset.run(&GreedyScheduler::new()), 
+    ///     Some(vec![(0, 1), (0, 3), (4, 2), (4, 4)]));
+    /// ```
+    pub fn map_with_carry<
+        B: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>,
+        C: Any + Send + Sync + Clone,
+        Seed: Fn() -> C,
+        Carry: 'static + Sync + Send + Clone + Fn(&C, &Vec<A>) -> C,
+        F: 'static + Sync + Send + Clone + Fn(&C, &A) -> B
+    >(&self, seed: Seed, carry_fn: Carry, f: F) -> DiskCollection<B> {
+        let acc = Arc::new(FileStore::empty(self.path.clone()));
+        let mut carry = Deferred::lift(seed(), None);
+        let mut new_parts = Vec::with_capacity(self.partitions.len());
+        for part in self.partitions.iter() {
+            let f = f.clone();
+            let acc = acc.clone();
+            new_parts.push(part.join(&carry, move |vs, c| {
+                let mut out = acc.writer();
+                for x in vs.stream() {
+                    out.add(f(c, &x));
+                }
+                out.finish()
+            }));
+            let carry_fn = carry_fn.clone();
+            carry = part.join(&carry, move |vs, c| {
+                carry_fn(c, &vs.stream().into_iter().collect())
+            });
+        }
+        self.from_defs(new_parts).named("map_with_carry")
+    }
+
+    /// Computes a running value across the whole collection, such as a cumulative sum,
+    /// continuing from one partition into the next in partition order.  `f` folds each
+    /// item into the running value.  Partitions are scanned in parallel from `init`, then
+    /// `combine` merges each partition's running values with the carry from all prior 
+    /// partitions, so `init` must be the identity of `combine`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4usize])
+    ///     .concat(&DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4usize]));
+    ///   let sums = col.scan_global(0, |acc, x| acc + x, |c, x| c + x);
+    ///   assert_eq!(sums.run(&GreedyScheduler::new()), 
+    ///     Some(vec![1, 3, 6, 10, 11, 13, 16, 20]));
+    /// ```
+    pub fn scan_global<
+        B: Any + Send + Sync + Clone + Serialize + for<'de> Deserialize<'de>,
+        F: 'static + Sync + Send + Clone + Fn(&B, &A) -> B,
+        R: 'static + Sync + Send + Clone + Fn(&B, &B) -> B
+    >(&self, init: B, f: F, combine: R) -> DiskCollection<B> {
+        let parts = scan_global(&self.partitions, init, f, combine, Disk(self.path.clone()));
+        self.from_defs(parts).named("scan_global")
+    }
+
+    /// Runs a function over each full partition for its side effects, such as bulk
+    /// inserting each partition into a database.  The function receives the partition
+    /// index and its items.  The returned Deferred completes once every partition has 
+    /// been processed.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3usize]).split(2);
+    ///   let done = col.for_each_partition(|idx, vs| println!("{}: {:?}", idx, vs));
+    ///   assert_eq!(done.run(&GreedyScheduler::new()), Some(()));
+    /// ```
+    pub fn for_each_partition<
+        F: 'static + Sync + Send + Clone + Fn(usize, &Vec<A>)
+    >(&self, f: F) -> Deferred<()> {
+        let done = batch_apply(&self.partitions, move |idx, vs| {
+            f(idx, &vs.stream().into_iter().collect())
+        });
+        tree_reduce(&done, |_x, _y| ()).unwrap_or_else(|| Deferred::lift((), None))
+    }
+
+    /// Produces every window of `size` consecutive items, including windows which 
+    /// straddle partition boundaries.  Each window is emitted in the partition of its
+    /// first item.  Each partition depends on the heads of the partitions following it.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   // Partitions are [1, 3] and [2, 4]
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4usize]).split(2);
+    ///   let windows = col.window_global(2);
+    ///   assert_eq!(windows.run(&GreedyScheduler::new()), 
+    ///     Some(vec![vec![1, 3], vec![3, 2], vec![2, 4]]));
+    /// ```
+    pub fn window_global(&self, size: usize) -> DiskCollection<Vec<A>> {
+        assert!(size > 0, "DiskCollection::window_global requires a window size of at least one");
+        let new_parts = window_global(&self.partitions, size, Arc::new(FileStore::empty(self.path.clone())));
+        self.from_defs(new_parts)
+    }
+
+    /// Keeps items from the start of the collection, in partition order, for as long as
+    /// they pass the predicate.  Everything from the first failing item onwards is 
+    /// dropped, even in later partitions.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4, 1usize]);
+    ///   assert_eq!(col.take_while(|x| *x < 3).run(&GreedyScheduler::new()), 
+    ///     Some(vec![1, 2]));
+    /// ```
+    pub fn take_while<
+        F: 'static + Sync + Send + Clone + Fn(&A) -> bool
+    >(&self, f: F) -> DiskCollection<A> {
+        let parts = prefix_while(&self.partitions, f, Disk(self.path.clone()), true);
+        self.from_defs(parts).named("take_while")
+    }
+
+    /// Drops items from the start of the collection, in partition order, for as long as
+    /// they pass the predicate.  Everything from the first failing item onwards is kept,
+    /// even if later items pass the predicate.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4, 1usize]);
+    ///   assert_eq!(col.skip_while(|x| *x < 3).run(&GreedyScheduler::new()), 
+    ///     Some(vec![3, 4, 1]));
+    /// ```
+    pub fn skip_while<
+        F: 'static + Sync + Send + Clone + Fn(&A) -> bool
+    >(&self, f: F) -> DiskCollection<A> {
+        let parts = prefix_while(&self.partitions, f, Disk(self.path.clone()), false);
+        self.from_defs(parts).named("skip_while")
+    }
     
     /// Maps a function over the values in the DiskCollection, returning a new DiskCollection
     /// ```rust
@@ -133,7 +449,147 @@ impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskC
     >(&self, f: F) -> DiskCollection<B> {
         self.emit(move |x, emitter| {
             emitter(f(x))
-        })
+        }).named("map")
+    }
+
+    /// Maps each item to a pair of values, returning two collections holding the first 
+    /// and second values respectively.  Both outputs are built in a single pass over each
+    /// partition, so deriving several collections from one doesn't traverse it twice.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3usize]);
+    ///   let (doubled, labels) = col.map_fanout(|x| (x * 2, format!("#{}", x)));
+    ///   assert_eq!(doubled.run(&GreedyScheduler::new()), Some(vec![2, 4, 6]));
+    ///   assert_eq!(labels.run(&GreedyScheduler::new()), 
+    ///     Some(vec!["#1".to_owned(), "#2".into(), "#3".into()]));
+    /// ```
+    pub fn map_fanout<
+        B: Any + Send + Sync + Clone + Serialize + for<'de> Deserialize<'de>,
+        C: Any + Send + Sync + Clone + Serialize + for<'de> Deserialize<'de>,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> (B, C)
+    >(&self, f: F) -> (DiskCollection<B>, DiskCollection<C>) {
+        let acc = Disk(self.path.clone());
+        let pairs = batch_apply(&self.partitions, move |_idx, vs| {
+            let mut left = acc.writer();
+            let mut right = acc.writer();
+            for v in vs.stream() {
+                let (b, c) = f(&v);
+                left.add(b);
+                right.add(c);
+            }
+            (left.finish(), right.finish())
+        });
+        let (left, right) = pairs.iter().map(|p| p.split()).unzip();
+        (self.from_defs(left).named("map_fanout"), self.from_defs(right).named("map_fanout"))
+    }
+
+    /// Maps a function over the values in the DiskCollection, like `map`, while reporting
+    /// progress for long partitions.  `progress` is called with the partition index, the
+    /// number of items processed so far and the partition's total every 10,000 items, and 
+    /// once more when the partition is finished.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), (0..25_000usize).collect());
+    ///   let doubled = col.map_progress(|x| x * 2, |idx, done, total| {
+    ///     println!("partition {}: {}/{}", idx, done, total);
+    ///   });
+    ///   assert_eq!(doubled.count().run(&GreedyScheduler::new()), Some(vec![25_000]));
+    /// ```
+    pub fn map_progress<
+        B: Any + Send + Sync + Clone + Serialize, 
+        F: 'static + Sync + Send + Clone + Fn(&A) -> B,
+        P: 'static + Sync + Send + Clone + Fn(usize, usize, usize)
+    >(&self, f: F, progress: P) -> DiskCollection<B> {
+        let parts = map_progress(&self.partitions, Disk(self.path.clone()), f, progress);
+
+        self.from_defs(parts).named("map_progress")
+    }
+
+    /// Maps a function over the values in the DiskCollection, like `map`, while updating a
+    /// side metric.  `f` receives the partition's partial value for `side`, which is 
+    /// merged into `side` once the partition is computed.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   use tange_collection::utils::SideAccumulator;
+    ///   
+    ///   let malformed = SideAccumulator::new(0usize, |total, partial| *total += partial);
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec!["1".to_owned(), "x".into(), "3".into(), "y".into()]);
+    ///   let parsed = col.map_with_accumulator(&malformed, |s, bad| {
+    ///     let n = s.parse::<usize>().ok();
+    ///     if n.is_none() { *bad += 1; }
+    ///     n
+    ///   });
+    ///   assert_eq!(parsed.run(&GreedyScheduler::new()), Some(vec![Some(1), None, Some(3), None]));
+    ///   assert_eq!(malformed.value(), 2);
+    /// ```
+    pub fn map_with_accumulator<
+        B: Any + Send + Sync + Clone + Serialize,
+        T: 'static + Send + Sync + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&A, &mut T) -> B
+    >(&self, side: &SideAccumulator<T>, f: F) -> DiskCollection<B> {
+        let parts = map_accumulating(&self.partitions, Disk(self.path.clone()), side, f);
+
+        self.from_defs(parts).named("map_with_accumulator")
+    }
+
+    /// Maps each item to a vector of values and flattens the results.  This is the fusion
+    /// of `map` and `flatten` in a single pass, avoiding the intermediate collection of
+    /// vectors and its nodes in the graph.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3usize]);
+    ///   let repeated = col.map_flatten(|x| vec![*x; *x]);
+    ///   assert_eq!(repeated.run(&GreedyScheduler::new()), 
+    ///     Some(vec![1, 2, 2, 3, 3, 3]));
+    /// ```
+    pub fn map_flatten<
+        B: Any + Send + Sync + Clone + Serialize,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> Vec<B>
+    >(&self, f: F) -> DiskCollection<B> {
+        self.emit(move |x, emitter| {
+            for xi in f(x) {
+                emitter(xi);
+            }
+        }).named("map_flatten")
+    }
+
+    /// Maps each item through `f` and then `g` in a single pass.  This is equivalent to
+    /// `map(f).map(g)`, but adds one node to the graph instead of two.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3usize]);
+    ///   let strings = col.map_then(|x| x * 2, |x| format!("{}", x));
+    ///   assert_eq!(strings.run(&GreedyScheduler::new()), 
+    ///     Some(vec!["2".into(), "4".into(), "6".into()]));
+    /// ```
+    pub fn map_then<
+        B: Any + Send + Sync + Clone,
+        C: Any + Send + Sync + Clone + Serialize,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> B,
+        G: 'static + Sync + Send + Clone + Fn(&B) -> C
+    >(&self, f: F, g: G) -> DiskCollection<C> {
+        self.emit(move |x, emitter| {
+            emitter(g(&f(x)))
+        }).named("map_then")
     }
 
     /// Filters out items in the collection that fail the predicate.
@@ -156,10 +612,83 @@ impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskC
             if f(x) { 
                 emitter(x.clone())
             }
-        })
+        }).named("filter")
+    }
+
+    /// Keeps only the items whose key, as computed by `key`, is in `keys`.  The set is 
+    /// shared by every partition rather than copied into each.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use std::collections::HashSet;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![(1, 10), (2, 20), (3, 30usize)]);
+    ///   let keys: HashSet<_> = vec![1, 3].into_iter().collect();
+    ///   let kept = col.filter_keys_in(keys, |x| x.0);
+    ///   assert_eq!(kept.run(&GreedyScheduler::new()), Some(vec![(1, 10), (3, 30)]));
+    /// ```
+    pub fn filter_keys_in<
+        K: 'static + Sync + Send + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, keys: HashSet<K>, key: F) -> DiskCollection<A> {
+        let keys = Arc::new(keys);
+        self.filter(move |x| keys.contains(&key(x))).named("filter_keys_in")
+    }
+
+    /// Checks every item against `f`, splitting the collection into the items which pass
+    /// and the items which fail, each paired with the error `f` returned for it.  The
+    /// checked items are marked with `cache`, so running both outputs on a scheduler with
+    /// a cache, such as `GreedyScheduler::with_cache_capacity`, evaluates `f` only once
+    /// per item.  Other schedulers evaluate `f` again for each output that is run.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![3, -1, 4, -5i32]);
+    ///   let (valid, invalid) = col.validate(|x| if *x >= 0 { Ok(()) } else { Err(x.abs()) });
+    ///   assert_eq!(valid.run(&GreedyScheduler::new()), Some(vec![3, 4]));
+    ///   assert_eq!(invalid.run(&GreedyScheduler::new()), Some(vec![(-1, 1), (-5, 5)]));
+    /// ```
+    pub fn validate<
+        E: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> Result<(), E>
+    >(&self, f: F) -> (DiskCollection<A>, DiskCollection<(A, E)>) {
+        let checked = self.map(move |x| f(x).map(|_| x.clone()).map_err(|e| (x.clone(), e)))
+            .named("validate")
+            .cache();
+        let valid = checked.flat_map_with_index(|_idx, r| r.clone().ok());
+        let invalid = checked.flat_map_with_index(|_idx, r| r.clone().err());
+        (valid, invalid)
     }
     
+    /// Pairs each item with a key derived from it, keeping the original item as the value.
+    /// This is convenient before keyed operations.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec!["a".to_owned(), "bb".into(), "cc".into()]);
+    ///   let keyed = col.key_by(|s| s.len());
+    ///   assert_eq!(keyed.run(&GreedyScheduler::new()), 
+    ///     Some(vec![(1, "a".into()), (2, "bb".into()), (2, "cc".into())]));
+    /// ```
+    pub fn key_by<
+        K: Any + Send + Sync + Clone + Serialize,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, f: F) -> DiskCollection<(K, A)> {
+        self.map(move |x| (f(x), x.clone())).named("key_by")
+    }
+
     /// Re-partitions a collection by the number of provided chunks.  It uniformly distributes data from each old partition into each new partition.
+    /// Items are dealt out round-robin by their position within their old partition, so a
+    /// single large partition is subdivided evenly and each new partition keeps the items
+    /// in their original relative order.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
@@ -175,61 +704,381 @@ impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskC
     /// ```
 
     pub fn split(&self, n_chunks: usize) -> DiskCollection<A> {
-        self.partition(n_chunks, |idx, _k| idx)
+        check_partitions("DiskCollection::split", n_chunks);
+        self.partition(n_chunks, |idx, _k| idx).named("split")
     }
 
-    /// Maps over all items in a collection, optionally emitting new values.  It can be used
-    /// to efficiently fuse a number of map/filter/flat_map functions into a single method.
+    /// Re-partitions the collection like `split` and marks the new partitions for caching,
+    /// so that reusing the result with a caching scheduler, such as 
+    /// `GreedyScheduler::with_cache_capacity`, only shuffles the data once.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::disk::DiskCollection;
     ///   
-    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3usize]);
-    ///   let new = col.emit(|item, emitter| {
-    ///     if item % 2 == 0 {
-    ///         emitter(format!("{}!", item));
-    ///     }
-    ///   });
-    ///   assert_eq!(new.run(&GreedyScheduler::new()), Some(vec!["2!".into()]));
+    ///   let s = GreedyScheduler::new().with_cache_capacity(8);
+    ///   let col = DiskCollection::from_vec("/tmp".into(), (0..100usize).collect()).repartition_cached(4);
+    ///   assert_eq!(col.n_partitions(), 4);
+    ///   assert_eq!(col.count().run(&s), Some(vec![100]));
+    ///   assert_eq!(col.filter(|x| x % 2 == 0).count().run(&s), Some(vec![50]));
     /// ```
-    pub fn emit<
-        B: Any + Send + Sync + Clone + Serialize,
-        F: 'static + Sync + Send + Clone + Fn(&A, &mut FnMut(B) -> ())
-    >(&self, f: F) -> DiskCollection<B> {
-
-        let parts = emit(&self.partitions, Disk(self.path.clone()), f);
-
-        self.from_defs(parts)
+    pub fn repartition_cached(&self, n_chunks: usize) -> DiskCollection<A> {
+        check_partitions("DiskCollection::repartition_cached", n_chunks);
+        self.split(n_chunks).cache()
     }
 
-    /// Re-partitions data into N new partitions by the given function.  The user provided
-    /// function is used as a hash function, mapping the returned value to a partition index.
-    /// This makes it useful for managing which partition data ends up!
+    /// Reduces the number of partitions to at most `n_chunks` by concatenating runs of
+    /// neighboring partitions, preserving the order of the data.  Unlike `split`, no data
+    /// is shuffled between partitions.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::disk::DiskCollection;
     ///   
-    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4usize]);
-    ///   let new_col = col.partition(2, |idx, x| if *x < 3 { 1 } else { 2 });
-    ///   
-    ///   assert_eq!(new_col.n_partitions(), 2);
-    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![3, 4, 1, 2]));
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4,5usize]).split(5);
+    ///   let two = col.coalesce(2);
+    ///   assert_eq!(two.n_partitions(), 2);
+    ///   assert_eq!(two.run(&GreedyScheduler::new()), col.run(&GreedyScheduler::new()));
     /// ```
+    pub fn coalesce(&self, n_chunks: usize) -> DiskCollection<A> {
+        check_partitions("DiskCollection::coalesce", n_chunks);
+        let size = self.partitions.len().div_ceil(n_chunks);
+        let new_parts = self.partitions.chunks(size.max(1))
+            .map(|group| concat(group).unwrap())
+            .collect();
+        self.from_defs(new_parts).named("coalesce")
+    }
 
-    pub fn partition<
-        F: 'static + Sync + Send + Clone + Fn(usize, &A) -> usize
-    >(&self, partitions: usize, f: F) -> DiskCollection<A> {
-        let new_chunks = partition(&self.partitions, 
-                                   partitions, 
+    /// Merges partitions together into `n_chunks` partitions like `coalesce`, but balances
+    /// the new partitions by size rather than merging neighbors.  Partitions are assigned 
+    /// whole, largest first, to whichever new partition holds the fewest items so far.
+    /// This computes the length of every partition before moving any data.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), (0..10usize).collect())
+    ///     .concat(&DiskCollection::from_vec("/tmp".into(), vec![10, 11]))
+    ///     .concat(&DiskCollection::from_vec("/tmp".into(), (12..20usize).collect()));
+    ///   let two = col.coalesce_balanced(2);
+    ///   assert_eq!(two.n_partitions(), 2);
+    ///   assert_eq!(two.count().run(&GreedyScheduler::new()), Some(vec![20]));
+    /// ```
+    pub fn coalesce_balanced(&self, n_chunks: usize) -> DiskCollection<A> {
+        check_partitions("DiskCollection::coalesce_balanced", n_chunks);
+        let new_parts = coalesce_balanced(&self.partitions, n_chunks);
+        self.from_defs(new_parts).named("coalesce_balanced")
+    }
+
+    /// Re-partitions the collection into `n_chunks` partitions of near equal size while
+    /// preserving the order of the items: concatenating the new partitions yields the 
+    /// same sequence as concatenating the old ones.  This computes the length of every
+    /// partition before moving any data.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4,5usize]).split(2);
+    ///   let three = col.repartition_ordered(3);
+    ///   assert_eq!(three.n_partitions(), 3);
+    ///   assert_eq!(three.run(&GreedyScheduler::new()), col.run(&GreedyScheduler::new()));
+    /// ```
+    pub fn repartition_ordered(&self, n_chunks: usize) -> DiskCollection<A> {
+        check_partitions("DiskCollection::repartition_ordered", n_chunks);
+        let new_parts = repartition_ordered(&self.partitions, n_chunks);
+        self.from_defs(new_parts).named("repartition_ordered")
+    }
+
+    /// Re-partitions the collection to have the same number of partitions as `other`, 
+    /// using `split`, so the two can be combined partition by partition with 
+    /// `zip_partitions`.  Only the partition count is matched, not how items are spread
+    /// across partitions.  When the counts already match, the collection is unchanged.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4,5usize]).split(3);
+    ///   let other = DiskCollection::from_vec("/tmp".into(), vec!["a".to_owned(), "b".into()]).split(5);
+    ///   let aligned = col.align_to(&other);
+    ///   assert_eq!(aligned.n_partitions(), 5);
+    /// ```
+    pub fn align_to<B: Clone + Send + Sync>(&self, other: &DiskCollection<B>) -> DiskCollection<A> {
+        let n = other.partitions.len();
+        if self.partitions.len() == n {
+            self.clone()
+        } else {
+            self.split(n)
+        }
+    }
+
+    /// Measures the size of every partition and, if the largest partition holds more than
+    /// `skew_ratio` times the average, re-partitions the collection into the same number
+    /// of near equal partitions with `repartition_ordered`.  Otherwise the collection is
+    /// returned unchanged.  Measuring runs the collection on `s`, so consider caching it
+    /// first to avoid computing it again.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), (0..90usize).collect())
+    ///     .concat(&DiskCollection::from_vec("/tmp".into(), (90..100usize).collect()));
+    ///   let balanced = col.auto_rebalance(&GreedyScheduler::new(), 1.5);
+    ///   assert_eq!(balanced.n_partitions(), 2);
+    ///   assert_eq!(balanced.count().run(&GreedyScheduler::new()), Some(vec![100]));
+    /// ```
+    pub fn auto_rebalance<S: Scheduler>(&self, s: &S, skew_ratio: f64) -> DiskCollection<A> {
+        let lengths = batch_apply(&self.partitions, |_idx, vs| vs.stream().into_iter().count());
+        let sizes = match collect_deferreds(&lengths).run(s) {
+            Some(sizes) => sizes,
+            None => return self.clone()
+        };
+        let total: usize = sizes.iter().sum();
+        let max = sizes.iter().cloned().max().unwrap_or(0);
+        if total == 0 || max as f64 <= skew_ratio * total as f64 / sizes.len() as f64 {
+            self.clone()
+        } else {
+            self.repartition_ordered(sizes.len()).named("auto_rebalance")
+        }
+    }
+
+    /// Maps over all items in a collection, optionally emitting new values.  It can be used
+    /// to efficiently fuse a number of map/filter/flat_map functions into a single method.
+    /// If `f` panics, the panic is re-raised with the partition index and the position of
+    /// the item within its partition, which also applies to `map`, `filter` and friends.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3usize]);
+    ///   let new = col.emit(|item, emitter| {
+    ///     if item % 2 == 0 {
+    ///         emitter(format!("{}!", item));
+    ///     }
+    ///   });
+    ///   assert_eq!(new.run(&GreedyScheduler::new()), Some(vec!["2!".into()]));
+    /// ```
+    pub fn emit<
+        B: Any + Send + Sync + Clone + Serialize,
+        F: 'static + Sync + Send + Clone + Fn(&A, &mut FnMut(B) -> ())
+    >(&self, f: F) -> DiskCollection<B> {
+
+        let parts = emit(&self.partitions, Disk(self.path.clone()), f);
+
+        self.from_defs(parts).named("emit")
+    }
+
+    /// Maps each item to zero or more new values, passing in the item's position within
+    /// its partition alongside the item itself.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec!["a".to_owned(), "b".into(), "c".into()]);
+    ///   let tagged = col.flat_map_with_index(|idx, item| {
+    ///     vec![format!("{}-{}", idx, item); idx]
+    ///   });
+    ///   assert_eq!(tagged.run(&GreedyScheduler::new()), 
+    ///     Some(vec!["1-b".into(), "2-c".into(), "2-c".into()]));
+    /// ```
+    pub fn flat_map_with_index<
+        B: Any + Send + Sync + Clone + Serialize,
+        I: IntoIterator<Item=B>,
+        F: 'static + Sync + Send + Clone + Fn(usize, &A) -> I
+    >(&self, f: F) -> DiskCollection<B> {
+        let parts = flat_map_indexed(&self.partitions, Disk(self.path.clone()), f);
+
+        self.from_defs(parts).named("flat_map_with_index")
+    }
+
+    /// Pairs every item with its index within the whole collection, in partition order.
+    /// The length of every partition is computed before any items are indexed.  Empty 
+    /// partitions are skipped over rather than taking up indices, so the indices are 
+    /// always contiguous from zero.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![10, 20usize])
+    ///     .concat(&DiskCollection::from_vec("/tmp".into(), vec![30usize]).filter(|_| false))
+    ///     .concat(&DiskCollection::from_vec("/tmp".into(), vec![40usize]));
+    ///   let indexed = col.zip_with_index_skipping_empty();
+    ///   assert_eq!(indexed.n_partitions(), 3);
+    ///   assert_eq!(indexed.run(&GreedyScheduler::new()), Some(vec![(0, 10), (1, 20), (2, 40)]));
+    /// ```
+    pub fn zip_with_index_skipping_empty(&self) -> DiskCollection<(usize, A)> {
+        let parts = zip_with_index(&self.partitions, Disk(self.path.clone()));
+        self.from_defs(parts).named("zip_with_index")
+    }
+
+    /// Re-partitions data into N new partitions by the given function.  The user provided
+    /// function is used as a hash function, mapping the returned value to a partition index.
+    /// This makes it useful for managing which partition data ends up!  Indices outside of
+    /// `0..partitions` wrap around modulo the partition count, so `partitions` itself maps
+    /// to partition 0.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4usize]);
+    ///   let new_col = col.partition(2, |idx, x| if *x < 3 { 1 } else { 2 });
+    ///   
+    ///   assert_eq!(new_col.n_partitions(), 2);
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![3, 4, 1, 2]));
+    /// ```
+
+    pub fn partition<
+        F: 'static + Sync + Send + Clone + Fn(usize, &A) -> usize
+    >(&self, partitions: usize, f: F) -> DiskCollection<A> {
+        check_partitions("DiskCollection::partition", partitions);
+        let new_chunks = partition(&self.partitions, 
+                                   partitions, 
                                    f);
         // Loop over each bucket
         self.from_defs(new_chunks)
     }
 
+    /// Tags each item with the partition `partition` would send it to, without moving
+    /// any data.  This makes it cheap to inspect a partitioning function before running
+    /// the full shuffle.  Targets wrap modulo `partitions`, as they do in `partition`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4usize]);
+    ///   let tagged = col.assign_partitions(2, |_idx, x| if *x < 3 { 1 } else { 2 });
+    ///   
+    ///   assert_eq!(tagged.n_partitions(), 1);
+    ///   assert_eq!(tagged.run(&GreedyScheduler::new()), 
+    ///     Some(vec![(1, 1), (1, 2), (0, 3), (0, 4)]));
+    /// ```
+    pub fn assign_partitions<
+        F: 'static + Sync + Send + Clone + Fn(usize, &A) -> usize
+    >(&self, partitions: usize, f: F) -> DiskCollection<(usize, A)> {
+        check_partitions("DiskCollection::assign_partitions", partitions);
+        self.flat_map_with_index(move |idx, x| Some((f(idx, x) % partitions, x.clone())))
+            .named("assign_partitions")
+    }
+
+    /// Tags each item with the index of the partition it is in and the partition that
+    /// `partition` would send it to, as `(source, target, item)`, without moving any data.
+    /// This makes it possible to audit how a partitioning function moves data between 
+    /// partitions.  Targets wrap modulo `partitions`, as they do in `partition`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4usize]).split(2);
+    ///   let traced = col.trace_shuffle(2, |_idx, x| x / 3);
+    ///   
+    ///   assert_eq!(traced.n_partitions(), 2);
+    ///   assert_eq!(traced.run(&GreedyScheduler::new()), 
+    ///     Some(vec![(0, 0, 1), (0, 1, 3), (1, 0, 2), (1, 1, 4)]));
+    /// ```
+    pub fn trace_shuffle<
+        F: 'static + Sync + Send + Clone + Fn(usize, &A) -> usize
+    >(&self, partitions: usize, f: F) -> DiskCollection<(usize, usize, A)> {
+        check_partitions("DiskCollection::trace_shuffle", partitions);
+        let acc = Disk(self.path.clone());
+        let parts = batch_apply(&self.partitions, move |idx, vs| {
+            let mut out = acc.writer();
+            for (i, x) in vs.stream().into_iter().enumerate() {
+                out.add((idx, f(i, &x) % partitions, x));
+            }
+            out.finish()
+        });
+        self.from_defs(parts).named("trace_shuffle")
+    }
+
+    /// Re-partitions data into N new partitions by hashing the provided key into the
+    /// valid range of partitions.  Unlike `partition_by_key`, the key only needs to be
+    /// hashable.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4usize]);
+    ///   let new_col = col.partition_by_hash(2, |x| x % 2);
+    ///   
+    ///   assert_eq!(new_col.n_partitions(), 2);
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()).map(|v| v.len()), Some(4));
+    /// ```
+    pub fn partition_by_hash<
+        K: Hash,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, partitions: usize, key: F) -> DiskCollection<A> {
+        self.partition(partitions, move |_idx, x| hash_key(&key(x)))
+    }
+
+    /// Re-partitions data using the provided Partitioner, which determines both the 
+    /// number of new partitions and where each item ends up.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::partition::RangePartitioner;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 15, 7, 30usize]);
+    ///   let ranged = col.partition_with(RangePartitioner::new(vec![10, 20], |x: &usize| *x));
+    ///   
+    ///   assert_eq!(ranged.n_partitions(), 3);
+    ///   assert_eq!(ranged.run(&GreedyScheduler::new()), Some(vec![1, 7, 15, 30]));
+    /// ```
+    pub fn partition_with<
+        P: 'static + Sync + Send + Clone + Partitioner<A>
+    >(&self, p: P) -> DiskCollection<A> {
+        let n = p.num_partitions();
+        self.partition(n, move |_idx, x| p.partition_of(x))
+    }
+
+    /// Re-partitions data into N new partitions by placing the hash of the provided key 
+    /// on a consistent-hashing ring.  Changing the number of partitions by one only moves
+    /// about `1 / N` of the keys between partitions, which keeps partitions stable across
+    /// runs where the partition count changes.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4,5,6usize]);
+    ///   let new_col = col.partition_consistent(3, |x| x % 2);
+    ///   
+    ///   assert_eq!(new_col.n_partitions(), 3);
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()).map(|v| v.len()), Some(6));
+    /// ```
+    pub fn partition_consistent<
+        K: Hash,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, partitions: usize, key: F) -> DiskCollection<A> {
+        check_partitions("DiskCollection::partition_consistent", partitions);
+        self.partition_with(ConsistentHashPartitioner::new(partitions, key))
+    }
+
     /// Folds and accumulates values across multiple partitions into K new partitions.
     /// This is also known as a "group by" with a following reducer.
     ///
@@ -265,162 +1114,1065 @@ impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskC
                    R: 'static + Sync + Send + Clone + Fn(&mut B, &B) -> ()>(
         &self, key: F, default: D, binop: O, reduce: R, partitions: usize
     ) -> DiskCollection<(K,B)> {
+        check_partitions("DiskCollection::fold_by", partitions);
         let fs = Arc::new(FileStore::empty(self.path.clone()));
         let results = fold_by(&self.partitions, key, default, binop, 
                               reduce, fs, partitions);
-        self.from_defs(results)
+        self.from_defs(results).named("fold_by")
+    }
+
+    /// Reduces all items sharing a key into a single item using `reduce`, yielding each
+    /// key with its reduced item.  Items are first reduced within each source partition,
+    /// so only one item per key and partition is shuffled; this greatly cuts down the data
+    /// moved for keys with few distinct values.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4, 5usize]).split(2);
+    ///   let maxes = col.reduce_by_key(|x| x % 2, |x, y| *x.max(y), 1).sort_by(|x| x.0);
+    ///   assert_eq!(maxes.run(&GreedyScheduler::new()), Some(vec![(0, 4), (1, 5)]));
+    /// ```
+    pub fn reduce_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        R: 'static + Sync + Send + Clone + Fn(&A, &A) -> A
+    >(&self, key: F, reduce: R, partitions: usize) -> DiskCollection<(K, A)> {
+        check_partitions("DiskCollection::reduce_by_key", partitions);
+        let results = reduce_by_key(&self.partitions, key, reduce, Arc::new(FileStore::empty(self.path.clone())), partitions);
+        self.from_defs(results).named("reduce_by_key")
+    }
+
+    /// Counts the number of distinct values for each key, such as the number of distinct
+    /// visitors per page.  Each source partition collects the set of distinct values per 
+    /// key, and the sets are merged after co-partitioning by key into `partitions` new
+    /// partitions.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let visits = DiskCollection::from_vec("/tmp".into(), vec![
+    ///     ("home".to_owned(), "ann".to_owned()), ("home".into(), "bob".into()), 
+    ///     ("home".into(), "ann".into()), ("about".into(), "ann".into())]);
+    ///   let visitors = visits.count_distinct_by_key(|x| x.0.clone(), |x| x.1.clone(), 1)
+    ///     .sort_by(|x| x.0.clone());
+    ///   assert_eq!(visitors.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("about".into(), 1), ("home".into(), 2)]));
+    /// ```
+    pub fn count_distinct_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        V: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        FK: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        FV: 'static + Sync + Send + Clone + Fn(&A) -> V
+    >(&self, key: FK, value: FV, partitions: usize) -> DiskCollection<(K, usize)> {
+        check_partitions("DiskCollection::count_distinct_by_key", partitions);
+        self.fold_by(key, 
+                     HashSet::new, 
+                     move |set, x| { set.insert(value(x)); }, 
+                     |set, other| {
+                         for v in other.iter() {
+                             set.insert(v.clone());
+                         }
+                     },
+                     partitions)
+            .map(|(k, set)| (k.clone(), set.len()))
+            .named("count_distinct_by_key")
+    }
+
+    /// Maps each item to a key and value, then reduces all values sharing a key into one
+    /// with `reduce`, co-partitioning by key into `partitions` new partitions.  This fuses
+    /// a `map` with `reduce_by_key`: values are reduced within each source partition as 
+    /// they are mapped, so the mapped pairs are never stored.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec!["a b a".to_owned(), "b c".into()]);
+    ///   let counts = col.emit(|line, emitter| {
+    ///       for word in line.split_whitespace() { emitter(word.to_owned()) }
+    ///     })
+    ///     .map_reduce_by_key(|word| (word.clone(), 1usize), |x, y| x + y, 1)
+    ///     .sort_by(|x| x.0.clone());
+    ///   assert_eq!(counts.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("a".into(), 2), ("b".into(), 2), ("c".into(), 1)]));
+    /// ```
+    pub fn map_reduce_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        V: Any + Sync + Send + Clone + Serialize + for<'de> Deserialize<'de>,
+        M: 'static + Sync + Send + Clone + Fn(&A) -> (K, V),
+        R: 'static + Sync + Send + Clone + Fn(&V, &V) -> V
+    >(&self, map: M, reduce: R, partitions: usize) -> DiskCollection<(K, V)> {
+        check_partitions("DiskCollection::map_reduce_by_key", partitions);
+        let results = map_reduce_by_key(&self.partitions, map, reduce, Arc::new(FileStore::empty(self.path.clone())), partitions);
+        self.from_defs(results).named("map_reduce_by_key")
+    }
+
+    /// Groups items by key, keeping the `k` items with the largest `sort_key` for each
+    /// key in descending order.  Only `k` items per key are held at any point, rather than
+    /// the full group.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![(1, 1), (2, 5), (1, 3), (1, 2)]);
+    ///   let top = col.top_k_by_key(2, |x| x.0, |x| x.1, 1).sort_by(|x| x.0);
+    ///   assert_eq!(top.run(&GreedyScheduler::new()), 
+    ///     Some(vec![(1, (1, 3)), (1, (1, 2)), (2, (2, 5))]));
+    /// ```
+    pub fn top_k_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        SK: Any + Sync + Send + Clone + Ord + Serialize + for<'de> Deserialize<'de>,
+        FK: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        FS: 'static + Sync + Send + Clone + Fn(&A) -> SK
+    >(&self, k: usize, key: FK, sort_key: FS, partitions: usize) -> DiskCollection<(K, A)> {
+        check_partitions("DiskCollection::top_k_by_key", partitions);
+        self.fold_by(key, move || TopK::new(k), 
+            move |top, x| top.push(sort_key(x), x.clone()),
+            |top, other| top.merge(other), partitions)
+            .map(|x| (x.0.clone(), x.1.sorted()))
+            .explode()
+            .named("top_k_by_key")
+    }
+
+    /// Computes `fold_by`, additionally sorting each output partition by key.  Keys are
+    /// only ordered within a partition; for a total order, fold into a single partition.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec!["b".to_owned(), "a".into(), "c".into(), "a".into(), "b".into(), "a".into()]).split(2);
+    ///   let counts = col.sorted_fold_by(|x| x.clone(), || 0usize, |acc, _x| *acc += 1, 
+    ///                                   |x, y| *x += *y, 1);
+    ///   assert_eq!(counts.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("a".into(), 3), ("b".into(), 2), ("c".into(), 1)]));
+    /// ```
+    pub fn sorted_fold_by<
+        K: Any + Sync + Send + Clone + Ord + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        B: Any + Sync + Send + Clone + Serialize + for<'de> Deserialize<'de>,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        D: 'static + Sync + Send + Clone + Fn() -> B,
+        O: 'static + Sync + Send + Clone + Fn(&mut B, &A),
+        R: 'static + Sync + Send + Clone + Fn(&mut B, &B)
+    >(
+        &self, key: F, default: D, binop: O, reduce: R, partitions: usize
+    ) -> DiskCollection<(K, B)> {
+        self.fold_by(key, default, binop, reduce, partitions)
+            .sort_by(|x| x.0.clone())
+    }
+
+    /// Aggregates items into non-overlapping buckets, such as fixed windows of time.  This
+    /// is `fold_by` keyed by the `bucket` function, with each resulting partition sorted by
+    /// bucket.  `utils::time_bucket` is a convenient bucketing function for timestamps.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::utils::time_bucket;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   // (timestamp, value) pairs
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![(3u64, 1), (12, 2), (17, 3), (5, 4)]);
+    ///   let sums = col.tumbling_reduce(|x| time_bucket(x.0, 10),
+    ///                                  || 0, |acc, x| *acc += x.1, |x, y| *x += *y, 1);
+    ///   assert_eq!(sums.run(&GreedyScheduler::new()), Some(vec![(0, 5), (10, 5)]));
+    /// ```
+    pub fn tumbling_reduce<
+        K: Any + Sync + Send + Clone + Ord + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        B: Any + Sync + Send + Clone + Serialize + for<'de> Deserialize<'de>,
+        FK: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        D: 'static + Sync + Send + Clone + Fn() -> B,
+        O: 'static + Sync + Send + Clone + Fn(&mut B, &A),
+        R: 'static + Sync + Send + Clone + Fn(&mut B, &B)
+    >(
+        &self, bucket: FK, default: D, binop: O, reduce: R, partitions: usize
+    ) -> DiskCollection<(K, B)> {
+        self.sorted_fold_by(bucket, default, binop, reduce, partitions)
+    }
+
+    /// Simple function to re-partition values by a given key.  The return key is hashed
+    /// and moduloed by the new partition count to determine where it will end up.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4usize]);
+    ///   let new_col = col.partition_by_key(2, |x| format!("{}", x));
+    ///   
+    ///   assert_eq!(new_col.n_partitions(), 2);
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![4, 1, 2, 3]));
+    /// ```
+
+    pub fn partition_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, n_chunks: usize, key: F) -> DiskCollection<A> {
+        check_partitions("DiskCollection::partition_by_key", n_chunks);
+        let results = partition_by_key(&self.partitions, n_chunks, key);
+        let groups = results.into_iter().map(|part| concat(&part).unwrap()).collect();
+        self.from_defs(groups).named("partition_by_key")
+    }
+
+    /// Re-partitions values by a given key like `partition_by_key`, then drops the empty
+    /// partitions, which are common when there are few distinct keys.  Finding them
+    /// requires a count pass over the re-partitioned collection, which is run right away
+    /// with a GreedyScheduler, like `eval`.  The returned collection is still lazy: it
+    /// holds the non-empty partitions of the re-partitioning, which are computed again
+    /// when it is run.  If the count pass fails, no partitions are dropped.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4usize]);
+    ///   let compact = col.partition_by_key_compact(10, |_x| 1);
+    ///   
+    ///   assert_eq!(compact.n_partitions(), 1);
+    ///   assert_eq!(compact.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 4]));
+    /// ```
+    pub fn partition_by_key_compact<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, n_chunks: usize, key: F) -> DiskCollection<A> {
+        let partitioned = self.partition_by_key(n_chunks, key);
+        let parts = non_empty(&partitioned.partitions, &GreedyScheduler::new());
+        self.from_defs(parts).named("partition_by_key_compact")
+    }
+
+    /// Re-partitions values by a given key and groups them, yielding each key alongside
+    /// all of its values.  This fuses `partition_by_key` with a per-partition grouping:
+    /// chunks are grouped locally and groups are merged as the chunks are concatenated,
+    /// saving a layer of nodes in the graph.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4, 5usize]).split(2);
+    ///   let groups = col.group_shuffle(2, |x| x % 2);
+    ///   let mut results = groups.run(&GreedyScheduler::new()).unwrap();
+    ///   results.sort();
+    ///   assert_eq!(results, vec![(0, vec![2, 4]), (1, vec![1, 3, 5])]);
+    /// ```
+    pub fn group_shuffle<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, partitions: usize, key: F) -> DiskCollection<(K, Vec<A>)> {
+        check_partitions("DiskCollection::group_shuffle", partitions);
+        let groups = group_shuffle(&self.partitions, partitions, key, Disk(self.path.clone()));
+        self.from_defs(groups).named("group_shuffle")
+    }
+
+    /// Re-partitions values by a given key and groups them, like `group_shuffle`, but 
+    /// never yields a group with more than `max_group` values.  Keys with more values are
+    /// yielded several times, each with a chunk of their values, so that a single large 
+    /// key can't exhaust memory.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4, 5, 7usize]).split(2);
+    ///   let groups = col.group_by_key_capped(1, |x| x % 2, 2);
+    ///   let mut sizes: Vec<_> = groups.run(&GreedyScheduler::new()).unwrap()
+    ///     .into_iter().map(|(k, vs)| (k, vs.len())).collect();
+    ///   sizes.sort();
+    ///   assert_eq!(sizes, vec![(0, 2), (1, 2), (1, 2)]);
+    /// ```
+    pub fn group_by_key_capped<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, partitions: usize, key: F, max_group: usize) -> DiskCollection<(K, Vec<A>)> {
+        check_partitions("DiskCollection::group_by_key_capped", partitions);
+        assert!(max_group > 0, "DiskCollection::group_by_key_capped requires groups of at least one value");
+        let parted = self.partition_by_key(partitions, key.clone());
+        let groups = group_capped(&parted.partitions, key, max_group, Arc::new(FileStore::empty(self.path.clone())));
+        self.from_defs(groups).named("group_by_key_capped")
+    }
+
+    /// Re-partitions values such that all values sharing a key stay together, while
+    /// balancing the partition sizes.  Unlike `partition_by_key`, keys aren't hashed into
+    /// partitions: the number of values per key is counted first, and keys are then placed
+    /// largest first onto the partition holding the fewest values so far.  This is useful
+    /// for skewed keys, at the cost of an additional pass to count keys.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 1, 1, 1, 2, 2, 3, 4usize]).split(3);
+    ///   let balanced = col.rebalance_by_key(2, |x| *x);
+    ///   let sizes: Vec<_> = balanced.to_memory().to_defs().iter()
+    ///     .map(|d| d.run(&GreedyScheduler::new()).unwrap().len())
+    ///     .collect();
+    ///   assert_eq!(sizes, vec![4, 4]);
+    /// ```
+    pub fn rebalance_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, partitions: usize, key: F) -> DiskCollection<A> {
+        check_partitions("DiskCollection::rebalance_by_key", partitions);
+        let parts = rebalance_by_key(&self.partitions, partitions, key);
+        self.from_defs(parts).named("rebalance_by_key")
+    }
+
+    /// Re-partitions values by a given key, like `partition_by_key`, but keeps the computed
+    /// key attached to each value.  This saves recomputing the key downstream.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4usize]);
+    ///   let new_col = col.partition_by_key_keyed(2, |x| format!("{}", x));
+    ///   
+    ///   assert_eq!(new_col.n_partitions(), 2);
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("4".into(), 4), ("1".into(), 1), ("2".into(), 2), ("3".into(), 3)]));
+    /// ```
+    pub fn partition_by_key_keyed<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, n_chunks: usize, key: F) -> DiskCollection<(K, A)> {
+        check_partitions("DiskCollection::partition_by_key_keyed", n_chunks);
+        self.key_by(key).partition_by_key(n_chunks, |x| x.0.clone())
+    }
+
+    /// Removes items with duplicate keys, co-partitioning by key into `partitions` new
+    /// partitions.  For each key, the first item in collection order is kept.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![(1, 1.), (2, 2.), (1, 3.)]);
+    ///   let unique = col.distinct_by(|x| x.0, 1);
+    ///   assert_eq!(unique.run(&GreedyScheduler::new()), Some(vec![(1, 1.), (2, 2.)]));
+    /// ```
+    pub fn distinct_by<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, key: F, partitions: usize) -> DiskCollection<A> {
+        check_partitions("DiskCollection::distinct_by", partitions);
+        let parted = self.partition_by_key(partitions, key.clone());
+        let new_parts = first_by_key(&parted.partitions, key, Arc::new(FileStore::empty(self.path.clone())));
+        self.from_defs(new_parts).named("distinct_by")
+    }
+
+    /// Samples exactly `min(n, total)` items uniformly at random, returning them in a
+    /// single partition.  Each partition keeps a reservoir of `n` candidates, which are
+    /// then merged.  The sample is deterministic for a given `seed` and partitioning.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), (0..100usize).collect()).split(4);
+    ///   let sample = col.sample_n(10, 42).run(&GreedyScheduler::new()).unwrap();
+    ///   assert_eq!(sample.len(), 10);
+    /// ```
+    pub fn sample_n(&self, n: usize, seed: u64) -> DiskCollection<A> {
+        let acc = Arc::new(FileStore::empty(self.path.clone()));
+        let sample = reservoir(&self.partitions, n, seed).apply(move |vs| acc.write_vec(vs.clone()));
+        self.from_defs(vec![sample]).named("sample_n")
+    }
+
+    /// Randomly reorders the collection into `partitions` new partitions.  Items are
+    /// scattered into random partitions and each new partition is then shuffled, drawing
+    /// from generators seeded by `seed`; the same seed and input partitioning always yield
+    /// the same order.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), (0..100usize).collect()).split(4);
+    ///   let mut shuffled = col.shuffle(42, 3).run(&GreedyScheduler::new()).unwrap();
+    ///   assert_ne!(shuffled, (0..100usize).collect::<Vec<_>>());
+    ///   shuffled.sort();
+    ///   assert_eq!(shuffled, (0..100usize).collect::<Vec<_>>());
+    /// ```
+    pub fn shuffle(&self, seed: u64, partitions: usize) -> DiskCollection<A> {
+        check_partitions("DiskCollection::shuffle", partitions);
+        self.from_defs(shuffle(&self.partitions, partitions, seed)).named("shuffle")
+    }
+
+    /// Sorts values within each partition by a key function.  If a global sort is desired,
+    /// the collection needs to be re-partitioned into a single partition
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4i32]);
+    ///   let new_col = col.sort_by(|x| -*x);
+    ///   
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![4, 3, 2, 1]));
+    /// ```
+pub fn sort_by<
+        K: Ord,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, key: F) -> DiskCollection<A> {
+        let acc = Arc::new(FileStore::empty(self.path.clone()));
+        let nps = batch_apply(&self.partitions, move |_idx, vs| {
+            let mut out = acc.writer();
+            let mut v2: Vec<_> = vs.stream().into_iter().collect();
+            v2.sort_by_key(|v| key(v));
+            for vi in v2 {
+                out.add(vi);
+            }
+            out.finish()
+        });
+        self.from_defs(nps).named("sort_by")
+    }
+
+    /// Sorts each partition by the provided key, then merges the sorted partitions
+    /// pairwise into a single, globally sorted Vec.  Ties keep their partition order.
+    /// This is best suited for small results, as the final merge is held in memory.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![5, 1, 4, 2, 3usize]).split(2);
+    ///   let sorted = col.sorted(|x| *x);
+    ///   assert_eq!(sorted.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 4, 5]));
+    /// ```
+    pub fn sorted<
+        K: Ord,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, key: F) -> Deferred<Vec<A>> {
+        merge_sorted(&self.partitions, key)
+    }
+
+    /// Passes items through unchanged, panicking at evaluation time if any partition is
+    /// not ordered by the provided key.  This is useful for checking custom key functions
+    /// used with `sort_by`.  The panic reports the partition index and offending keys.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![3, 1, 2usize]).sort_by(|x| *x);
+    ///   let checked = col.assert_partitions_sorted(|x| *x);
+    ///   assert_eq!(checked.run(&GreedyScheduler::new()), Some(vec![1, 2, 3]));
+    /// ```
+    pub fn assert_partitions_sorted<
+        K: Ord + Debug,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, key: F) -> DiskCollection<A> {
+        let nps = batch_apply(&self.partitions, move |idx, vs| {
+            let mut prev = None;
+            for (i, v) in vs.stream().into_iter().enumerate() {
+                let cur = key(&v);
+                if let Some(left) = prev {
+                    if left > cur {
+                        panic!("Partition {} is out of order at position {}: {:?} followed by {:?}",
+                               idx, i, left, cur);
+                    }
+                }
+                prev = Some(cur);
+            }
+            vs.clone()
+        });
+        self.from_defs(nps).named("assert_partitions_sorted")
+    }
+
+    /// Inner Joins two collections by the provided key function.
+    /// If multiple values of the same key are found, they will be cross product for each
+    /// pair found.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   let name_age: Vec<(String,u32)> = vec![("Andrew".into(), 33), ("Leah".into(), 12)];
+    ///   let name_money: Vec<(String,f32)> = vec![("Leah".into(), 20.50)];
+    ///   
+    ///   let na = DiskCollection::from_vec("/tmp".into(), name_age);
+    ///   let nm = DiskCollection::from_vec("/tmp".into(), name_money);
+    ///   let joined = na.join_on(&nm,
+    ///                           |nax| nax.0.clone(),
+    ///                           |nmx| nmx.0.clone(),
+    ///                           |nax, nmx| (nax.0.clone(), nax.1, nmx.1),
+    ///                           1);
+    ///   assert_eq!(joined.run(&GreedyScheduler::new()), 
+    ///           Some(vec![("Leah".into(), ("Leah".into(), 12, 20.50))]));
+    /// ```
+    pub fn join_on<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        B: Any + Sync + Send + Clone + Serialize + for<'de> Deserialize<'de>,
+        C: Any + Sync + Send + Clone + Serialize,
+        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
+        J:   'static + Sync + Send + Clone + Fn(&A, &B) -> C,
+    >(
+        &self, 
+        other: &DiskCollection<B>, 
+        key1: KF1, 
+        key2: KF2,
+        joiner: J,
+        partitions: usize, 
+    ) -> DiskCollection<(K,C)> {
+        // Group each by a common key
+        let p1 = self.map(move |x| (key1(x), x.clone()))
+            .partition_by_key(partitions, |x| x.0.clone());
+        let p2 = other.map(move |x| (key2(x), x.clone()))
+            .partition_by_key(partitions, |x| x.0.clone());
+
+        let mut new_parts = Vec::with_capacity(p1.partitions.len());
+        for (l, r) in p1.partitions.iter().zip(p2.partitions.iter()) {
+            let acc = Arc::new(FileStore::empty(self.path.clone()));
+            new_parts.push(jok(l, r, acc, joiner.clone()));
+        }
+
+        self.from_defs(new_parts).named("join_on")
+    }
+
+    /// Left Joins two collections by the provided key functions.  Every item of this
+    /// collection is paired with each item of `other` sharing its key, or with `default()`
+    /// when there are none, so no items from this collection are dropped.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///
+    ///   let name_age = DiskCollection::from_vec("/tmp".into(), vec![("Andrew".to_owned(), 33), ("Leah".into(), 12)]);
+    ///   let name_money = DiskCollection::from_vec("/tmp".into(), vec![("Leah".to_owned(), 20.5)]);
+    ///   let joined = name_age.left_join_or(&name_money, |x| x.0.clone(), |x| x.0.clone(), 
+    ///                                      || ("".into(), 0.0), 1)
+    ///     .map(|(name, (age, money))| (name.clone(), age.1, money.1))
+    ///     .sort_by(|x| x.0.clone());
+    ///   assert_eq!(joined.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("Andrew".into(), 33, 0.0), ("Leah".into(), 12, 20.5)]));
+    /// ```
+    pub fn left_join_or<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        B: Any + Sync + Send + Clone + Serialize + for<'de> Deserialize<'de>,
+        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
+        D:   'static + Sync + Send + Clone + Fn() -> B,
+    >(
+        &self, 
+        other: &DiskCollection<B>, 
+        key1: KF1, 
+        key2: KF2,
+        default: D,
+        partitions: usize, 
+    ) -> DiskCollection<(K, (A, B))> {
+        check_partitions("DiskCollection::left_join_or", partitions);
+        let p1 = self.map(move |x| (key1(x), x.clone()))
+            .partition_by_key(partitions, |x| x.0.clone());
+        let p2 = other.map(move |x| (key2(x), x.clone()))
+            .partition_by_key(partitions, |x| x.0.clone());
+
+        let mut new_parts = Vec::with_capacity(p1.partitions.len());
+        for (l, r) in p1.partitions.iter().zip(p2.partitions.iter()) {
+            new_parts.push(left_join_on_key(l, r, Arc::new(FileStore::empty(self.path.clone())), default.clone()));
+        }
+
+        self.from_defs(new_parts).named("left_join_or")
+    }
+
+    /// Anti Joins two collections by the provided key functions, keeping only the items
+    /// whose key is not present in the other collection.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///
+    ///   let left = DiskCollection::from_vec("/tmp".into(), vec![(1, "x".to_owned()), (2, "y".into())]);
+    ///   let right = DiskCollection::from_vec("/tmp".into(), vec![1]);
+    ///   let missing = left.anti_join(&right, |l| l.0, |r| *r, 2);
+    ///   assert_eq!(missing.run(&GreedyScheduler::new()), Some(vec![(2, "y".into())]));
+    /// ```
+    pub fn anti_join<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        B: Any + Sync + Send + Clone + Serialize + for<'de> Deserialize<'de>,
+        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
+    >(
+        &self, 
+        other: &DiskCollection<B>, 
+        key1: KF1, 
+        key2: KF2,
+        partitions: usize, 
+    ) -> DiskCollection<A> {
+        check_partitions("DiskCollection::anti_join", partitions);
+        self.filter_on_keys(other, key1, key2, partitions, false)
+    }
+
+    /// Semi Joins two collections by the provided key functions, keeping only the items
+    /// whose key is present in the other collection.  Each item is emitted once, regardless
+    /// of how many matches it has, and no data from the other collection is included.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///
+    ///   let left = DiskCollection::from_vec("/tmp".into(), vec![(1, "x".to_owned()), (2, "y".into())]);
+    ///   let right = DiskCollection::from_vec("/tmp".into(), vec![1, 1]);
+    ///   let matched = left.semi_join(&right, |l| l.0, |r| *r, 2);
+    ///   assert_eq!(matched.run(&GreedyScheduler::new()), Some(vec![(1, "x".into())]));
+    /// ```
+    pub fn semi_join<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        B: Any + Sync + Send + Clone + Serialize + for<'de> Deserialize<'de>,
+        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
+    >(
+        &self, 
+        other: &DiskCollection<B>, 
+        key1: KF1, 
+        key2: KF2,
+        partitions: usize, 
+    ) -> DiskCollection<A> {
+        check_partitions("DiskCollection::semi_join", partitions);
+        self.filter_on_keys(other, key1, key2, partitions, true)
+    }
+
+    // Co-partitions both collections by key, keeping the items from this collection 
+    // whose key presence in the other matches `keep_matched`.
+    fn filter_on_keys<
+        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
+        B: Any + Sync + Send + Clone + Serialize + for<'de> Deserialize<'de>,
+        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
+    >(
+        &self, 
+        other: &DiskCollection<B>, 
+        key1: KF1, 
+        key2: KF2,
+        partitions: usize, 
+        keep_matched: bool
+    ) -> DiskCollection<A> {
+        let p1 = self.key_by(key1).partition_by_key(partitions, |x| x.0.clone());
+        let p2 = other.map(key2).partition_by_key(partitions, |k| k.clone());
+
+        let mut new_parts = Vec::with_capacity(p1.partitions.len());
+        for (l, r) in p1.partitions.iter().zip(p2.partitions.iter()) {
+            new_parts.push(filter_on_keys(l, r, Arc::new(FileStore::empty(self.path.clone())), keep_matched));
+        }
+
+        self.from_defs(new_parts)
+    }
+
+    /// Builds a Bloom filter over the keys of the collection, using `n_bits` bits and
+    /// `n_hashes` hash functions.  Each partition builds its own filter, which are then 
+    /// unioned together.  The resulting filter is useful for cheaply pre-filtering another
+    /// collection before a join with `filter_by_bloom`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3usize]);
+    ///   let bf = col.build_bloom(|x| *x, 1024, 3).run(&GreedyScheduler::new()).unwrap();
+    ///   assert!(bf.contains(&2usize));
+    /// ```
+    pub fn build_bloom<
+        K: Hash,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, key: F, n_bits: usize, n_hashes: usize) -> Deferred<BloomFilter> {
+        bloom(&self.partitions, key, n_bits, n_hashes)
+    }
+
+    /// Keeps only the items whose key might be a member of the provided Bloom filter.  
+    /// Items whose key was inserted into the filter are always kept; due to false 
+    /// positives, some items whose key was not inserted may be kept as well.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3usize]);
+    ///   let bf = col.build_bloom(|x| *x, 1024, 3);
+    ///   let other = DiskCollection::from_vec("/tmp".into(), vec![2, 3, 4, 5usize]);
+    ///   let kept = other.filter_by_bloom(&bf, |x| *x).run(&GreedyScheduler::new()).unwrap();
+    ///   assert!(kept.contains(&2) && kept.contains(&3));
+    /// ```
+    pub fn filter_by_bloom<
+        K: Hash,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, bf: &Deferred<BloomFilter>, key: F) -> DiskCollection<A> {
+        let parts = filter_by_bloom(&self.partitions, bf, key);
+        self.from_defs(parts).named("filter_by_bloom")
+    }
+
+    /// Maps each item with access to a shared context, such as a lookup table, computed
+    /// once by another Deferred.  Every partition is joined against the same context.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3usize]).split(2);
+    ///   let offset = col.len().apply(|n| *n * 10);
+    ///   let shifted = col.map_with_broadcast(&offset, |o, x| o + x);
+    ///   assert_eq!(shifted.run(&GreedyScheduler::new()), Some(vec![31, 33, 32]));
+    /// ```
+    pub fn map_with_broadcast<
+        B: Any + Send + Sync + Clone + Serialize,
+        C: Any + Send + Sync + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&C, &A) -> B
+    >(&self, ctx: &Deferred<C>, f: F) -> DiskCollection<B> {
+        let parts = map_with_broadcast(&self.partitions, ctx, Disk(self.path.clone()), f);
+        self.from_defs(parts).named("map_with_broadcast")
+    }
+
+    /// Executes the Collection, returning the result of the computation
+    pub fn run<S: Scheduler>(&self, s: &S) -> Option<Vec<A>> {
+        let defs = batch_apply(&self.partitions, |_idx, vs| {
+            vs.stream().into_iter().collect::<Vec<_>>()
+        });
+        let cat = tree_reduce(&defs, |x, y| {
+            let mut v1: Vec<_> = (*x).clone();
+            for yi in y {
+                v1.push(yi.clone());
+            }
+            v1
+        });
+        cat.and_then(|x| x.run(s))
+    }
+
+    /// Executes the Collection like `run`, but concatenates partitions strictly left to
+    /// right, guaranteeing that the items of each partition follow those of all earlier
+    /// partitions.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), (0..20usize).collect()).split(5).sort_by(|x| *x);
+    ///   let out = col.run_ordered(&GreedyScheduler::new()).unwrap();
+    ///   assert_eq!(&out[..4], &[0, 5, 10, 15]);
+    /// ```
+    pub fn run_ordered<S: Scheduler>(&self, s: &S) -> Option<Vec<A>> {
+        let defs = batch_apply(&self.partitions, |_idx, vs| {
+            vs.stream().into_iter().collect::<Vec<_>>()
+        });
+        let cat = tree_reduce_ordered(&defs, |x, y| {
+            let mut v1: Vec<_> = (*x).clone();
+            for yi in y {
+                v1.push(yi.clone());
+            }
+            v1
+        });
+        cat.and_then(|x| x.run(s))
+    }
+    
+    /// Computes the collection on a background thread, sending each partition down the
+    /// returned channel as soon as it is computed.  Partitions arrive in the order they
+    /// finish, not partition order.  The whole collection is computed in a single run, so
+    /// shared upstream work, such as the map side of a shuffle, is only done once.  The
+    /// channel is closed when the run finishes, or early if a task fails.  Joining the
+    /// returned handle tells the two apart: it returns `Err` if a task panicked, and
+    /// `Ok(None)` if the scheduler couldn't compute the collection.
+    ///
+    /// The scheduler is taken by value, rather than borrowed, because it moves to the
+    /// background thread and must outlive the call.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4usize]).split(2);
+    ///   let (rx, handle) = col.run_to_channel(GreedyScheduler::new());
+    ///   let mut parts: Vec<_> = rx.iter().collect();
+    ///   parts.sort();
+    ///   assert_eq!(parts, vec![vec![1, 3], vec![2, 4]]);
+    ///   assert_eq!(handle.join().unwrap(), Some(()));
+    /// ```
+    pub fn run_to_channel<S: 'static + Scheduler + Send>(&self, s: S) -> (mpsc::Receiver<Vec<A>>, thread::JoinHandle<Option<()>>) {
+        run_to_channel(self.partitions.clone(), s)
+    }
+
+    /// Returns up to the first `n` items of the collection, in partition order.  Partitions
+    /// are computed one at a time and computation stops once `n` items are collected, so
+    /// later partitions are never computed.  This is handy for previewing results.  As each
+    /// partition is run separately, work shared between partitions is repeated for every
+    /// partition computed; after a shuffle, such as `partition_by_key`, each one reruns
+    /// the map side of the shuffle.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4, 5usize]).split(3);
+    ///   assert_eq!(col.first(3, &GreedyScheduler::new()), vec![1, 4, 2]);
+    /// ```
+    pub fn first<S: Scheduler>(&self, n: usize, s: &S) -> Vec<A> {
+        first_n(&self.partitions, n, s)
+    }
+
+    /// Executes the Collection, returning its items in a single Vec for random access by
+    /// global index.  This is the same as `run`; see `collect_with_offsets` to also
+    /// recover the partition boundaries.
+    pub fn collect_indexed<S: Scheduler>(&self, s: &S) -> Option<Vec<A>> {
+        self.run(s)
+    }
+
+    /// Executes the Collection, returning its items in a single Vec alongside the offsets
+    /// of the partition boundaries.  There is one more offset than partitions: partition
+    /// `i` spans `items[offsets[i]..offsets[i + 1]]`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4, 5usize]).split(2);
+    ///   let (items, offsets) = col.collect_with_offsets(&GreedyScheduler::new()).unwrap();
+    ///   assert_eq!(items, vec![1, 3, 5, 2, 4]);
+    ///   assert_eq!(offsets, vec![0, 3, 5]);
+    ///   assert_eq!(&items[offsets[1]..offsets[2]], &[2, 4]);
+    /// ```
+    pub fn collect_with_offsets<S: Scheduler>(&self, s: &S) -> Option<(Vec<A>, Vec<usize>)> {
+        with_offsets(&self.partitions).run(s)
+    }
+
+    /// Executes the Collection, returning the result of the computation
+    pub fn eval(&self) -> Option<Vec<A>> {
+        self.run(&GreedyScheduler::new())
+    }
+
+}
+
+impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskCollection<Vec<A>> {
+    /// Flattens a vector of values
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![vec![1usize,2],vec![3,4]]);
+    ///   let flattened = col.flatten();
+    ///   assert_eq!(flattened.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 4]));
+    /// ```
+    pub fn flatten(&self) -> DiskCollection<A> {
+        self.emit(move |x, emitter| {
+            for xi in x {
+                emitter(xi.clone());
+            }
+        }).named("flatten")
+    }
+}
+
+impl <K: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>,
+      V: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskCollection<(K, V)> {
+
+    /// Drops the keys from a keyed collection, returning only the values.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![("a".to_owned(), 1), ("b".into(), 2)]);
+    ///   assert_eq!(col.values().run(&GreedyScheduler::new()), Some(vec![1, 2]));
+    /// ```
+    pub fn values(&self) -> DiskCollection<V> {
+        self.map(|x| x.1.clone())
+    }
+
+    /// Alias for `values`, reading more naturally after a `key_by`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![("a".to_owned(), 1), ("b".into(), 2)]);
+    ///   assert_eq!(col.unkey().run(&GreedyScheduler::new()), Some(vec![1, 2]));
+    /// ```
+    pub fn unkey(&self) -> DiskCollection<V> {
+        self.values()
     }
 
-    /// Simple function to re-partition values by a given key.  The return key is hashed
-    /// and moduloed by the new partition count to determine where it will end up.
+    /// Flips the key and value of each item.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::disk::DiskCollection;
     ///   
-    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4usize]);
-    ///   let new_col = col.partition_by_key(2, |x| format!("{}", x));
-    ///   
-    ///   assert_eq!(new_col.n_partitions(), 2);
-    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![4, 1, 2, 3]));
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![("a".to_owned(), 1), ("b".into(), 2)]);
+    ///   assert_eq!(col.swap().run(&GreedyScheduler::new()), 
+    ///     Some(vec![(1, "a".into()), (2, "b".into())]));
     /// ```
-
-    pub fn partition_by_key<
-        K: Any + Sync + Send + Clone + Hash + Eq,
-        F: 'static + Sync + Send + Clone + Fn(&A) -> K
-    >(&self, n_chunks: usize, key: F) -> DiskCollection<A> {
-        let results = partition_by_key(&self.partitions, n_chunks, key);
-        let groups = results.into_iter().map(|part| concat(&part).unwrap()).collect();
-        self.from_defs(groups)
+    pub fn swap(&self) -> DiskCollection<(V, K)> {
+        self.map(|x| (x.1.clone(), x.0.clone()))
     }
 
-    /// Sorts values within each partition by a key function.  If a global sort is desired,
-    /// the collection needs to be re-partitioned into a single partition
+    /// Gathers the keys and values of the collection into two parallel Vecs, a columnar
+    /// layout of the pairs.  Partitions are concatenated in order, so position `i` of both
+    /// columns comes from the same pair.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::disk::DiskCollection;
     ///   
-    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1,2,3,4i32]);
-    ///   let new_col = col.sort_by(|x| -*x);
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![("a".to_owned(), 1), ("b".into(), 2)]);
+    ///   assert_eq!(col.to_columns().run(&GreedyScheduler::new()), 
+    ///     Some((vec!["a".into(), "b".into()], vec![1, 2])));
+    /// ```
+    pub fn to_columns(&self) -> Deferred<(Vec<K>, Vec<V>)> {
+        to_columns(&self.partitions)
+    }
+}
+
+impl <K: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>,
+      V: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskCollection<(K, Vec<V>)> {
+
+    /// Emits a `(key, value)` pair for every value in each group, turning grouped
+    /// collections back into rows.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
     ///   
-    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![4, 3, 2, 1]));
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![("a".to_owned(), vec![1, 2])]);
+    ///   assert_eq!(col.explode().run(&GreedyScheduler::new()), 
+    ///     Some(vec![("a".into(), 1), ("a".into(), 2)]));
     /// ```
-pub fn sort_by<
-        K: Ord,
-        F: 'static + Sync + Send + Clone + Fn(&A) -> K
-    >(&self, key: F) -> DiskCollection<A> {
-        let acc = Arc::new(FileStore::empty(self.path.clone()));
-        let nps = batch_apply(&self.partitions, move |_idx, vs| {
-            let mut out = acc.writer();
-            let mut v2: Vec<_> = vs.stream().into_iter().collect();
-            v2.sort_by_key(|v| key(v));
-            for vi in v2 {
-                out.add(vi);
+    pub fn explode(&self) -> DiskCollection<(K, V)> {
+        self.emit(|x, emitter| {
+            for v in x.1.iter() {
+                emitter((x.0.clone(), v.clone()));
             }
-            out.finish()
-        });
-        self.from_defs(nps)
+        }).named("explode")
     }
+}
 
-    /// Inner Joins two collections by the provided key function.
-    /// If multiple values of the same key are found, they will be cross product for each
-    /// pair found.
+impl <K: Any + Send + Sync + Clone + Hash + Eq + Serialize + for<'de>Deserialize<'de>,
+      V: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskCollection<(K, V)> {
+
+    /// Executes the Collection, collecting the key/value pairs into a HashMap.  When a 
+    /// key occurs multiple times, the last value in collection order wins.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::disk::DiskCollection;
-    ///   let name_age: Vec<(String,u32)> = vec![("Andrew".into(), 33), ("Leah".into(), 12)];
-    ///   let name_money: Vec<(String,f32)> = vec![("Leah".into(), 20.50)];
     ///   
-    ///   let na = DiskCollection::from_vec("/tmp".into(), name_age);
-    ///   let nm = DiskCollection::from_vec("/tmp".into(), name_money);
-    ///   let joined = na.join_on(&nm,
-    ///                           |nax| nax.0.clone(),
-    ///                           |nmx| nmx.0.clone(),
-    ///                           |nax, nmx| (nax.0.clone(), nax.1, nmx.1),
-    ///                           1);
-    ///   assert_eq!(joined.run(&GreedyScheduler::new()), 
-    ///           Some(vec![("Leah".into(), ("Leah".into(), 12, 20.50))]));
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![("a".to_owned(), 1), ("b".into(), 2), ("a".into(), 3)]);
+    ///   let map = col.collect_map(&GreedyScheduler::new()).unwrap();
+    ///   assert_eq!(map.len(), 2);
+    ///   assert_eq!(map[&"a".to_owned()], 3);
     /// ```
-    pub fn join_on<
-        K: Any + Sync + Send + Clone + Hash + Eq + Serialize + for<'de> Deserialize<'de>,
-        B: Any + Sync + Send + Clone + Serialize + for<'de> Deserialize<'de>,
-        C: Any + Sync + Send + Clone + Serialize,
-        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
-        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
-        J:   'static + Sync + Send + Clone + Fn(&A, &B) -> C,
-    >(
-        &self, 
-        other: &DiskCollection<B>, 
-        key1: KF1, 
-        key2: KF2,
-        joiner: J,
-        partitions: usize, 
-    ) -> DiskCollection<(K,C)> {
-        // Group each by a common key
-        let p1 = self.map(move |x| (key1(x), x.clone()))
-            .partition_by_key(partitions, |x| x.0.clone());
-        let p2 = other.map(move |x| (key2(x), x.clone()))
-            .partition_by_key(partitions, |x| x.0.clone());
+    pub fn collect_map<S: Scheduler>(&self, s: &S) -> Option<HashMap<K, V>> {
+        self.run(s).map(|vs| vs.into_iter().collect())
+    }
+
+    /// Compares this collection, the old version of a keyed dataset, against `other`, the
+    /// new version, classifying every key as added, removed, modified or unchanged.  Both
+    /// collections are re-partitioned by key into `partitions` partitions.  Keys are 
+    /// expected to be unique within each collection.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::Change;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let old = DiskCollection::from_vec("/tmp".into(), vec![(1, 1), (2, 2), (3, 3usize)]);
+    ///   let new = DiskCollection::from_vec("/tmp".into(), vec![(1, 1), (2, 5), (4, 4usize)]);
+    ///   let mut changes = old.diff(&new, 2).run(&GreedyScheduler::new()).unwrap();
+    ///   changes.sort_by_key(|c| c.0);
+    ///   assert_eq!(changes, vec![(1, Change::Unchanged), (2, Change::Modified(2, 5)), 
+    ///                (3, Change::Removed(3)), (4, Change::Added(4))]);
+    /// ```
+    pub fn diff(&self, other: &DiskCollection<(K, V)>, partitions: usize) -> DiskCollection<(K, Change<V>)> 
+            where V: PartialEq {
+        check_partitions("DiskCollection::diff", partitions);
+        let p1 = self.partition_by_key(partitions, |x| x.0.clone());
+        let p2 = other.partition_by_key(partitions, |x| x.0.clone());
 
         let mut new_parts = Vec::with_capacity(p1.partitions.len());
         for (l, r) in p1.partitions.iter().zip(p2.partitions.iter()) {
-            let acc = Arc::new(FileStore::empty(self.path.clone()));
-            new_parts.push(jok(l, r, acc, joiner.clone()));
+            new_parts.push(diff_on_key(l, r, Disk(self.path.clone())));
         }
 
-        self.from_defs(new_parts)
-    }
-
-    /// Executes the Collection, returning the result of the computation
-    pub fn run<S: Scheduler>(&self, s: &S) -> Option<Vec<A>> {
-        let defs = batch_apply(&self.partitions, |_idx, vs| {
-            vs.stream().into_iter().collect::<Vec<_>>()
-        });
-        let cat = tree_reduce(&defs, |x, y| {
-            let mut v1: Vec<_> = (*x).clone();
-            for yi in y {
-                v1.push(yi.clone());
-            }
-            v1
-        });
-        cat.and_then(|x| x.run(s))
-    }
-    
-    /// Executes the Collection, returning the result of the computation
-    pub fn eval(&self) -> Option<Vec<A>> {
-        self.run(&GreedyScheduler::new())
+        self.from_defs(new_parts).named("diff")
     }
 
-}
-
-impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskCollection<Vec<A>> {
-    /// Flattens a vector of values
+    /// Aggregates the values of each key into a `B`.  Within a partition, values are 
+    /// folded into a fresh `default()` with `combine`; the partial aggregates for a key are
+    /// then combined across partitions with `merge`.  Unlike `fold_by`, the functions take
+    /// and return aggregates by value, which suits aggregates whose type differs from the
+    /// values.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::disk::DiskCollection;
     ///   
-    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![vec![1usize,2],vec![3,4]]);
-    ///   let flattened = col.flatten();
-    ///   assert_eq!(flattened.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 4]));
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![("a".to_owned(), 3), ("b".into(), 2), ("a".into(), 1), ("a".into(), 5)]);
+    ///   let lists = col.aggregate_by_key(Vec::new, 
+    ///                                    |mut acc, v| { acc.push(*v); acc },
+    ///                                    |mut l, r| { l.extend_from_slice(&r); l }, 
+    ///                                    1)
+    ///                  .sort_by(|x| x.0.clone());
+    ///   assert_eq!(lists.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("a".into(), vec![3, 1, 5]), ("b".into(), vec![2])]));
     /// ```
-    pub fn flatten(&self) -> DiskCollection<A> {
-        self.emit(move |x, emitter| {
-            for xi in x {
-                emitter(xi.clone());
-            }
-        })
+    pub fn aggregate_by_key<
+        B: Any + Send + Sync + Clone + Serialize + for<'de> Deserialize<'de>,
+        D: 'static + Sync + Send + Clone + Fn() -> B,
+        C: 'static + Sync + Send + Clone + Fn(B, &V) -> B,
+        M: 'static + Sync + Send + Clone + Fn(B, B) -> B
+    >(&self, default: D, combine: C, merge: M, partitions: usize) -> DiskCollection<(K, B)> {
+        let d2 = default.clone();
+        self.fold_by(|x| x.0.clone(), default.clone(), 
+                     move |acc, x| {
+                         let prev = ::std::mem::replace(acc, default());
+                         *acc = combine(prev, &x.1);
+                     },
+                     move |acc, other| {
+                         let prev = ::std::mem::replace(acc, d2());
+                         *acc = merge(prev, other.clone());
+                     },
+                     partitions)
     }
 }
 
 impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskCollection<A> {
-    /// Returns the number of items in the collection
+    /// Returns the number of items in the collection.  A collection without partitions
+    /// counts as zero items.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
@@ -436,13 +2188,84 @@ impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> DiskC
         let nps = batch_apply(&self.partitions, |_idx, vs| {
             vs.stream().into_iter().map(|_| 1usize).sum::<usize>()
         });
-        let count = tree_reduce(&nps, |x, y| x + y).unwrap();
+        let count = tree_reduce(&nps, |x, y| x + y).unwrap_or_else(|| Deferred::lift(0, None));
         let acc = Arc::new(FileStore::empty(self.path.clone()));
         let out = count.apply(move |x| {
             acc.write_vec(vec![*x])
         });
         self.from_defs(vec![out])
     }
+
+    /// Returns the number of items in the collection as a single Deferred value.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3usize]).split(2);
+    ///   assert_eq!(col.len().run(&GreedyScheduler::new()), Some(3));
+    /// ```
+    pub fn len(&self) -> Deferred<usize> {
+        let nps = batch_apply(&self.partitions, |_idx, vs| vs.stream().into_iter().count());
+        tree_reduce(&nps, |x, y| x + y).unwrap_or_else(|| Deferred::lift(0, None))
+    }
+
+    /// Returns whether the collection has no items.  Each partition stops looking as soon
+    /// as it finds an item, making this cheaper than checking `len`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3usize]).filter(|x| *x > 5);
+    ///   assert_eq!(col.is_empty().run(&GreedyScheduler::new()), Some(true));
+    /// ```
+    pub fn is_empty(&self) -> Deferred<bool> {
+        let nps = batch_apply(&self.partitions, |_idx, vs| vs.stream().into_iter().next().is_none());
+        tree_reduce(&nps, |x, y| *x && *y).unwrap_or_else(|| Deferred::lift(true, None))
+    }
+
+    /// Counts the number of items falling into each bucket returned by `f`, returning a
+    /// single map.  Each partition counts its own items before the maps are merged.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec!["a".to_owned(), "bb".into(), "cc".into(), "d".into()]).split(2);
+    ///   let buckets = col.count_buckets(|s| s.len()).run(&GreedyScheduler::new()).unwrap();
+    ///   assert_eq!(buckets[&1], 2);
+    ///   assert_eq!(buckets[&2], 2);
+    /// ```
+    pub fn count_buckets<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, f: F) -> Deferred<HashMap<K, usize>> {
+        count_buckets(&self.partitions, f)
+    }
+
+    /// Reduces all items in the collection into one with `f`, returning the reducing
+    /// Deferred itself so it can be joined into other computations.  Items are reduced
+    /// within each partition before partitions are combined, so `f` should be associative
+    /// and commutative.  The Deferred computes None if the collection has no items.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 4usize]).split(2);
+    ///   let sum = col.reduce_to_deferred(|x, y| x + y);
+    ///   assert_eq!(sum.apply(|s| s.map(|s| s * 10)).run(&GreedyScheduler::new()), Some(Some(100)));
+    /// ```
+    pub fn reduce_to_deferred<
+        F: 'static + Sync + Send + Clone + Fn(&A, &A) -> A
+    >(&self, f: F) -> Deferred<Option<A>> {
+        reduce(&self.partitions, f)
+    }
 }
 
 impl <A: Any + Send + Sync + Clone + PartialEq + Hash + Eq + Serialize + for<'de>Deserialize<'de>> DiskCollection<A> {
@@ -468,11 +2291,113 @@ pub fn frequencies(&self, partitions: usize) -> DiskCollection<(A, usize)> {
     }
 }
 
+impl <A: Any + Send + Sync + Clone + Ord + Serialize + for<'de>Deserialize<'de>> DiskCollection<A> {
+
+    /// Sorts each partition by the natural ordering of its items.  Like `sort_by`, items
+    /// are not moved between partitions; see `sort_global` for a total ordering.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![3, 1, 2usize]);
+    ///   assert_eq!(col.sort().run(&GreedyScheduler::new()), Some(vec![1, 2, 3]));
+    /// ```
+    pub fn sort(&self) -> DiskCollection<A> {
+        self.sort_by(|x| x.clone()).named("sort")
+    }
+
+    /// Sorts the collection into `partitions` partitions by the natural ordering of its 
+    /// items, so that concatenating the partitions in order yields a globally sorted 
+    /// result.  Items are range partitioned using a sample of the data, then each 
+    /// partition is sorted.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![5, 3, 9, 1, 7, 2usize]).split(3);
+    ///   let sorted = col.sort_global(2);
+    ///   assert_eq!(sorted.n_partitions(), 2);
+    ///   assert_eq!(sorted.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 5, 7, 9]));
+    /// ```
+    pub fn sort_global(&self, partitions: usize) -> DiskCollection<A> {
+        check_partitions("DiskCollection::sort_global", partitions);
+        let bounds = sample_bounds(&self.partitions, |x: &A| x.clone(), partitions);
+        let shards = range_partition(&self.partitions, &bounds, partitions, |x: &A| x.clone());
+        self.from_defs(shards).sort().named("sort_global")
+    }
+}
+
+impl <A: Any + Send + Sync + Clone + Into<f64> + Serialize + for<'de>Deserialize<'de>> DiskCollection<A> {
+
+    /// Computes the arithmetic mean of the collection.  Each partition computes its sum
+    /// and count, which are combined before dividing.  Returns NaN for an empty collection.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec![1, 2, 3, 6u32]).split(2);
+    ///   assert_eq!(col.mean().run(&GreedyScheduler::new()), Some(3.));
+    /// ```
+    pub fn mean(&self) -> Deferred<f64> {
+        mean(&self.partitions)
+    }
+
+    /// Estimates the value at quantile `q` within [0, 1].  Each partition builds a 
+    /// t-digest of its values, which are then merged together, avoiding a global sort.
+    /// Returns NaN for an empty collection.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), (0..1000u32).collect()).split(4);
+    ///   let median = col.approx_quantile(0.5).run(&GreedyScheduler::new()).unwrap();
+    ///   assert!((median - 499.5).abs() < 5.);
+    /// ```
+    pub fn approx_quantile(&self, q: f64) -> Deferred<f64> {
+        self.approx_quantiles(&[q]).apply(|qs| qs[0])
+    }
+
+    /// Estimates the values at a set of quantiles, using a single t-digest.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), (0..1000u32).collect()).split(4);
+    ///   let qs = col.approx_quantiles(&[0., 1.]).run(&GreedyScheduler::new());
+    ///   assert_eq!(qs, Some(vec![0., 999.]));
+    /// ```
+    pub fn approx_quantiles(&self, qs: &[f64]) -> Deferred<Vec<f64>> {
+        let qs = qs.to_vec();
+        digest(&self.partitions, 100.).apply(move |d| {
+            qs.iter().map(|q| d.quantile(*q)).collect()
+        })
+    }
+}
+
 // Writes out data
 impl DiskCollection<String> {
     /// Writes each record in a collection to disk, newline delimited.
     /// DiskCollection will create anew file within the path for each partition written.
     pub fn sink(&self, path: &str) -> DiskCollection<usize> {
+        self.sink_named(path, |idx| idx.to_string())
+    }
+
+    /// Writes each record in a collection to disk, newline delimited, like `sink`, but 
+    /// names the file written for each partition with `name_fn`, which receives the 
+    /// partition index.
+    pub fn sink_named<
+        N: 'static + Sync + Send + Clone + Fn(usize) -> String
+    >(&self, path: &str, name_fn: N) -> DiskCollection<usize> {
         let acc = Arc::new(FileStore::empty(self.path.clone()));
         let p: Arc<String> = Arc::new(path.to_owned());
         let pats = batch_apply(&self.partitions, move |idx, vs| {
@@ -481,14 +2406,14 @@ impl DiskCollection<String> {
             fs::create_dir_all(local)
                 .expect("Welp, something went terribly wrong when creating directory");
 
-            let file = fs::File::create(&format!("{}/{}", local, idx))
+            let file = fs::File::create(format!("{}/{}", local, name_fn(idx)))
                 .expect("Issues opening file!");
             let mut bw = BufWriter::new(file);
 
             let mut size = 0usize;
             for line in vs.stream() {
-                bw.write(line.as_bytes()).expect("Error writing out line");
-                bw.write(b"\n").expect("Error writing out line");
+                bw.write_all(line.as_bytes()).expect("Error writing out line");
+                bw.write_all(b"\n").expect("Error writing out line");
                 size += 1;
             }
 
@@ -497,6 +2422,87 @@ impl DiskCollection<String> {
         
         self.from_defs(pats)
     }
+
+    /// Writes each record in a collection to disk, newline delimited, like `sink`.  Each
+    /// partition is first written to a hidden temporary file within the path and only renamed
+    /// to its final name once completely written, so readers never observe partial files.
+    pub fn sink_atomic(&self, path: &str) -> DiskCollection<usize> {
+        let acc = Arc::new(FileStore::empty(self.path.clone()));
+        let p: Arc<String> = Arc::new(path.to_owned());
+        let pats = batch_apply(&self.partitions, move |idx, vs| {
+            let size = write_atomic(&p, idx, |bw| {
+                let mut size = 0usize;
+                for line in vs.stream() {
+                    bw.write_all(line.as_bytes())?;
+                    bw.write_all(b"\n")?;
+                    size += 1;
+                }
+                Ok(size)
+            });
+            acc.write_vec(vec![size])
+        });
+
+        self.from_defs(pats)
+    }
+
+    /// Writes each record in a collection to disk, newline delimited, into at most
+    /// `max_files` files.  Neighboring partitions are coalesced before writing, avoiding
+    /// a large number of small files.
+    pub fn sink_coalesced(&self, path: &str, max_files: usize) -> DiskCollection<usize> {
+        self.coalesce(max_files).sink(path)
+    }
+
+    /// Writes each record in a collection to disk, newline delimited, writing each 
+    /// contiguous group of `group` partitions into a single file named after the group's
+    /// index.  Returns the number of records written to each file.
+    pub fn sink_grouped(&self, path: &str, group: usize) -> DiskCollection<usize> {
+        assert!(group > 0, "DiskCollection::sink_grouped requires groups of at least one partition");
+        let groups = self.partitions.chunks(group)
+            .map(|g| concat(g).unwrap())
+            .collect();
+        self.from_defs(groups).sink(path)
+    }
+
+    /// Writes the entire collection into a single file at `path`, newline delimited, 
+    /// with partitions written in order.  If provided, `separator` is written as its own
+    /// line between each partition.  Returns the number of records written, not counting
+    /// separators.  Partitions are appended to the file one after another, in partition
+    /// order.
+    pub fn sink_single(&self, path: &str, separator: Option<String>) -> Deferred<usize> {
+        sink_single(&self.partitions, path, separator)
+    }
+
+    /// Writes the collection into `partitions` sorted files.  Records are range
+    /// partitioned using a sample of the data, then each shard is sorted before writing,
+    /// so that concatenating the files in index order yields globally sorted output.
+    pub fn sink_sorted(&self, path: &str, partitions: usize) -> DiskCollection<usize> {
+        check_partitions("DiskCollection::sink_sorted", partitions);
+        let bounds = sample_bounds(&self.partitions, |s: &String| s.clone(), partitions);
+        let shards = range_partition(&self.partitions, &bounds, partitions, |s: &String| s.clone());
+        let sharded = self.from_defs(shards);
+        sharded.sort_by(|s| s.clone()).sink(path)
+    }
+
+    /// Feeds each partition through an external command, Unix pipe style.  A process is
+    /// started per partition, the partition's records are written to its stdin one per
+    /// line, and the lines it writes to stdout become the new partition.  Panics when the
+    /// partition is computed if the command can't be started or exits with a failure.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::disk::DiskCollection;
+    ///   
+    ///   let col = DiskCollection::from_vec("/tmp".into(), vec!["b".to_owned(), "a".into(), "c".into()]);
+    ///   let sorted = col.pipe("sort", Vec::new());
+    ///   assert_eq!(sorted.run(&GreedyScheduler::new()), 
+    ///     Some(vec!["a".into(), "b".into(), "c".into()]));
+    /// ```
+    pub fn pipe(&self, command: &str, args: Vec<String>) -> DiskCollection<String> {
+        let parts = pipe(&self.partitions, Disk(self.path.clone()), command, args);
+
+        self.from_defs(parts).named("pipe")
+    }
 }
 
 #[cfg(test)]
@@ -512,7 +2518,7 @@ mod test_lib {
     fn test_fold_by() {
         let col = make_col();
         let out = col.fold_by(|x| *x, || 0, |x, _y| *x += 1, |x, y| *x += y, 1);
-        let mut results = out.run(&LeveledScheduler).unwrap();
+        let mut results = out.run(&LeveledScheduler::new()).unwrap();
         results.sort();
         assert_eq!(results, vec![(1, 2), (2, 2), (3, 1)]);
     }
@@ -522,7 +2528,7 @@ mod test_lib {
         let col = make_col();
         let out = col.fold_by(|x| *x, || 0, |x, _y| *x += 1, |x, y| *x += y, 2);
         assert_eq!(out.partitions.len(), 2);
-        let mut results = out.run(&LeveledScheduler).unwrap();
+        let mut results = out.run(&LeveledScheduler::new()).unwrap();
         results.sort();
         assert_eq!(results, vec![(1, 2), (2, 2), (3, 1)]);
     }
@@ -533,7 +2539,7 @@ mod test_lib {
         let computed = col.partition_by_key(2, |x| *x)
             .sort_by(|x| *x);
         assert_eq!(computed.partitions.len(), 2);
-        let results = computed.run(&LeveledScheduler).unwrap();
+        let results = computed.run(&LeveledScheduler::new()).unwrap();
         assert_eq!(results, vec![2, 2, 3, 1, 1]);
     }
 
@@ -550,7 +2556,7 @@ mod test_lib {
     #[test]
     fn test_count() {
         let col = make_col();
-        let results = col.split(3).count().run(&mut LeveledScheduler).unwrap();
+        let results = col.split(3).count().run(&mut LeveledScheduler::new()).unwrap();
         assert_eq!(results, vec![5]);
     }
 
@@ -562,7 +2568,7 @@ mod test_lib {
         let out = col1.join_on(&col2, |x| *x, |y| y.0, |x, y| {
             (*x, y.1)
         }, 5).split(1).sort_by(|x| x.0);
-        let results = out.run(&LeveledScheduler).unwrap();
+        let results = out.run(&LeveledScheduler::new()).unwrap();
         let expected = vec![(2, (2, 1.23)), (2, (2, 1.23)), (3, (3, 2.34))];
         assert_eq!(results, expected);
     }
@@ -576,7 +2582,7 @@ mod test_lib {
                 }
             })
             .sort_by(|x| *x)
-            .run(&LeveledScheduler).unwrap();
+            .run(&LeveledScheduler::new()).unwrap();
         let expected = vec![0, 0, 0, 1, 1, 2];
         assert_eq!(results, expected);
     }
@@ -585,9 +2591,33 @@ mod test_lib {
     fn test_sort() {
         let results = DiskCollection::from_vec("/tmp".into(), vec![1, 3, 2usize])
             .sort_by(|x| *x)
-            .run(&LeveledScheduler).unwrap();
+            .run(&LeveledScheduler::new()).unwrap();
         let expected = vec![1, 2, 3];
         assert_eq!(results, expected);
     }
 
+    #[test]
+    #[should_panic(expected = "DiskCollection::partition requires at least one partition")]
+    fn test_partition_zero_partitions() {
+        make_col().partition(0, |idx, _x| idx);
+    }
+
+    #[test]
+    #[should_panic(expected = "DiskCollection::split requires at least one partition")]
+    fn test_split_zero_partitions() {
+        make_col().split(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "DiskCollection::fold_by requires at least one partition")]
+    fn test_fold_by_zero_partitions() {
+        make_col().fold_by(|x| *x, || 0, |x, _y| *x += 1, |x, y| *x += y, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "DiskCollection::partition_by_key requires at least one partition")]
+    fn test_partition_by_key_zero_partitions() {
+        make_col().partition_by_key(0, |x| *x);
+    }
+
 }
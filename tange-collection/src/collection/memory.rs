@@ -11,16 +11,22 @@ use std::any::Any;
 use std::io::prelude::*;
 use std::io::BufWriter;
 use std::hash::Hash;
-use std::sync::Arc;
+use std::fmt::Debug;
+use std::collections::{HashMap,HashSet};
+use std::sync::{Arc,mpsc};
+use std::thread;
 
 use self::serde::{Deserialize,Serialize};
 
 use collection::disk::DiskCollection;
-use tange::deferred::{Deferred, batch_apply, tree_reduce};
+use tange::deferred::{Deferred, batch_apply, batch_join, tree_reduce, tree_reduce_ordered, collect_deferreds};
 use tange::scheduler::{Scheduler,GreedyScheduler};
-use partitioned::{join_on_key as jok, partition, partition_by_key, fold_by, concat};
-use interfaces::{Memory,Disk};
-use super::emit;
+use partitioned::{join_on_key as jok, left_join_on_key, diff_on_key, partition, partition_by_key, fold_by, reduce_by_key, map_reduce_by_key, concat, digest, bloom, filter_by_bloom, filter_on_keys, map_with_broadcast, group_shuffle, group_capped, interleave, merge_sorted, hash_key, mean, count_buckets, reduce, sink_single, first_by_key, window_global, zip_with_index, scan_global, prefix_while, sample_bounds, range_partition, rebalance_by_key, repartition_ordered, coalesce_balanced};
+use interfaces::{Memory,Disk,Accumulator,Stream};
+use sketch::BloomFilter;
+use partition::{Partitioner,ConsistentHashPartitioner};
+use super::{StorageLevel, Change, emit, flat_map_indexed, check_partitions, TopK, reservoir, shuffle, label, explain, run_to_channel, with_offsets, first_n, map_progress, pipe, write_atomic, to_columns, non_empty, map_accumulating};
+use utils::SideAccumulator;
 
 
 /// MemoryCollection struct
@@ -29,6 +35,37 @@ pub struct MemoryCollection<A>  {
     partitions: Vec<Deferred<Vec<A>>>
 }
 
+impl MemoryCollection<i64> {
+
+    /// Creates a MemoryCollection holding the integers in `start..end`, split into
+    /// `partitions` contiguous partitions of near equal size.  Each partition generates its
+    /// numbers when the collection is computed, rather than up front.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_range(0, 5, 2);
+    ///   assert_eq!(col.n_partitions(), 2);
+    ///   assert_eq!(col.run(&GreedyScheduler::new()), Some(vec![0, 1, 2, 3, 4]));
+    /// ```
+    pub fn from_range(start: i64, end: i64, partitions: usize) -> MemoryCollection<i64> {
+        check_partitions("MemoryCollection::from_range", partitions);
+        // Widen so that neither the length nor the bounds can overflow
+        let len = (end as i128 - start as i128).max(0);
+        let n = partitions as i128;
+        let bound = |p: i128| (start as i128 + len / n * p + p.min(len % n)) as i64;
+        let parts = (0..partitions).map(|p| {
+            let lo = bound(p as i128);
+            let hi = bound(p as i128 + 1);
+            Deferred::lift_lazy(move || (lo..hi).collect::<Vec<_>>(), 
+                                Some(&format!("Range: {}..{}", lo, hi)))
+        }).collect();
+        MemoryCollection { partitions: parts }
+    }
+}
+
 impl <A: Any + Send + Sync + Clone> MemoryCollection<A> {
 
     /// Creates a MemoryCollection from a set of Deferred objects.
@@ -59,11 +96,76 @@ impl <A: Any + Send + Sync + Clone> MemoryCollection<A> {
         }
     }
 
+    /// Creates a new MemoryCollection from a Vec of items, split into `partitions` 
+    /// contiguous partitions of near equal size.  Unlike `from_vec` followed by `split`,
+    /// the items are divided up front, without an extra node in the graph.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec_split(vec![1,2,3,4,5usize], 2);
+    ///   assert_eq!(col.n_partitions(), 2);
+    ///   assert_eq!(col.run(&GreedyScheduler::new()), Some(vec![1,2,3,4,5usize]));
+    /// ```
+    pub fn from_vec_split(vs: Vec<A>, partitions: usize) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::from_vec_split", partitions);
+        let len = vs.len();
+        let mut rest = vs;
+        let mut parts = Vec::with_capacity(partitions);
+        // Split chunks off the end, so no items are copied
+        for p in (0..partitions).rev() {
+            let chunk = rest.split_off(p * len / partitions);
+            parts.push(Deferred::lift(chunk, None));
+        }
+        parts.reverse();
+        MemoryCollection { partitions: parts }
+    }
+
     /// Returns the current number of data partitions 
     pub fn n_partitions(&self) -> usize {
         self.partitions.len()
     }
 
+    /// Describes the plan for computing the collection without running it: the number
+    /// of partitions and, for each partition, the chain of operations leading to it.
+    /// Where an operation combines two inputs, the chain follows the left-hand input.
+    /// ```rust
+    ///   extern crate tange_collection;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3usize]).map(|x| x + 1);
+    ///   assert_eq!(col.explain(), 
+    ///     "MemoryCollection with 1 partition(s)\n  0: Input<name=Input> -> Task<name=map>\n");
+    /// ```
+    pub fn explain(&self) -> String {
+        explain("MemoryCollection", &self.partitions)
+    }
+
+    /// Marks each partition of the collection for caching.  Schedulers with a cache, such
+    /// as `GreedyScheduler::with_cache_capacity`, keep the computed partitions between
+    /// runs, evicting the least recently used ones when the cache is full, so iterative
+    /// algorithms can reuse a collection without repeating its lineage.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let s = GreedyScheduler::new().with_cache_capacity(8);
+    ///   let squares = MemoryCollection::from_vec(vec![1,2,3usize]).map(|x| x * x).cache();
+    ///   assert_eq!(squares.run(&s), Some(vec![1, 4, 9]));
+    ///   assert_eq!(squares.map(|x| x + 1).run(&s), Some(vec![2, 5, 10]));
+    /// ```
+    pub fn cache(&self) -> MemoryCollection<A> {
+        MemoryCollection { partitions: self.partitions.iter().map(|d| d.cache()).collect() }
+    }
+
+    fn named(&self, name: &str) -> MemoryCollection<A> {
+        MemoryCollection { partitions: label(&self.partitions, name) }
+    }
+
     /// Concatentates two collections into a single Collection
     /// ```rust
     ///   extern crate tange;
@@ -86,496 +188,3945 @@ impl <A: Any + Send + Sync + Clone> MemoryCollection<A> {
 
         MemoryCollection { partitions: nps }
     }
-    
-    /// Maps a function over the values in the DiskCollection, returning a new DiskCollection
+
+    /// Combines the aligned partitions of two collections with the provided function,
+    /// which receives the full contents of each pair of partitions.  Both collections must
+    /// have the same number of partitions, but the partitions themselves can differ in 
+    /// length.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::memory::MemoryCollection;
     ///   
-    ///   let one = MemoryCollection::from_vec(vec![1,2,3usize]);
-    ///   let strings = one.map(|i| format!("{}", i));
-    ///   assert_eq!(strings.run(&GreedyScheduler::new()), 
-    ///     Some(vec!["1".into(),"2".into(),"3".into()]));
+    ///   let left = MemoryCollection::from_vec(vec![1,3,5usize]);
+    ///   let right = MemoryCollection::from_vec(vec![2,4usize]);
+    ///   let sizes = left.zip_partitions(&right, |l, r| vec![l.len(), r.len()]);
+    ///   assert_eq!(sizes.run(&GreedyScheduler::new()), Some(vec![3, 2]));
     /// ```
-    pub fn map<
-        B: Any + Send + Sync + Clone, 
-        F: 'static + Sync + Send + Clone + Fn(&A) -> B
-    >(&self, f: F) -> MemoryCollection<B> {
-        self.emit(move |x, emitter| {
-            emitter(f(x))
-        })
+    pub fn zip_partitions<
+        B: Any + Send + Sync + Clone,
+        C: Any + Send + Sync + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&Vec<A>, &Vec<B>) -> Vec<C>
+    >(&self, other: &MemoryCollection<B>, f: F) -> MemoryCollection<C> {
+        assert_eq!(self.partitions.len(), other.partitions.len(), 
+                   "MemoryCollection::zip_partitions requires equal partition counts");
+        let new_parts = batch_join(&self.partitions, &other.partitions, move |_idx, l, r| {
+            f(l, r)
+        });
+        MemoryCollection { partitions: new_parts }
     }
 
-    /// Filters out items in the collection that fail the predicate.
+    /// Merges two collections fairly by alternating between them: within each aligned pair
+    /// of partitions, items are taken one from each side in turn until both are exhausted.
+    /// Both collections must have the same number of partitions.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::memory::MemoryCollection;
     ///   
-    ///   let col = MemoryCollection::from_vec(vec![1,2,3usize]);
-    ///   let odds = col.filter(|x| x % 2 == 1);
-    ///   assert_eq!(odds.run(&GreedyScheduler::new()), 
-    ///     Some(vec![1, 3usize]));
+    ///   let odds = MemoryCollection::from_vec(vec![1, 3, 5usize]);
+    ///   let evens = MemoryCollection::from_vec(vec![2, 4usize]);
+    ///   let merged = odds.interleave(&evens);
+    ///   assert_eq!(merged.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 4, 5]));
     /// ```
-
-    pub fn filter<
-        F: 'static + Sync + Send + Clone + Fn(&A) -> bool
-    >(&self, f: F) -> MemoryCollection<A> {
-        self.emit(move |x, emitter| {
-            if f(x) { 
-                emitter(x.clone())
-            }
-        })
+    pub fn interleave(&self, other: &MemoryCollection<A>) -> MemoryCollection<A> {
+        assert_eq!(self.partitions.len(), other.partitions.len(), 
+                   "MemoryCollection::interleave requires equal partition counts");
+        let parts = interleave(&self.partitions, &other.partitions, Memory);
+        MemoryCollection { partitions: parts }.named("interleave")
     }
-    
-    /// Re-partitions a collection by the number of provided chunks.  It uniformly distributes data from each old partition into each new partition.
+
+    /// Maps each item with a carry value computed from the preceding partitions.  The 
+    /// first partition receives `seed()`; each following partition receives the result of
+    /// `carry` applied to the previous carry and the previous partition.  Since every 
+    /// partition depends on the one before it, this serializes partition processing.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::memory::MemoryCollection;
     ///   
-    ///   let col = MemoryCollection::from_vec(vec![1,2,3usize]);
-    ///   assert_eq!(col.n_partitions(), 1);
-    ///   let two = col.split(2);
-    ///   assert_eq!(two.n_partitions(), 2);
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4usize]).split(2);
+    ///   // Offset each item by the sum of all prior partitions
+    ///   let offset = col.map_with_carry(|| 0, 
+    ///       |c, vs| c + vs.iter().sum::<usize>(),
+    ///       |c, x| (*c, *x));
+    ///   assert_eq!(offset.run(&GreedyScheduler::new()), 
+    ///     Some(vec![(0, 1), (0, 3), (4, 2), (4, 4)]));
     /// ```
-    pub fn split(&self, n_chunks: usize) -> MemoryCollection<A> {
-        self.partition(n_chunks, |idx, _k| idx)
+    pub fn map_with_carry<
+        B: Any + Send + Sync + Clone,
+        C: Any + Send + Sync + Clone,
+        Seed: Fn() -> C,
+        Carry: 'static + Sync + Send + Clone + Fn(&C, &Vec<A>) -> C,
+        F: 'static + Sync + Send + Clone + Fn(&C, &A) -> B
+    >(&self, seed: Seed, carry_fn: Carry, f: F) -> MemoryCollection<B> {
+        let mut carry = Deferred::lift(seed(), None);
+        let mut new_parts = Vec::with_capacity(self.partitions.len());
+        for part in self.partitions.iter() {
+            let f = f.clone();
+            new_parts.push(part.join(&carry, move |vs, c| {
+                vs.iter().map(|x| f(c, x)).collect::<Vec<_>>()
+            }));
+            let carry_fn = carry_fn.clone();
+            carry = part.join(&carry, move |vs, c| carry_fn(c, vs));
+        }
+        MemoryCollection { partitions: new_parts }.named("map_with_carry")
     }
 
-    /// Maps over all items in a collection, optionally emitting new values.  It can be used
-    /// to efficiently fuse a number of map/filter/flat_map functions into a single method.
+    /// Computes a running value across the whole collection, such as a cumulative sum,
+    /// continuing from one partition into the next in partition order.  `f` folds each
+    /// item into the running value.  Partitions are scanned in parallel from `init`, then
+    /// `combine` merges each partition's running values with the carry from all prior 
+    /// partitions, so `init` must be the identity of `combine`.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::memory::MemoryCollection;
     ///   
-    ///   let col = MemoryCollection::from_vec(vec![1,2,3usize]);
-    ///   let new = col.emit(|item, emitter| {
-    ///     if item % 2 == 0 {
-    ///         emitter(format!("{}!", item));
-    ///     }
-    ///   });
-    ///   assert_eq!(new.run(&GreedyScheduler::new()), Some(vec!["2!".into()]));
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4usize])
+    ///     .concat(&MemoryCollection::from_vec(vec![1, 2, 3, 4usize]));
+    ///   let sums = col.scan_global(0, |acc, x| acc + x, |c, x| c + x);
+    ///   assert_eq!(sums.run(&GreedyScheduler::new()), 
+    ///     Some(vec![1, 3, 6, 10, 11, 13, 16, 20]));
     /// ```
-
-    pub fn emit<
+    pub fn scan_global<
         B: Any + Send + Sync + Clone,
-        F: 'static + Sync + Send + Clone + Fn(&A, &mut FnMut(B) -> ())
-    >(&self, f: F) -> MemoryCollection<B> {
-        let parts = emit(&self.partitions, Memory, f);
-
-        MemoryCollection { partitions: parts }
+        F: 'static + Sync + Send + Clone + Fn(&B, &A) -> B,
+        R: 'static + Sync + Send + Clone + Fn(&B, &B) -> B
+    >(&self, init: B, f: F, combine: R) -> MemoryCollection<B> {
+        let parts = scan_global(&self.partitions, init, f, combine, Memory);
+        MemoryCollection { partitions: parts }.named("scan_global")
     }
 
-    /// Maps over all items in a collection, emitting new values.  It can be used
-    /// to efficiently fuse a number of map/filter/flat_map functions into a single method.
-    /// `emit_to_disk` differs from the original `emit` by writing the emitted values directly
-    /// to disk, returning a DiskCollection instead of MemoryCollection.  This makes it convenient to switch to out-of-core when needed.
+    /// Runs a function over each full partition for its side effects, such as bulk
+    /// inserting each partition into a database.  The function receives the partition
+    /// index and its items.  The returned Deferred completes once every partition has 
+    /// been processed.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::memory::MemoryCollection;
     ///   
-    ///   let col = MemoryCollection::from_vec(vec![1,2,3usize]);
-    ///   let new = col.emit_to_disk("/tmp".into(), |item, emitter| {
-    ///     if item % 2 == 0 {
-    ///         emitter(format!("{}!", item));
-    ///     }
-    ///   });
-    ///   assert_eq!(new.run(&GreedyScheduler::new()), Some(vec!["2!".into()]));
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3usize]).split(2);
+    ///   let done = col.for_each_partition(|idx, vs| println!("{}: {:?}", idx, vs));
+    ///   assert_eq!(done.run(&GreedyScheduler::new()), Some(()));
     /// ```
-
-    pub fn emit_to_disk<
-        B: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>,
-        F: 'static + Sync + Send + Clone + Fn(&A, &mut FnMut(B) -> ())
-    >(&self, path: String, f: F) -> DiskCollection<B> {
-        let parts = emit(&self.partitions, Disk::from_str(&path), f);
-
-        DiskCollection::from_stores(path, parts)
+    pub fn for_each_partition<
+        F: 'static + Sync + Send + Clone + Fn(usize, &Vec<A>)
+    >(&self, f: F) -> Deferred<()> {
+        let done = batch_apply(&self.partitions, move |idx, vs| f(idx, vs));
+        tree_reduce(&done, |_x, _y| ()).unwrap_or_else(|| Deferred::lift((), None))
     }
 
-    /// Re-partitions data into N new partitions by the given function.  The user provided
-    /// function is used as a hash function, mapping the returned value to a partition index.
-    /// This makes it useful for managing which partition data ends up!
+    /// Produces every window of `size` consecutive items, including windows which 
+    /// straddle partition boundaries.  Each window is emitted in the partition of its
+    /// first item.  Each partition depends on the heads of the partitions following it.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::memory::MemoryCollection;
     ///   
-    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4usize]);
-    ///   let new_col = col.partition(2, |idx, x| if *x < 3 { 1 } else { 2 });
-    ///   
-    ///   assert_eq!(new_col.n_partitions(), 2);
-    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![3, 4, 1, 2]));
+    ///   // Partitions are [1, 3] and [2, 4]
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4usize]).split(2);
+    ///   let windows = col.window_global(2);
+    ///   assert_eq!(windows.run(&GreedyScheduler::new()), 
+    ///     Some(vec![vec![1, 3], vec![3, 2], vec![2, 4]]));
     /// ```
-    pub fn partition<
-        F: 'static + Sync + Send + Clone + Fn(usize, &A) -> usize
-    >(&self, partitions: usize, f: F) -> MemoryCollection<A> {
-        let new_chunks = partition(&self.partitions, 
-                                   partitions, 
-                                   f);
-        // Loop over each bucket
-        MemoryCollection { partitions: new_chunks }
+    pub fn window_global(&self, size: usize) -> MemoryCollection<Vec<A>> {
+        assert!(size > 0, "MemoryCollection::window_global requires a window size of at least one");
+        let new_parts = window_global(&self.partitions, size, Memory);
+        MemoryCollection { partitions: new_parts }
     }
 
-    /// Folds and accumulates values across multiple partitions into K new partitions.
-    /// This is also known as a "group by" with a following reducer.
-    ///
-    /// MemoryCollection first performs a block aggregation: that is, it combines values
-    /// within each partition first using the `binop` function.  It then hashes
-    /// each key to a new partition index, where it will then aggregate all keys using the
-    /// `reduce` function.
-    ///
+    /// Keeps items from the start of the collection, in partition order, for as long as
+    /// they pass the predicate.  Everything from the first failing item onwards is 
+    /// dropped, even in later partitions.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::memory::MemoryCollection;
     ///   
-    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4,5usize]);
-    ///   // Sum all odds and evens together
-    ///   let group_sum = col.fold_by(|x| x % 2,
-    ///                               || 0usize,
-    ///                               |block_acc, item| {*block_acc += *item},
-    ///                               |part_acc1, part_acc2| {*part_acc1 += *part_acc2},
-    ///                               1)
-    ///                   .sort_by(|x| x.0);
-    ///   
-    ///   assert_eq!(group_sum.n_partitions(), 1);
-    ///   assert_eq!(group_sum.run(&GreedyScheduler::new()), Some(vec![(0, 6), (1, 9)]));
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4, 1usize]);
+    ///   assert_eq!(col.take_while(|x| *x < 3).run(&GreedyScheduler::new()), 
+    ///     Some(vec![1, 2]));
     /// ```
-
-    pub fn fold_by<K: Any + Sync + Send + Clone + Hash + Eq,
-                   B: Any + Sync + Send + Clone,
-                   D: 'static + Sync + Send + Clone + Fn() -> B, 
-                   F: 'static + Sync + Send + Clone + Fn(&A) -> K, 
-                   O: 'static + Sync + Send + Clone + Fn(&mut B, &A) -> (),
-                   R: 'static + Sync + Send + Clone + Fn(&mut B, &B) -> ()>(
-        &self, key: F, default: D, binop: O, reduce: R, partitions: usize
-    ) -> MemoryCollection<(K,B)> {
-        let results = fold_by(&self.partitions, key, default, binop, 
-                              reduce, Vec::with_capacity(0), partitions);
-        MemoryCollection { partitions: results }
+    pub fn take_while<
+        F: 'static + Sync + Send + Clone + Fn(&A) -> bool
+    >(&self, f: F) -> MemoryCollection<A> {
+        let parts = prefix_while(&self.partitions, f, Memory, true);
+        MemoryCollection { partitions: parts }.named("take_while")
     }
 
-    /// Simple function to re-partition values by a given key.  The return key is hashed
-    /// and moduloed by the new partition count to determine where it will end up.
+    /// Drops items from the start of the collection, in partition order, for as long as
+    /// they pass the predicate.  Everything from the first failing item onwards is kept,
+    /// even if later items pass the predicate.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::memory::MemoryCollection;
     ///   
-    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4usize]);
-    ///   let new_col = col.partition_by_key(2, |x| format!("{}", x));
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4, 1usize]);
+    ///   assert_eq!(col.skip_while(|x| *x < 3).run(&GreedyScheduler::new()), 
+    ///     Some(vec![3, 4, 1]));
+    /// ```
+    pub fn skip_while<
+        F: 'static + Sync + Send + Clone + Fn(&A) -> bool
+    >(&self, f: F) -> MemoryCollection<A> {
+        let parts = prefix_while(&self.partitions, f, Memory, false);
+        MemoryCollection { partitions: parts }.named("skip_while")
+    }
+    
+    /// Maps a function over the values in the DiskCollection, returning a new DiskCollection
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
     ///   
-    ///   assert_eq!(new_col.n_partitions(), 2);
-    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![4, 1, 2, 3]));
+    ///   let one = MemoryCollection::from_vec(vec![1,2,3usize]);
+    ///   let strings = one.map(|i| format!("{}", i));
+    ///   assert_eq!(strings.run(&GreedyScheduler::new()), 
+    ///     Some(vec!["1".into(),"2".into(),"3".into()]));
     /// ```
-    pub fn partition_by_key<
-        K: Any + Sync + Send + Clone + Hash + Eq,
-        F: 'static + Sync + Send + Clone + Fn(&A) -> K
-    >(&self, n_chunks: usize, key: F) -> MemoryCollection<A> {
-        let results = partition_by_key(&self.partitions, n_chunks, key);
-        let groups = results.into_iter().map(|part| concat(&part).unwrap()).collect();
-        MemoryCollection {partitions: groups}
+    pub fn map<
+        B: Any + Send + Sync + Clone, 
+        F: 'static + Sync + Send + Clone + Fn(&A) -> B
+    >(&self, f: F) -> MemoryCollection<B> {
+        self.emit(move |x, emitter| {
+            emitter(f(x))
+        }).named("map")
     }
 
-    /// Sorts values within each partition by a key function.  If a global sort is desired,
-    /// the collection needs to be re-partitioned into a single partition
+    /// Maps each item to a pair of values, returning two collections holding the first 
+    /// and second values respectively.  Both outputs are built in a single pass over each
+    /// partition, so deriving several collections from one doesn't traverse it twice.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::memory::MemoryCollection;
     ///   
-    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4i32]);
-    ///   let new_col = col.sort_by(|x| -*x);
-    ///   
-    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![4, 3, 2, 1]));
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3usize]);
+    ///   let (doubled, labels) = col.map_fanout(|x| (x * 2, format!("#{}", x)));
+    ///   assert_eq!(doubled.run(&GreedyScheduler::new()), Some(vec![2, 4, 6]));
+    ///   assert_eq!(labels.run(&GreedyScheduler::new()), 
+    ///     Some(vec!["#1".to_owned(), "#2".into(), "#3".into()]));
     /// ```
-    pub fn sort_by<
-        K: Ord,
-        F: 'static + Sync + Send + Clone + Fn(&A) -> K
-    >(&self, key: F) -> MemoryCollection<A> {
-        let nps = batch_apply(&self.partitions, move |_idx, vs| {
-            let mut v2: Vec<_> = vs.clone();
-            v2.sort_by_key(|v| key(v));
-            v2
+    pub fn map_fanout<
+        B: Any + Send + Sync + Clone,
+        C: Any + Send + Sync + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> (B, C)
+    >(&self, f: F) -> (MemoryCollection<B>, MemoryCollection<C>) {
+        let pairs = batch_apply(&self.partitions, move |_idx, vs| {
+            let mut left = Vec::with_capacity(vs.len());
+            let mut right = Vec::with_capacity(vs.len());
+            for v in vs {
+                let (b, c) = f(v);
+                left.push(b);
+                right.push(c);
+            }
+            (left, right)
         });
-        MemoryCollection { partitions: nps }
+        let (left, right) = pairs.iter().map(|p| p.split()).unzip();
+        (MemoryCollection { partitions: left }.named("map_fanout"), 
+         MemoryCollection { partitions: right }.named("map_fanout"))
     }
 
-    /// Inner Joins two collections by the provided key function.
-    /// If multiple values of the same key are found, they will be cross product for each
-    /// pair found.
+    /// Maps a function over the values in the MemoryCollection, like `map`, while reporting
+    /// progress for long partitions.  `progress` is called with the partition index, the
+    /// number of items processed so far and the partition's total every 10,000 items, and 
+    /// once more when the partition is finished.
     /// ```rust
     ///   extern crate tange;
     ///   extern crate tange_collection;
     ///   use tange::scheduler::GreedyScheduler;
     ///   use tange_collection::collection::memory::MemoryCollection;
-    ///
-    ///   let name_age: Vec<(String,u32)> = vec![("Andrew".into(), 33), ("Leah".into(), 12)];
-    ///   let name_money: Vec<(String,f32)> = vec![("Leah".into(), 20.50)];
     ///   
-    ///   let na = MemoryCollection::from_vec(name_age);
-    ///   let nm = MemoryCollection::from_vec(name_money);
-    ///   let joined = na.join_on(&nm,
-    ///                           |nax| nax.0.clone(),
-    ///                           |nmx| nmx.0.clone(),
-    ///                           |nax, nmx| (nax.0.clone(), nax.1, nmx.1),
-    ///                           1);
-    ///   assert_eq!(joined.run(&GreedyScheduler::new()), 
-    ///           Some(vec![("Leah".into(), ("Leah".into(), 12, 20.50))]));
+    ///   let col = MemoryCollection::from_vec((0..25_000usize).collect());
+    ///   let doubled = col.map_progress(|x| x * 2, |idx, done, total| {
+    ///     println!("partition {}: {}/{}", idx, done, total);
+    ///   });
+    ///   assert_eq!(doubled.count().run(&GreedyScheduler::new()), Some(vec![25_000]));
     /// ```
+    pub fn map_progress<
+        B: Any + Send + Sync + Clone, 
+        F: 'static + Sync + Send + Clone + Fn(&A) -> B,
+        P: 'static + Sync + Send + Clone + Fn(usize, usize, usize)
+    >(&self, f: F, progress: P) -> MemoryCollection<B> {
+        let parts = map_progress(&self.partitions, Memory, f, progress);
 
-    pub fn join_on<
-        K: Any + Sync + Send + Clone + Hash + Eq,
-        B: Any + Sync + Send + Clone,
-        C: Any + Sync + Send + Clone,
-        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
-        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
-        J:   'static + Sync + Send + Clone + Fn(&A, &B) -> C,
-    >(
-        &self, 
-        other: &MemoryCollection<B>, 
-        key1: KF1, 
-        key2: KF2,
-        joiner: J,
-        partitions: usize, 
-    ) -> MemoryCollection<(K,C)> {
-        // Group each by a common key
-        let p1 = self.map(move |x| (key1(x), x.clone()))
-            .partition_by_key(partitions, |x| x.0.clone());
-        let p2 = other.map(move |x| (key2(x), x.clone()))
-           .partition_by_key(partitions, |x| x.0.clone());
+        MemoryCollection { partitions: parts }.named("map_progress")
+    }
 
-        let mut new_parts = Vec::with_capacity(p1.partitions.len());
-        for (l, r) in p1.partitions.iter().zip(p2.partitions.iter()) {
-            new_parts.push(jok(l, r, Memory, joiner.clone()));
+    /// Maps a function over the values in the MemoryCollection, like `map`, while updating a
+    /// side metric.  `f` receives the partition's partial value for `side`, which is 
+    /// merged into `side` once the partition is computed.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   use tange_collection::utils::SideAccumulator;
+    ///   
+    ///   let malformed = SideAccumulator::new(0usize, |total, partial| *total += partial);
+    ///   let col = MemoryCollection::from_vec(vec!["1", "x", "3", "y"]);
+    ///   let parsed = col.map_with_accumulator(&malformed, |s, bad| {
+    ///     let n = s.parse::<usize>().ok();
+    ///     if n.is_none() { *bad += 1; }
+    ///     n
+    ///   });
+    ///   assert_eq!(parsed.run(&GreedyScheduler::new()), Some(vec![Some(1), None, Some(3), None]));
+    ///   assert_eq!(malformed.value(), 2);
+    /// ```
+    pub fn map_with_accumulator<
+        B: Any + Send + Sync + Clone,
+        T: 'static + Send + Sync + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&A, &mut T) -> B
+    >(&self, side: &SideAccumulator<T>, f: F) -> MemoryCollection<B> {
+        let parts = map_accumulating(&self.partitions, Memory, side, f);
+
+        MemoryCollection { partitions: parts }.named("map_with_accumulator")
+    }
+
+    /// Maps each item to a vector of values and flattens the results.  This is the fusion
+    /// of `map` and `flatten` in a single pass, avoiding the intermediate collection of
+    /// vectors and its nodes in the graph.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3usize]);
+    ///   let repeated = col.map_flatten(|x| vec![*x; *x]);
+    ///   assert_eq!(repeated.run(&GreedyScheduler::new()), 
+    ///     Some(vec![1, 2, 2, 3, 3, 3]));
+    /// ```
+    pub fn map_flatten<
+        B: Any + Send + Sync + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> Vec<B>
+    >(&self, f: F) -> MemoryCollection<B> {
+        self.emit(move |x, emitter| {
+            for xi in f(x) {
+                emitter(xi);
+            }
+        }).named("map_flatten")
+    }
+
+    /// Maps each item through `f` and then `g` in a single pass.  This is equivalent to
+    /// `map(f).map(g)`, but adds one node to the graph instead of two.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3usize]);
+    ///   let strings = col.map_then(|x| x * 2, |x| format!("{}", x));
+    ///   assert_eq!(strings.run(&GreedyScheduler::new()), 
+    ///     Some(vec!["2".into(), "4".into(), "6".into()]));
+    /// ```
+    pub fn map_then<
+        B: Any + Send + Sync + Clone,
+        C: Any + Send + Sync + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> B,
+        G: 'static + Sync + Send + Clone + Fn(&B) -> C
+    >(&self, f: F, g: G) -> MemoryCollection<C> {
+        self.emit(move |x, emitter| {
+            emitter(g(&f(x)))
+        }).named("map_then")
+    }
+
+    /// Filters out items in the collection that fail the predicate.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3usize]);
+    ///   let odds = col.filter(|x| x % 2 == 1);
+    ///   assert_eq!(odds.run(&GreedyScheduler::new()), 
+    ///     Some(vec![1, 3usize]));
+    /// ```
+
+    pub fn filter<
+        F: 'static + Sync + Send + Clone + Fn(&A) -> bool
+    >(&self, f: F) -> MemoryCollection<A> {
+        self.emit(move |x, emitter| {
+            if f(x) { 
+                emitter(x.clone())
+            }
+        }).named("filter")
+    }
+
+    /// Keeps only the items whose key, as computed by `key`, is in `keys`.  The set is 
+    /// shared by every partition rather than copied into each.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use std::collections::HashSet;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![(1, "a"), (2, "b"), (3, "c")]);
+    ///   let keys: HashSet<_> = vec![1, 3].into_iter().collect();
+    ///   let kept = col.filter_keys_in(keys, |x| x.0);
+    ///   assert_eq!(kept.run(&GreedyScheduler::new()), Some(vec![(1, "a"), (3, "c")]));
+    /// ```
+    pub fn filter_keys_in<
+        K: 'static + Sync + Send + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, keys: HashSet<K>, key: F) -> MemoryCollection<A> {
+        let keys = Arc::new(keys);
+        self.filter(move |x| keys.contains(&key(x))).named("filter_keys_in")
+    }
+
+    /// Checks every item against `f`, splitting the collection into the items which pass
+    /// and the items which fail, each paired with the error `f` returned for it.  The
+    /// checked items are marked with `cache`, so running both outputs on a scheduler with
+    /// a cache, such as `GreedyScheduler::with_cache_capacity`, evaluates `f` only once
+    /// per item.  Other schedulers evaluate `f` again for each output that is run.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![3, -1, 4, -5i32]);
+    ///   let (valid, invalid) = col.validate(|x| if *x >= 0 { Ok(()) } else { Err("negative") });
+    ///   assert_eq!(valid.run(&GreedyScheduler::new()), Some(vec![3, 4]));
+    ///   assert_eq!(invalid.run(&GreedyScheduler::new()), 
+    ///     Some(vec![(-1, "negative"), (-5, "negative")]));
+    /// ```
+    pub fn validate<
+        E: Any + Send + Sync + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> Result<(), E>
+    >(&self, f: F) -> (MemoryCollection<A>, MemoryCollection<(A, E)>) {
+        let checked = self.map(move |x| f(x).map(|_| x.clone()).map_err(|e| (x.clone(), e)))
+            .named("validate")
+            .cache();
+        let valid = checked.flat_map_with_index(|_idx, r| r.clone().ok());
+        let invalid = checked.flat_map_with_index(|_idx, r| r.clone().err());
+        (valid, invalid)
+    }
+    
+    /// Pairs each item with a key derived from it, keeping the original item as the value.
+    /// This is convenient before keyed operations.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec!["a".to_owned(), "bb".into(), "cc".into()]);
+    ///   let keyed = col.key_by(|s| s.len());
+    ///   assert_eq!(keyed.run(&GreedyScheduler::new()), 
+    ///     Some(vec![(1, "a".into()), (2, "bb".into()), (2, "cc".into())]));
+    /// ```
+    pub fn key_by<
+        K: Any + Send + Sync + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, f: F) -> MemoryCollection<(K, A)> {
+        self.map(move |x| (f(x), x.clone())).named("key_by")
+    }
+
+    /// Re-partitions a collection by the number of provided chunks.  It uniformly distributes data from each old partition into each new partition.
+    /// Items are dealt out round-robin by their position within their old partition, so a
+    /// single large partition is subdivided evenly and each new partition keeps the items
+    /// in their original relative order.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3usize]);
+    ///   assert_eq!(col.n_partitions(), 1);
+    ///   let two = col.split(2);
+    ///   assert_eq!(two.n_partitions(), 2);
+    /// ```
+    pub fn split(&self, n_chunks: usize) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::split", n_chunks);
+        self.partition(n_chunks, |idx, _k| idx).named("split")
+    }
+
+    /// Re-partitions the collection like `split` and marks the new partitions for caching,
+    /// so that reusing the result with a caching scheduler, such as 
+    /// `GreedyScheduler::with_cache_capacity`, only shuffles the data once.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let s = GreedyScheduler::new().with_cache_capacity(8);
+    ///   let col = MemoryCollection::from_vec((0..100usize).collect()).repartition_cached(4);
+    ///   assert_eq!(col.n_partitions(), 4);
+    ///   assert_eq!(col.count().run(&s), Some(vec![100]));
+    ///   assert_eq!(col.filter(|x| x % 2 == 0).count().run(&s), Some(vec![50]));
+    /// ```
+    pub fn repartition_cached(&self, n_chunks: usize) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::repartition_cached", n_chunks);
+        self.split(n_chunks).cache()
+    }
+
+    /// Reduces the number of partitions to at most `n_chunks` by concatenating runs of
+    /// neighboring partitions, preserving the order of the data.  Unlike `split`, no data
+    /// is shuffled between partitions.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4,5usize]).split(5);
+    ///   let two = col.coalesce(2);
+    ///   assert_eq!(two.n_partitions(), 2);
+    ///   assert_eq!(two.run(&GreedyScheduler::new()), col.run(&GreedyScheduler::new()));
+    /// ```
+    pub fn coalesce(&self, n_chunks: usize) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::coalesce", n_chunks);
+        let size = self.partitions.len().div_ceil(n_chunks);
+        let new_parts = self.partitions.chunks(size.max(1))
+            .map(|group| concat(group).unwrap())
+            .collect();
+        MemoryCollection { partitions: new_parts }.named("coalesce")
+    }
+
+    /// Merges partitions together into `n_chunks` partitions like `coalesce`, but balances
+    /// the new partitions by size rather than merging neighbors.  Partitions are assigned 
+    /// whole, largest first, to whichever new partition holds the fewest items so far.
+    /// This computes the length of every partition before moving any data.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec((0..10usize).collect())
+    ///     .concat(&MemoryCollection::from_vec(vec![10, 11]))
+    ///     .concat(&MemoryCollection::from_vec((12..20usize).collect()));
+    ///   let two = col.coalesce_balanced(2);
+    ///   assert_eq!(two.n_partitions(), 2);
+    ///   assert_eq!(two.count().run(&GreedyScheduler::new()), Some(vec![20]));
+    /// ```
+    pub fn coalesce_balanced(&self, n_chunks: usize) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::coalesce_balanced", n_chunks);
+        let new_parts = coalesce_balanced(&self.partitions, n_chunks);
+        MemoryCollection { partitions: new_parts }.named("coalesce_balanced")
+    }
+
+    /// Re-partitions the collection into `n_chunks` partitions of near equal size while
+    /// preserving the order of the items: concatenating the new partitions yields the 
+    /// same sequence as concatenating the old ones.  This computes the length of every
+    /// partition before moving any data.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4,5usize]).split(2);
+    ///   let three = col.repartition_ordered(3);
+    ///   assert_eq!(three.n_partitions(), 3);
+    ///   assert_eq!(three.run(&GreedyScheduler::new()), col.run(&GreedyScheduler::new()));
+    /// ```
+    pub fn repartition_ordered(&self, n_chunks: usize) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::repartition_ordered", n_chunks);
+        let new_parts = repartition_ordered(&self.partitions, n_chunks);
+        MemoryCollection { partitions: new_parts }.named("repartition_ordered")
+    }
+
+    /// Re-partitions the collection to have the same number of partitions as `other`, 
+    /// using `split`, so the two can be combined partition by partition with 
+    /// `zip_partitions`.  Only the partition count is matched, not how items are spread
+    /// across partitions.  When the counts already match, the collection is unchanged.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4,5usize]).split(3);
+    ///   let other = MemoryCollection::from_vec(vec!["a", "b"]).split(5);
+    ///   let aligned = col.align_to(&other);
+    ///   assert_eq!(aligned.n_partitions(), 5);
+    /// ```
+    pub fn align_to<B>(&self, other: &MemoryCollection<B>) -> MemoryCollection<A> {
+        let n = other.partitions.len();
+        if self.partitions.len() == n {
+            self.clone()
+        } else {
+            self.split(n)
+        }
+    }
+
+    /// Measures the size of every partition and, if the largest partition holds more than
+    /// `skew_ratio` times the average, re-partitions the collection into the same number
+    /// of near equal partitions with `repartition_ordered`.  Otherwise the collection is
+    /// returned unchanged.  Measuring runs the collection on `s`, so consider caching it
+    /// first to avoid computing it again.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec((0..90usize).collect())
+    ///     .concat(&MemoryCollection::from_vec((90..100usize).collect()));
+    ///   let balanced = col.auto_rebalance(&GreedyScheduler::new(), 1.5);
+    ///   assert_eq!(balanced.n_partitions(), 2);
+    ///   assert_eq!(balanced.count().run(&GreedyScheduler::new()), Some(vec![100]));
+    /// ```
+    pub fn auto_rebalance<S: Scheduler>(&self, s: &S, skew_ratio: f64) -> MemoryCollection<A> {
+        let lengths = batch_apply(&self.partitions, |_idx, vs| vs.len());
+        let sizes = match collect_deferreds(&lengths).run(s) {
+            Some(sizes) => sizes,
+            None => return self.clone()
+        };
+        let total: usize = sizes.iter().sum();
+        let max = sizes.iter().cloned().max().unwrap_or(0);
+        if total == 0 || max as f64 <= skew_ratio * total as f64 / sizes.len() as f64 {
+            self.clone()
+        } else {
+            self.repartition_ordered(sizes.len()).named("auto_rebalance")
+        }
+    }
+
+    /// Maps over all items in a collection, optionally emitting new values.  It can be used
+    /// to efficiently fuse a number of map/filter/flat_map functions into a single method.
+    /// If `f` panics, the panic is re-raised with the partition index and the position of
+    /// the item within its partition, which also applies to `map`, `filter` and friends.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3usize]);
+    ///   let new = col.emit(|item, emitter| {
+    ///     if item % 2 == 0 {
+    ///         emitter(format!("{}!", item));
+    ///     }
+    ///   });
+    ///   assert_eq!(new.run(&GreedyScheduler::new()), Some(vec!["2!".into()]));
+    /// ```
+
+    pub fn emit<
+        B: Any + Send + Sync + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&A, &mut FnMut(B) -> ())
+    >(&self, f: F) -> MemoryCollection<B> {
+        let parts = emit(&self.partitions, Memory, f);
+
+        MemoryCollection { partitions: parts }.named("emit")
+    }
+
+    /// Maps each item to zero or more new values, passing in the item's position within
+    /// its partition alongside the item itself.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec!["a", "b", "c"]);
+    ///   let tagged = col.flat_map_with_index(|idx, item| {
+    ///     vec![format!("{}-{}", idx, item); idx]
+    ///   });
+    ///   assert_eq!(tagged.run(&GreedyScheduler::new()), 
+    ///     Some(vec!["1-b".into(), "2-c".into(), "2-c".into()]));
+    /// ```
+    pub fn flat_map_with_index<
+        B: Any + Send + Sync + Clone,
+        I: IntoIterator<Item=B>,
+        F: 'static + Sync + Send + Clone + Fn(usize, &A) -> I
+    >(&self, f: F) -> MemoryCollection<B> {
+        let parts = flat_map_indexed(&self.partitions, Memory, f);
+
+        MemoryCollection { partitions: parts }.named("flat_map_with_index")
+    }
+
+    /// Pairs every item with its index within the whole collection, in partition order.
+    /// The length of every partition is computed before any items are indexed.  Empty 
+    /// partitions are skipped over rather than taking up indices, so the indices are 
+    /// always contiguous from zero.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec!["a", "b"])
+    ///     .concat(&MemoryCollection::from_vec(vec!["c"]).filter(|_| false))
+    ///     .concat(&MemoryCollection::from_vec(vec!["d"]));
+    ///   let indexed = col.zip_with_index_skipping_empty();
+    ///   assert_eq!(indexed.n_partitions(), 3);
+    ///   assert_eq!(indexed.run(&GreedyScheduler::new()), Some(vec![(0, "a"), (1, "b"), (2, "d")]));
+    /// ```
+    pub fn zip_with_index_skipping_empty(&self) -> MemoryCollection<(usize, A)> {
+        let parts = zip_with_index(&self.partitions, Memory);
+        MemoryCollection { partitions: parts }.named("zip_with_index")
+    }
+
+    /// Maps over all items in a collection, emitting new values.  It can be used
+    /// to efficiently fuse a number of map/filter/flat_map functions into a single method.
+    /// If `f` panics, the panic is re-raised with the partition index and the position of
+    /// the item within its partition, which also applies to `map`, `filter` and friends.
+    /// `emit_to_disk` differs from the original `emit` by writing the emitted values directly
+    /// to disk, returning a DiskCollection instead of MemoryCollection.  This makes it convenient to switch to out-of-core when needed.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3usize]);
+    ///   let new = col.emit_to_disk("/tmp".into(), |item, emitter| {
+    ///     if item % 2 == 0 {
+    ///         emitter(format!("{}!", item));
+    ///     }
+    ///   });
+    ///   assert_eq!(new.run(&GreedyScheduler::new()), Some(vec!["2!".into()]));
+    /// ```
+
+    pub fn emit_to_disk<
+        B: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>,
+        F: 'static + Sync + Send + Clone + Fn(&A, &mut FnMut(B) -> ())
+    >(&self, path: String, f: F) -> DiskCollection<B> {
+        let parts = emit(&self.partitions, Disk::from_str(&path), f);
+
+        DiskCollection::from_stores(path, parts)
+    }
+
+    /// Re-partitions data into N new partitions by the given function.  The user provided
+    /// function is used as a hash function, mapping the returned value to a partition index.
+    /// This makes it useful for managing which partition data ends up!  Indices outside of
+    /// `0..partitions` wrap around modulo the partition count, so `partitions` itself maps
+    /// to partition 0.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4usize]);
+    ///   let new_col = col.partition(2, |idx, x| if *x < 3 { 1 } else { 2 });
+    ///   
+    ///   assert_eq!(new_col.n_partitions(), 2);
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![3, 4, 1, 2]));
+    /// ```
+    pub fn partition<
+        F: 'static + Sync + Send + Clone + Fn(usize, &A) -> usize
+    >(&self, partitions: usize, f: F) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::partition", partitions);
+        let new_chunks = partition(&self.partitions, 
+                                   partitions, 
+                                   f);
+        // Loop over each bucket
+        MemoryCollection { partitions: new_chunks }
+    }
+
+    /// Tags each item with the partition `partition` would send it to, without moving
+    /// any data.  This makes it cheap to inspect a partitioning function before running
+    /// the full shuffle.  Targets wrap modulo `partitions`, as they do in `partition`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4usize]);
+    ///   let tagged = col.assign_partitions(2, |_idx, x| if *x < 3 { 1 } else { 2 });
+    ///   
+    ///   assert_eq!(tagged.n_partitions(), 1);
+    ///   assert_eq!(tagged.run(&GreedyScheduler::new()), 
+    ///     Some(vec![(1, 1), (1, 2), (0, 3), (0, 4)]));
+    /// ```
+    pub fn assign_partitions<
+        F: 'static + Sync + Send + Clone + Fn(usize, &A) -> usize
+    >(&self, partitions: usize, f: F) -> MemoryCollection<(usize, A)> {
+        check_partitions("MemoryCollection::assign_partitions", partitions);
+        self.flat_map_with_index(move |idx, x| Some((f(idx, x) % partitions, x.clone())))
+            .named("assign_partitions")
+    }
+
+    /// Tags each item with the index of the partition it is in and the partition that
+    /// `partition` would send it to, as `(source, target, item)`, without moving any data.
+    /// This makes it possible to audit how a partitioning function moves data between 
+    /// partitions.  Targets wrap modulo `partitions`, as they do in `partition`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4usize]).split(2);
+    ///   let traced = col.trace_shuffle(2, |_idx, x| x / 3);
+    ///   
+    ///   assert_eq!(traced.n_partitions(), 2);
+    ///   assert_eq!(traced.run(&GreedyScheduler::new()), 
+    ///     Some(vec![(0, 0, 1), (0, 1, 3), (1, 0, 2), (1, 1, 4)]));
+    /// ```
+    pub fn trace_shuffle<
+        F: 'static + Sync + Send + Clone + Fn(usize, &A) -> usize
+    >(&self, partitions: usize, f: F) -> MemoryCollection<(usize, usize, A)> {
+        check_partitions("MemoryCollection::trace_shuffle", partitions);
+        let parts = batch_apply(&self.partitions, move |idx, vs| {
+            vs.iter().enumerate()
+                .map(|(i, x)| (idx, f(i, x) % partitions, x.clone()))
+                .collect::<Vec<_>>()
+        });
+        MemoryCollection { partitions: parts }.named("trace_shuffle")
+    }
+
+    /// Re-partitions data into N new partitions by hashing the provided key into the
+    /// valid range of partitions.  Unlike `partition_by_key`, the key only needs to be
+    /// hashable.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4usize]);
+    ///   let new_col = col.partition_by_hash(2, |x| x % 2);
+    ///   
+    ///   assert_eq!(new_col.n_partitions(), 2);
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()).map(|v| v.len()), Some(4));
+    /// ```
+    pub fn partition_by_hash<
+        K: Hash,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, partitions: usize, key: F) -> MemoryCollection<A> {
+        self.partition(partitions, move |_idx, x| hash_key(&key(x)))
+    }
+
+    /// Re-partitions data using the provided Partitioner, which determines both the 
+    /// number of new partitions and where each item ends up.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::partition::RangePartitioner;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 15, 7, 30usize]);
+    ///   let ranged = col.partition_with(RangePartitioner::new(vec![10, 20], |x: &usize| *x));
+    ///   
+    ///   assert_eq!(ranged.n_partitions(), 3);
+    ///   assert_eq!(ranged.run(&GreedyScheduler::new()), Some(vec![1, 7, 15, 30]));
+    /// ```
+    pub fn partition_with<
+        P: 'static + Sync + Send + Clone + Partitioner<A>
+    >(&self, p: P) -> MemoryCollection<A> {
+        let n = p.num_partitions();
+        self.partition(n, move |_idx, x| p.partition_of(x))
+    }
+
+    /// Re-partitions data into N new partitions by placing the hash of the provided key 
+    /// on a consistent-hashing ring.  Changing the number of partitions by one only moves
+    /// about `1 / N` of the keys between partitions, which keeps partitions stable across
+    /// runs where the partition count changes.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4,5,6usize]);
+    ///   let new_col = col.partition_consistent(3, |x| x % 2);
+    ///   
+    ///   assert_eq!(new_col.n_partitions(), 3);
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()).map(|v| v.len()), Some(6));
+    /// ```
+    pub fn partition_consistent<
+        K: Hash,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, partitions: usize, key: F) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::partition_consistent", partitions);
+        self.partition_with(ConsistentHashPartitioner::new(partitions, key))
+    }
+
+    /// Folds and accumulates values across multiple partitions into K new partitions.
+    /// This is also known as a "group by" with a following reducer.
+    ///
+    /// MemoryCollection first performs a block aggregation: that is, it combines values
+    /// within each partition first using the `binop` function.  It then hashes
+    /// each key to a new partition index, where it will then aggregate all keys using the
+    /// `reduce` function.
+    ///
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4,5usize]);
+    ///   // Sum all odds and evens together
+    ///   let group_sum = col.fold_by(|x| x % 2,
+    ///                               || 0usize,
+    ///                               |block_acc, item| {*block_acc += *item},
+    ///                               |part_acc1, part_acc2| {*part_acc1 += *part_acc2},
+    ///                               1)
+    ///                   .sort_by(|x| x.0);
+    ///   
+    ///   assert_eq!(group_sum.n_partitions(), 1);
+    ///   assert_eq!(group_sum.run(&GreedyScheduler::new()), Some(vec![(0, 6), (1, 9)]));
+    /// ```
+
+    pub fn fold_by<K: Any + Sync + Send + Clone + Hash + Eq,
+                   B: Any + Sync + Send + Clone,
+                   D: 'static + Sync + Send + Clone + Fn() -> B, 
+                   F: 'static + Sync + Send + Clone + Fn(&A) -> K, 
+                   O: 'static + Sync + Send + Clone + Fn(&mut B, &A) -> (),
+                   R: 'static + Sync + Send + Clone + Fn(&mut B, &B) -> ()>(
+        &self, key: F, default: D, binop: O, reduce: R, partitions: usize
+    ) -> MemoryCollection<(K,B)> {
+        check_partitions("MemoryCollection::fold_by", partitions);
+        let results = fold_by(&self.partitions, key, default, binop, 
+                              reduce, Vec::with_capacity(0), partitions);
+        MemoryCollection { partitions: results }.named("fold_by")
+    }
+
+    /// Reduces all items sharing a key into a single item using `reduce`, yielding each
+    /// key with its reduced item.  Items are first reduced within each source partition,
+    /// so only one item per key and partition is shuffled; this greatly cuts down the data
+    /// moved for keys with few distinct values.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4, 5usize]).split(2);
+    ///   let maxes = col.reduce_by_key(|x| x % 2, |x, y| *x.max(y), 1).sort_by(|x| x.0);
+    ///   assert_eq!(maxes.run(&GreedyScheduler::new()), Some(vec![(0, 4), (1, 5)]));
+    /// ```
+    pub fn reduce_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        R: 'static + Sync + Send + Clone + Fn(&A, &A) -> A
+    >(&self, key: F, reduce: R, partitions: usize) -> MemoryCollection<(K, A)> {
+        check_partitions("MemoryCollection::reduce_by_key", partitions);
+        let results = reduce_by_key(&self.partitions, key, reduce, Vec::with_capacity(0), partitions);
+        MemoryCollection { partitions: results }.named("reduce_by_key")
+    }
+
+    /// Counts the number of distinct values for each key, such as the number of distinct
+    /// visitors per page.  Each source partition collects the set of distinct values per 
+    /// key, and the sets are merged after co-partitioning by key into `partitions` new
+    /// partitions.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let visits = MemoryCollection::from_vec(vec![("home", "ann"), ("home", "bob"), ("home", "ann"), ("about", "ann")]);
+    ///   let visitors = visits.count_distinct_by_key(|x| x.0.clone(), |x| x.1.clone(), 1)
+    ///     .sort_by(|x| x.0.clone());
+    ///   assert_eq!(visitors.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("about".into(), 1), ("home".into(), 2)]));
+    /// ```
+    pub fn count_distinct_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        V: Any + Sync + Send + Clone + Hash + Eq,
+        FK: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        FV: 'static + Sync + Send + Clone + Fn(&A) -> V
+    >(&self, key: FK, value: FV, partitions: usize) -> MemoryCollection<(K, usize)> {
+        check_partitions("MemoryCollection::count_distinct_by_key", partitions);
+        self.fold_by(key, 
+                     HashSet::new, 
+                     move |set, x| { set.insert(value(x)); }, 
+                     |set, other| {
+                         for v in other.iter() {
+                             set.insert(v.clone());
+                         }
+                     },
+                     partitions)
+            .map(|(k, set)| (k.clone(), set.len()))
+            .named("count_distinct_by_key")
+    }
+
+    /// Maps each item to a key and value, then reduces all values sharing a key into one
+    /// with `reduce`, co-partitioning by key into `partitions` new partitions.  This fuses
+    /// a `map` with `reduce_by_key`: values are reduced within each source partition as 
+    /// they are mapped, so the mapped pairs are never stored.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec!["a b a", "b c"]);
+    ///   let counts = col.emit(|line, emitter| {
+    ///       for word in line.split_whitespace() { emitter(word.to_owned()) }
+    ///     })
+    ///     .map_reduce_by_key(|word| (word.clone(), 1usize), |x, y| x + y, 1)
+    ///     .sort_by(|x| x.0.clone());
+    ///   assert_eq!(counts.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("a".into(), 2), ("b".into(), 2), ("c".into(), 1)]));
+    /// ```
+    pub fn map_reduce_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        V: Any + Sync + Send + Clone,
+        M: 'static + Sync + Send + Clone + Fn(&A) -> (K, V),
+        R: 'static + Sync + Send + Clone + Fn(&V, &V) -> V
+    >(&self, map: M, reduce: R, partitions: usize) -> MemoryCollection<(K, V)> {
+        check_partitions("MemoryCollection::map_reduce_by_key", partitions);
+        let results = map_reduce_by_key(&self.partitions, map, reduce, Vec::with_capacity(0), partitions);
+        MemoryCollection { partitions: results }.named("map_reduce_by_key")
+    }
+
+    /// Groups items by key, keeping the `k` items with the largest `sort_key` for each
+    /// key in descending order.  Only `k` items per key are held at any point, rather than
+    /// the full group.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![("a", 1), ("b", 5), ("a", 3), ("a", 2)]);
+    ///   let top = col.top_k_by_key(2, |x| x.0, |x| x.1, 1).sort_by(|x| x.0);
+    ///   assert_eq!(top.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("a", ("a", 3)), ("a", ("a", 2)), ("b", ("b", 5))]));
+    /// ```
+    pub fn top_k_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        SK: Any + Sync + Send + Clone + Ord,
+        FK: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        FS: 'static + Sync + Send + Clone + Fn(&A) -> SK
+    >(&self, k: usize, key: FK, sort_key: FS, partitions: usize) -> MemoryCollection<(K, A)> {
+        check_partitions("MemoryCollection::top_k_by_key", partitions);
+        self.fold_by(key, move || TopK::new(k), 
+            move |top, x| top.push(sort_key(x), x.clone()),
+            |top, other| top.merge(other), partitions)
+            .map(|x| (x.0.clone(), x.1.sorted()))
+            .explode()
+            .named("top_k_by_key")
+    }
+
+    /// Computes `fold_by`, additionally sorting each output partition by key.  Keys are
+    /// only ordered within a partition; for a total order, fold into a single partition.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec!["b", "a", "c", "a", "b", "a"]).split(2);
+    ///   let counts = col.sorted_fold_by(|x| x.clone(), || 0usize, |acc, _x| *acc += 1, 
+    ///                                   |x, y| *x += *y, 1);
+    ///   assert_eq!(counts.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("a".into(), 3), ("b".into(), 2), ("c".into(), 1)]));
+    /// ```
+    pub fn sorted_fold_by<
+        K: Any + Sync + Send + Clone + Ord + Hash + Eq,
+        B: Any + Sync + Send + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        D: 'static + Sync + Send + Clone + Fn() -> B,
+        O: 'static + Sync + Send + Clone + Fn(&mut B, &A),
+        R: 'static + Sync + Send + Clone + Fn(&mut B, &B)
+    >(
+        &self, key: F, default: D, binop: O, reduce: R, partitions: usize
+    ) -> MemoryCollection<(K, B)> {
+        self.fold_by(key, default, binop, reduce, partitions)
+            .sort_by(|x| x.0.clone())
+    }
+
+    /// Aggregates items into non-overlapping buckets, such as fixed windows of time.  This
+    /// is `fold_by` keyed by the `bucket` function, with each resulting partition sorted by
+    /// bucket.  `utils::time_bucket` is a convenient bucketing function for timestamps.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::utils::time_bucket;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   // (timestamp, value) pairs
+    ///   let col = MemoryCollection::from_vec(vec![(3u64, 1), (12, 2), (17, 3), (5, 4)]);
+    ///   let sums = col.tumbling_reduce(|x| time_bucket(x.0, 10),
+    ///                                  || 0, |acc, x| *acc += x.1, |x, y| *x += *y, 1);
+    ///   assert_eq!(sums.run(&GreedyScheduler::new()), Some(vec![(0, 5), (10, 5)]));
+    /// ```
+    pub fn tumbling_reduce<
+        K: Any + Sync + Send + Clone + Ord + Hash + Eq,
+        B: Any + Sync + Send + Clone,
+        FK: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        D: 'static + Sync + Send + Clone + Fn() -> B,
+        O: 'static + Sync + Send + Clone + Fn(&mut B, &A),
+        R: 'static + Sync + Send + Clone + Fn(&mut B, &B)
+    >(
+        &self, bucket: FK, default: D, binop: O, reduce: R, partitions: usize
+    ) -> MemoryCollection<(K, B)> {
+        self.sorted_fold_by(bucket, default, binop, reduce, partitions)
+    }
+
+    /// Simple function to re-partition values by a given key.  The return key is hashed
+    /// and moduloed by the new partition count to determine where it will end up.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4usize]);
+    ///   let new_col = col.partition_by_key(2, |x| format!("{}", x));
+    ///   
+    ///   assert_eq!(new_col.n_partitions(), 2);
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![4, 1, 2, 3]));
+    /// ```
+    pub fn partition_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, n_chunks: usize, key: F) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::partition_by_key", n_chunks);
+        let results = partition_by_key(&self.partitions, n_chunks, key);
+        let groups = results.into_iter().map(|part| concat(&part).unwrap()).collect();
+        MemoryCollection {partitions: groups}.named("partition_by_key")
+    }
+
+    /// Re-partitions values by a given key like `partition_by_key`, then drops the empty
+    /// partitions, which are common when there are few distinct keys.  Finding them
+    /// requires a count pass over the re-partitioned collection, which is run right away
+    /// with a GreedyScheduler, like `eval`.  The returned collection is still lazy: it
+    /// holds the non-empty partitions of the re-partitioning, which are computed again
+    /// when it is run.  If the count pass fails, no partitions are dropped.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4usize]);
+    ///   let compact = col.partition_by_key_compact(10, |_x| 1);
+    ///   
+    ///   assert_eq!(compact.n_partitions(), 1);
+    ///   assert_eq!(compact.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 4]));
+    /// ```
+    pub fn partition_by_key_compact<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, n_chunks: usize, key: F) -> MemoryCollection<A> {
+        let partitioned = self.partition_by_key(n_chunks, key);
+        let parts = non_empty(&partitioned.partitions, &GreedyScheduler::new());
+        MemoryCollection { partitions: parts }.named("partition_by_key_compact")
+    }
+
+    /// Re-partitions values by a given key and groups them, yielding each key alongside
+    /// all of its values.  This fuses `partition_by_key` with a per-partition grouping:
+    /// chunks are grouped locally and groups are merged as the chunks are concatenated,
+    /// saving a layer of nodes in the graph.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4, 5usize]).split(2);
+    ///   let groups = col.group_shuffle(2, |x| x % 2);
+    ///   let mut results = groups.run(&GreedyScheduler::new()).unwrap();
+    ///   results.sort();
+    ///   assert_eq!(results, vec![(0, vec![2, 4]), (1, vec![1, 3, 5])]);
+    /// ```
+    pub fn group_shuffle<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, partitions: usize, key: F) -> MemoryCollection<(K, Vec<A>)> {
+        check_partitions("MemoryCollection::group_shuffle", partitions);
+        let groups = group_shuffle(&self.partitions, partitions, key, Memory);
+        MemoryCollection { partitions: groups }.named("group_shuffle")
+    }
+
+    /// Re-partitions values by a given key and groups them, like `group_shuffle`, but 
+    /// never yields a group with more than `max_group` values.  Keys with more values are
+    /// yielded several times, each with a chunk of their values, so that a single large 
+    /// key can't exhaust memory.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4, 5, 7usize]).split(2);
+    ///   let groups = col.group_by_key_capped(1, |x| x % 2, 2);
+    ///   let mut sizes: Vec<_> = groups.run(&GreedyScheduler::new()).unwrap()
+    ///     .into_iter().map(|(k, vs)| (k, vs.len())).collect();
+    ///   sizes.sort();
+    ///   assert_eq!(sizes, vec![(0, 2), (1, 2), (1, 2)]);
+    /// ```
+    pub fn group_by_key_capped<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, partitions: usize, key: F, max_group: usize) -> MemoryCollection<(K, Vec<A>)> {
+        check_partitions("MemoryCollection::group_by_key_capped", partitions);
+        assert!(max_group > 0, "MemoryCollection::group_by_key_capped requires groups of at least one value");
+        let parted = self.partition_by_key(partitions, key.clone());
+        let groups = group_capped(&parted.partitions, key, max_group, Memory);
+        MemoryCollection { partitions: groups }.named("group_by_key_capped")
+    }
+
+    /// Re-partitions values such that all values sharing a key stay together, while
+    /// balancing the partition sizes.  Unlike `partition_by_key`, keys aren't hashed into
+    /// partitions: the number of values per key is counted first, and keys are then placed
+    /// largest first onto the partition holding the fewest values so far.  This is useful
+    /// for skewed keys, at the cost of an additional pass to count keys.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 1, 1, 1, 2, 2, 3, 4usize]).split(3);
+    ///   let balanced = col.rebalance_by_key(2, |x| *x);
+    ///   let sizes: Vec<_> = balanced.to_defs().iter()
+    ///     .map(|d| d.run(&GreedyScheduler::new()).unwrap().len())
+    ///     .collect();
+    ///   assert_eq!(sizes, vec![4, 4]);
+    /// ```
+    pub fn rebalance_by_key<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, partitions: usize, key: F) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::rebalance_by_key", partitions);
+        let parts = rebalance_by_key(&self.partitions, partitions, key);
+        MemoryCollection { partitions: parts }.named("rebalance_by_key")
+    }
+
+    /// Re-partitions values by a given key, like `partition_by_key`, but keeps the computed
+    /// key attached to each value.  This saves recomputing the key downstream.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4usize]);
+    ///   let new_col = col.partition_by_key_keyed(2, |x| format!("{}", x));
+    ///   
+    ///   assert_eq!(new_col.n_partitions(), 2);
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("4".into(), 4), ("1".into(), 1), ("2".into(), 2), ("3".into(), 3)]));
+    /// ```
+    pub fn partition_by_key_keyed<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, n_chunks: usize, key: F) -> MemoryCollection<(K, A)> {
+        check_partitions("MemoryCollection::partition_by_key_keyed", n_chunks);
+        self.key_by(key).partition_by_key(n_chunks, |x| x.0.clone())
+    }
+
+    /// Removes items with duplicate keys, co-partitioning by key into `partitions` new
+    /// partitions.  For each key, the first item in collection order is kept.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![(1, "a"), (2, "b"), (1, "c")]);
+    ///   let unique = col.distinct_by(|x| x.0, 1);
+    ///   assert_eq!(unique.run(&GreedyScheduler::new()), Some(vec![(1, "a"), (2, "b")]));
+    /// ```
+    pub fn distinct_by<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, key: F, partitions: usize) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::distinct_by", partitions);
+        let parted = self.partition_by_key(partitions, key.clone());
+        let new_parts = first_by_key(&parted.partitions, key, Memory);
+        MemoryCollection { partitions: new_parts }.named("distinct_by")
+    }
+
+    /// Samples exactly `min(n, total)` items uniformly at random, returning them in a
+    /// single partition.  Each partition keeps a reservoir of `n` candidates, which are
+    /// then merged.  The sample is deterministic for a given `seed` and partitioning.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec((0..100usize).collect()).split(4);
+    ///   let sample = col.sample_n(10, 42).run(&GreedyScheduler::new()).unwrap();
+    ///   assert_eq!(sample.len(), 10);
+    /// ```
+    pub fn sample_n(&self, n: usize, seed: u64) -> MemoryCollection<A> {
+        MemoryCollection { partitions: vec![reservoir(&self.partitions, n, seed)] }.named("sample_n")
+    }
+
+    /// Randomly reorders the collection into `partitions` new partitions.  Items are
+    /// scattered into random partitions and each new partition is then shuffled, drawing
+    /// from generators seeded by `seed`; the same seed and input partitioning always yield
+    /// the same order.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec((0..100usize).collect()).split(4);
+    ///   let mut shuffled = col.shuffle(42, 3).run(&GreedyScheduler::new()).unwrap();
+    ///   assert_ne!(shuffled, (0..100usize).collect::<Vec<_>>());
+    ///   shuffled.sort();
+    ///   assert_eq!(shuffled, (0..100usize).collect::<Vec<_>>());
+    /// ```
+    pub fn shuffle(&self, seed: u64, partitions: usize) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::shuffle", partitions);
+        MemoryCollection { partitions: shuffle(&self.partitions, partitions, seed) }.named("shuffle")
+    }
+
+    /// Sorts values within each partition by a key function.  If a global sort is desired,
+    /// the collection needs to be re-partitioned into a single partition
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1,2,3,4i32]);
+    ///   let new_col = col.sort_by(|x| -*x);
+    ///   
+    ///   assert_eq!(new_col.run(&GreedyScheduler::new()), Some(vec![4, 3, 2, 1]));
+    /// ```
+    pub fn sort_by<
+        K: Ord,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, key: F) -> MemoryCollection<A> {
+        let nps = batch_apply(&self.partitions, move |_idx, vs| {
+            let mut v2: Vec<_> = vs.clone();
+            v2.sort_by_key(|v| key(v));
+            v2
+        });
+        MemoryCollection { partitions: nps }.named("sort_by")
+    }
+
+    /// Sorts each partition by the provided key, then merges the sorted partitions
+    /// pairwise into a single, globally sorted Vec.  Ties keep their partition order.
+    /// This is best suited for small results, as the final merge is held in memory.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![5, 1, 4, 2, 3usize]).split(2);
+    ///   let sorted = col.sorted(|x| *x);
+    ///   assert_eq!(sorted.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 4, 5]));
+    /// ```
+    pub fn sorted<
+        K: Ord,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, key: F) -> Deferred<Vec<A>> {
+        merge_sorted(&self.partitions, key)
+    }
+
+    /// Passes items through unchanged, panicking at evaluation time if any partition is
+    /// not ordered by the provided key.  This is useful for checking custom key functions
+    /// used with `sort_by`.  The panic reports the partition index and offending keys.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![3, 1, 2usize]).sort_by(|x| *x);
+    ///   let checked = col.assert_partitions_sorted(|x| *x);
+    ///   assert_eq!(checked.run(&GreedyScheduler::new()), Some(vec![1, 2, 3]));
+    /// ```
+    pub fn assert_partitions_sorted<
+        K: Ord + Debug,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, key: F) -> MemoryCollection<A> {
+        let nps = batch_apply(&self.partitions, move |idx, vs| {
+            for (i, pair) in vs.windows(2).enumerate() {
+                let (left, right) = (key(&pair[0]), key(&pair[1]));
+                if left > right {
+                    panic!("Partition {} is out of order at position {}: {:?} followed by {:?}",
+                           idx, i + 1, left, right);
+                }
+            }
+            vs.clone()
+        });
+        MemoryCollection { partitions: nps }.named("assert_partitions_sorted")
+    }
+
+    /// Inner Joins two collections by the provided key function.
+    /// If multiple values of the same key are found, they will be cross product for each
+    /// pair found.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///
+    ///   let name_age: Vec<(String,u32)> = vec![("Andrew".into(), 33), ("Leah".into(), 12)];
+    ///   let name_money: Vec<(String,f32)> = vec![("Leah".into(), 20.50)];
+    ///   
+    ///   let na = MemoryCollection::from_vec(name_age);
+    ///   let nm = MemoryCollection::from_vec(name_money);
+    ///   let joined = na.join_on(&nm,
+    ///                           |nax| nax.0.clone(),
+    ///                           |nmx| nmx.0.clone(),
+    ///                           |nax, nmx| (nax.0.clone(), nax.1, nmx.1),
+    ///                           1);
+    ///   assert_eq!(joined.run(&GreedyScheduler::new()), 
+    ///           Some(vec![("Leah".into(), ("Leah".into(), 12, 20.50))]));
+    /// ```
+
+    pub fn join_on<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        B: Any + Sync + Send + Clone,
+        C: Any + Sync + Send + Clone,
+        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
+        J:   'static + Sync + Send + Clone + Fn(&A, &B) -> C,
+    >(
+        &self, 
+        other: &MemoryCollection<B>, 
+        key1: KF1, 
+        key2: KF2,
+        joiner: J,
+        partitions: usize, 
+    ) -> MemoryCollection<(K,C)> {
+        // Group each by a common key
+        let p1 = self.map(move |x| (key1(x), x.clone()))
+            .partition_by_key(partitions, |x| x.0.clone());
+        let p2 = other.map(move |x| (key2(x), x.clone()))
+           .partition_by_key(partitions, |x| x.0.clone());
+
+        let mut new_parts = Vec::with_capacity(p1.partitions.len());
+        for (l, r) in p1.partitions.iter().zip(p2.partitions.iter()) {
+            new_parts.push(jok(l, r, Memory, joiner.clone()));
+        }
+
+        MemoryCollection { partitions: new_parts }.named("join_on")
+    }
+
+    /// Left Joins two collections by the provided key functions.  Every item of this
+    /// collection is paired with each item of `other` sharing its key, or with `default()`
+    /// when there are none, so no items from this collection are dropped.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///
+    ///   let name_age = MemoryCollection::from_vec(vec![("Andrew".to_owned(), 33), ("Leah".into(), 12)]);
+    ///   let name_money = MemoryCollection::from_vec(vec![("Leah".to_owned(), 20.5)]);
+    ///   let joined = name_age.left_join_or(&name_money, |x| x.0.clone(), |x| x.0.clone(), 
+    ///                                      || ("".into(), 0.0), 1)
+    ///     .map(|(name, (age, money))| (name.clone(), age.1, money.1))
+    ///     .sort_by(|x| x.0.clone());
+    ///   assert_eq!(joined.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("Andrew".into(), 33, 0.0), ("Leah".into(), 12, 20.5)]));
+    /// ```
+    pub fn left_join_or<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        B: Any + Sync + Send + Clone,
+        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
+        D:   'static + Sync + Send + Clone + Fn() -> B,
+    >(
+        &self, 
+        other: &MemoryCollection<B>, 
+        key1: KF1, 
+        key2: KF2,
+        default: D,
+        partitions: usize, 
+    ) -> MemoryCollection<(K, (A, B))> {
+        check_partitions("MemoryCollection::left_join_or", partitions);
+        let p1 = self.map(move |x| (key1(x), x.clone()))
+            .partition_by_key(partitions, |x| x.0.clone());
+        let p2 = other.map(move |x| (key2(x), x.clone()))
+            .partition_by_key(partitions, |x| x.0.clone());
+
+        let mut new_parts = Vec::with_capacity(p1.partitions.len());
+        for (l, r) in p1.partitions.iter().zip(p2.partitions.iter()) {
+            new_parts.push(left_join_on_key(l, r, Memory, default.clone()));
+        }
+
+        MemoryCollection { partitions: new_parts }.named("left_join_or")
+    }
+
+    /// Anti Joins two collections by the provided key functions, keeping only the items
+    /// whose key is not present in the other collection.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///
+    ///   let left = MemoryCollection::from_vec(vec![(1, "x"), (2, "y")]);
+    ///   let right = MemoryCollection::from_vec(vec![1]);
+    ///   let missing = left.anti_join(&right, |l| l.0, |r| *r, 2);
+    ///   assert_eq!(missing.run(&GreedyScheduler::new()), Some(vec![(2, "y")]));
+    /// ```
+    pub fn anti_join<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        B: Any + Sync + Send + Clone,
+        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
+    >(
+        &self, 
+        other: &MemoryCollection<B>, 
+        key1: KF1, 
+        key2: KF2,
+        partitions: usize, 
+    ) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::anti_join", partitions);
+        self.filter_on_keys(other, key1, key2, partitions, false)
+    }
+
+    /// Semi Joins two collections by the provided key functions, keeping only the items
+    /// whose key is present in the other collection.  Each item is emitted once, regardless
+    /// of how many matches it has, and no data from the other collection is included.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///
+    ///   let left = MemoryCollection::from_vec(vec![(1, "x"), (2, "y")]);
+    ///   let right = MemoryCollection::from_vec(vec![1, 1]);
+    ///   let matched = left.semi_join(&right, |l| l.0, |r| *r, 2);
+    ///   assert_eq!(matched.run(&GreedyScheduler::new()), Some(vec![(1, "x")]));
+    /// ```
+    pub fn semi_join<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        B: Any + Sync + Send + Clone,
+        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
+    >(
+        &self, 
+        other: &MemoryCollection<B>, 
+        key1: KF1, 
+        key2: KF2,
+        partitions: usize, 
+    ) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::semi_join", partitions);
+        self.filter_on_keys(other, key1, key2, partitions, true)
+    }
+
+    // Co-partitions both collections by key, keeping the items from this collection 
+    // whose key presence in the other matches `keep_matched`.
+    fn filter_on_keys<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        B: Any + Sync + Send + Clone,
+        KF1: 'static + Sync + Send + Clone + Fn(&A) -> K,
+        KF2: 'static + Sync + Send + Clone + Fn(&B) -> K,
+    >(
+        &self, 
+        other: &MemoryCollection<B>, 
+        key1: KF1, 
+        key2: KF2,
+        partitions: usize, 
+        keep_matched: bool
+    ) -> MemoryCollection<A> {
+        let p1 = self.key_by(key1).partition_by_key(partitions, |x| x.0.clone());
+        let p2 = other.map(key2).partition_by_key(partitions, |k| k.clone());
+
+        let mut new_parts = Vec::with_capacity(p1.partitions.len());
+        for (l, r) in p1.partitions.iter().zip(p2.partitions.iter()) {
+            new_parts.push(filter_on_keys(l, r, Memory, keep_matched));
+        }
+
+        MemoryCollection { partitions: new_parts }
+    }
+
+    /// Builds a Bloom filter over the keys of the collection, using `n_bits` bits and
+    /// `n_hashes` hash functions.  Each partition builds its own filter, which are then 
+    /// unioned together.  The resulting filter is useful for cheaply pre-filtering another
+    /// collection before a join with `filter_by_bloom`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3usize]);
+    ///   let bf = col.build_bloom(|x| *x, 1024, 3).run(&GreedyScheduler::new()).unwrap();
+    ///   assert!(bf.contains(&2usize));
+    /// ```
+    pub fn build_bloom<
+        K: Hash,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, key: F, n_bits: usize, n_hashes: usize) -> Deferred<BloomFilter> {
+        bloom(&self.partitions, key, n_bits, n_hashes)
+    }
+
+    /// Keeps only the items whose key might be a member of the provided Bloom filter.  
+    /// Items whose key was inserted into the filter are always kept; due to false 
+    /// positives, some items whose key was not inserted may be kept as well.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3usize]);
+    ///   let bf = col.build_bloom(|x| *x, 1024, 3);
+    ///   let other = MemoryCollection::from_vec(vec![2, 3, 4, 5usize]);
+    ///   let kept = other.filter_by_bloom(&bf, |x| *x).run(&GreedyScheduler::new()).unwrap();
+    ///   assert!(kept.contains(&2) && kept.contains(&3));
+    /// ```
+    pub fn filter_by_bloom<
+        K: Hash,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, bf: &Deferred<BloomFilter>, key: F) -> MemoryCollection<A> {
+        let parts = filter_by_bloom(&self.partitions, bf, key);
+        MemoryCollection { partitions: parts }.named("filter_by_bloom")
+    }
+
+    /// Maps each item with access to a shared context, such as a lookup table, computed
+    /// once by another Deferred.  Every partition is joined against the same context.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3usize]).split(2);
+    ///   let offset = col.len().apply(|n| *n * 10);
+    ///   let shifted = col.map_with_broadcast(&offset, |o, x| o + x);
+    ///   assert_eq!(shifted.run(&GreedyScheduler::new()), Some(vec![31, 33, 32]));
+    /// ```
+    pub fn map_with_broadcast<
+        B: Any + Send + Sync + Clone,
+        C: Any + Send + Sync + Clone,
+        F: 'static + Sync + Send + Clone + Fn(&C, &A) -> B
+    >(&self, ctx: &Deferred<C>, f: F) -> MemoryCollection<B> {
+        let parts = map_with_broadcast(&self.partitions, ctx, Memory, f);
+        MemoryCollection { partitions: parts }.named("map_with_broadcast")
+    }
+
+    /// Executes the Collection, returning the result of the computation
+    pub fn run<S: Scheduler>(&self, s: &S) -> Option<Vec<A>> {
+        let cat = tree_reduce(&self.partitions, |x, y| {
+            let mut v1: Vec<_> = (*x).clone();
+            for yi in y {
+                v1.push(yi.clone());
+            }
+            v1
+        });
+        cat.and_then(|x| x.run(s))
+    }
+
+    /// Executes the Collection like `run`, but concatenates partitions strictly left to
+    /// right, guaranteeing that the items of each partition follow those of all earlier
+    /// partitions.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec((0..20usize).collect()).split(5).sort_by(|x| *x);
+    ///   let out = col.run_ordered(&GreedyScheduler::new()).unwrap();
+    ///   assert_eq!(&out[..4], &[0, 5, 10, 15]);
+    /// ```
+    pub fn run_ordered<S: Scheduler>(&self, s: &S) -> Option<Vec<A>> {
+        let cat = tree_reduce_ordered(&self.partitions, |x, y| {
+            let mut v1: Vec<_> = (*x).clone();
+            for yi in y {
+                v1.push(yi.clone());
+            }
+            v1
+        });
+        cat.and_then(|x| x.run(s))
+    }
+    
+    /// Computes the collection on a background thread, sending each partition down the
+    /// returned channel as soon as it is computed.  Partitions arrive in the order they
+    /// finish, not partition order.  The whole collection is computed in a single run, so
+    /// shared upstream work, such as the map side of a shuffle, is only done once.  The
+    /// channel is closed when the run finishes, or early if a task fails.  Joining the
+    /// returned handle tells the two apart: it returns `Err` if a task panicked, and
+    /// `Ok(None)` if the scheduler couldn't compute the collection.
+    ///
+    /// The scheduler is taken by value, rather than borrowed, because it moves to the
+    /// background thread and must outlive the call.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4usize]).split(2);
+    ///   let (rx, handle) = col.run_to_channel(GreedyScheduler::new());
+    ///   let mut parts: Vec<_> = rx.iter().collect();
+    ///   parts.sort();
+    ///   assert_eq!(parts, vec![vec![1, 3], vec![2, 4]]);
+    ///   assert_eq!(handle.join().unwrap(), Some(()));
+    /// ```
+    pub fn run_to_channel<S: 'static + Scheduler + Send>(&self, s: S) -> (mpsc::Receiver<Vec<A>>, thread::JoinHandle<Option<()>>) {
+        run_to_channel(self.partitions.clone(), s)
+    }
+
+    /// Returns up to the first `n` items of the collection, in partition order.  Partitions
+    /// are computed one at a time and computation stops once `n` items are collected, so
+    /// later partitions are never computed.  This is handy for previewing results.  As each
+    /// partition is run separately, work shared between partitions is repeated for every
+    /// partition computed; after a shuffle, such as `partition_by_key`, each one reruns
+    /// the map side of the shuffle.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4, 5usize]).split(3);
+    ///   assert_eq!(col.first(3, &GreedyScheduler::new()), vec![1, 4, 2]);
+    /// ```
+    pub fn first<S: Scheduler>(&self, n: usize, s: &S) -> Vec<A> {
+        first_n(&self.partitions, n, s)
+    }
+
+    /// Executes the Collection, returning its items in a single Vec for random access by
+    /// global index.  This is the same as `run`; see `collect_with_offsets` to also
+    /// recover the partition boundaries.
+    pub fn collect_indexed<S: Scheduler>(&self, s: &S) -> Option<Vec<A>> {
+        self.run(s)
+    }
+
+    /// Executes the Collection, returning its items in a single Vec alongside the offsets
+    /// of the partition boundaries.  There is one more offset than partitions: partition
+    /// `i` spans `items[offsets[i]..offsets[i + 1]]`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4, 5usize]).split(2);
+    ///   let (items, offsets) = col.collect_with_offsets(&GreedyScheduler::new()).unwrap();
+    ///   assert_eq!(items, vec![1, 3, 5, 2, 4]);
+    ///   assert_eq!(offsets, vec![0, 3, 5]);
+    ///   assert_eq!(&items[offsets[1]..offsets[2]], &[2, 4]);
+    /// ```
+    pub fn collect_with_offsets<S: Scheduler>(&self, s: &S) -> Option<(Vec<A>, Vec<usize>)> {
+        with_offsets(&self.partitions).run(s)
+    }
+
+    /// Executes the Collection, returning the result of the computation
+    pub fn eval(&self) -> Option<Vec<A>> {
+        self.run(&GreedyScheduler::new())
+    }
+
+}
+
+impl <A: Any + Send + Sync + Clone> MemoryCollection<Vec<A>> {
+
+    /// Flattens a vector of values
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![vec![1usize,2],vec![3,4]]);
+    ///   let flattened = col.flatten();
+    ///   assert_eq!(flattened.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 4]));
+    /// ```
+
+    pub fn flatten(&self) -> MemoryCollection<A> {
+        self.emit(move |x, emitter| {
+            for xi in x {
+                emitter(xi.clone());
+            }
+        }).named("flatten")
+    }
+}
+
+impl <K: Any + Send + Sync + Clone, V: Any + Send + Sync + Clone> MemoryCollection<(K, V)> {
+
+    /// Drops the keys from a keyed collection, returning only the values.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![("a", 1), ("b", 2)]);
+    ///   assert_eq!(col.values().run(&GreedyScheduler::new()), Some(vec![1, 2]));
+    /// ```
+    pub fn values(&self) -> MemoryCollection<V> {
+        self.map(|x| x.1.clone())
+    }
+
+    /// Alias for `values`, reading more naturally after a `key_by`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![("a", 1), ("b", 2)]);
+    ///   assert_eq!(col.unkey().run(&GreedyScheduler::new()), Some(vec![1, 2]));
+    /// ```
+    pub fn unkey(&self) -> MemoryCollection<V> {
+        self.values()
+    }
+
+    /// Flips the key and value of each item.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![("a", 1), ("b", 2)]);
+    ///   assert_eq!(col.swap().run(&GreedyScheduler::new()), 
+    ///     Some(vec![(1, "a".into()), (2, "b".into())]));
+    /// ```
+    pub fn swap(&self) -> MemoryCollection<(V, K)> {
+        self.map(|x| (x.1.clone(), x.0.clone()))
+    }
+
+    /// Gathers the keys and values of the collection into two parallel Vecs, a columnar
+    /// layout of the pairs.  Partitions are concatenated in order, so position `i` of both
+    /// columns comes from the same pair.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![("a", 1), ("b", 2)]);
+    ///   assert_eq!(col.to_columns().run(&GreedyScheduler::new()), 
+    ///     Some((vec!["a".into(), "b".into()], vec![1, 2])));
+    /// ```
+    pub fn to_columns(&self) -> Deferred<(Vec<K>, Vec<V>)> {
+        to_columns(&self.partitions)
+    }
+}
+
+impl <K: Any + Send + Sync + Clone, V: Any + Send + Sync + Clone> MemoryCollection<(K, Vec<V>)> {
+
+    /// Emits a `(key, value)` pair for every value in each group, turning grouped
+    /// collections back into rows.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![("a", vec![1, 2]), ("b", vec![])]);
+    ///   assert_eq!(col.explode().run(&GreedyScheduler::new()), 
+    ///     Some(vec![("a", 1), ("a", 2)]));
+    /// ```
+    pub fn explode(&self) -> MemoryCollection<(K, V)> {
+        self.emit(|x, emitter| {
+            for v in x.1.iter() {
+                emitter((x.0.clone(), v.clone()));
+            }
+        }).named("explode")
+    }
+}
+
+impl <K: Any + Send + Sync + Clone + Hash + Eq, V: Any + Send + Sync + Clone> MemoryCollection<(K, V)> {
+
+    /// Executes the Collection, collecting the key/value pairs into a HashMap.  When a 
+    /// key occurs multiple times, the last value in collection order wins.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![("a", 1), ("b", 2), ("a", 3)]);
+    ///   let map = col.collect_map(&GreedyScheduler::new()).unwrap();
+    ///   assert_eq!(map.len(), 2);
+    ///   assert_eq!(map[&"a"], 3);
+    /// ```
+    pub fn collect_map<S: Scheduler>(&self, s: &S) -> Option<HashMap<K, V>> {
+        self.run(s).map(|vs| vs.into_iter().collect())
+    }
+
+    /// Compares this collection, the old version of a keyed dataset, against `other`, the
+    /// new version, classifying every key as added, removed, modified or unchanged.  Both
+    /// collections are re-partitioned by key into `partitions` partitions.  Keys are 
+    /// expected to be unique within each collection.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::Change;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let old = MemoryCollection::from_vec(vec![("a", 1), ("b", 2), ("c", 3)]);
+    ///   let new = MemoryCollection::from_vec(vec![("a", 1), ("b", 5), ("d", 4)]);
+    ///   let mut changes = old.diff(&new, 2).run(&GreedyScheduler::new()).unwrap();
+    ///   changes.sort_by_key(|c| c.0);
+    ///   assert_eq!(changes, vec![("a", Change::Unchanged), ("b", Change::Modified(2, 5)), 
+    ///                ("c", Change::Removed(3)), ("d", Change::Added(4))]);
+    /// ```
+    pub fn diff(&self, other: &MemoryCollection<(K, V)>, partitions: usize) -> MemoryCollection<(K, Change<V>)> 
+            where V: PartialEq {
+        check_partitions("MemoryCollection::diff", partitions);
+        let p1 = self.partition_by_key(partitions, |x| x.0.clone());
+        let p2 = other.partition_by_key(partitions, |x| x.0.clone());
+
+        let mut new_parts = Vec::with_capacity(p1.partitions.len());
+        for (l, r) in p1.partitions.iter().zip(p2.partitions.iter()) {
+            new_parts.push(diff_on_key(l, r, Memory));
+        }
+
+        MemoryCollection { partitions: new_parts }.named("diff")
+    }
+
+    /// Aggregates the values of each key into a `B`.  Within a partition, values are 
+    /// folded into a fresh `default()` with `combine`; the partial aggregates for a key are
+    /// then combined across partitions with `merge`.  Unlike `fold_by`, the functions take
+    /// and return aggregates by value, which suits aggregates whose type differs from the
+    /// values.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![("a", 3), ("b", 2), ("a", 1), ("a", 5)]);
+    ///   let lists = col.aggregate_by_key(Vec::new, 
+    ///                                    |mut acc, v| { acc.push(*v); acc },
+    ///                                    |mut l, r| { l.extend_from_slice(&r); l }, 
+    ///                                    1)
+    ///                  .sort_by(|x| x.0.clone());
+    ///   assert_eq!(lists.run(&GreedyScheduler::new()), 
+    ///     Some(vec![("a".into(), vec![3, 1, 5]), ("b".into(), vec![2])]));
+    /// ```
+    pub fn aggregate_by_key<
+        B: Any + Send + Sync + Clone,
+        D: 'static + Sync + Send + Clone + Fn() -> B,
+        C: 'static + Sync + Send + Clone + Fn(B, &V) -> B,
+        M: 'static + Sync + Send + Clone + Fn(B, B) -> B
+    >(&self, default: D, combine: C, merge: M, partitions: usize) -> MemoryCollection<(K, B)> {
+        let d2 = default.clone();
+        self.fold_by(|x| x.0.clone(), default.clone(), 
+                     move |acc, x| {
+                         let prev = ::std::mem::replace(acc, default());
+                         *acc = combine(prev, &x.1);
+                     },
+                     move |acc, other| {
+                         let prev = ::std::mem::replace(acc, d2());
+                         *acc = merge(prev, other.clone());
+                     },
+                     partitions)
+    }
+}
+
+impl <A: Any + Send + Sync + Clone> MemoryCollection<A> {
+
+    /// Returns the number of items in the collection.  A collection without partitions
+    /// counts as zero items.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![vec![1usize,2],vec![3,4]]);
+    ///   assert_eq!(col.count().run(&GreedyScheduler::new()), Some(vec![2]));
+    ///   let flattened = col.flatten();
+    ///   assert_eq!(flattened.count().run(&GreedyScheduler::new()), Some(vec![4]));
+    /// ```
+    pub fn count(&self) -> MemoryCollection<usize> {
+        let nps = batch_apply(&self.partitions, |_idx, vs| vs.len());
+        let count = tree_reduce(&nps, |x, y| x + y).unwrap_or_else(|| Deferred::lift(0, None));
+        let out = count.apply(|x| vec![*x]);
+        MemoryCollection { partitions: vec![out] }
+    }
+
+    /// Returns the number of items in the collection as a single Deferred value.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3usize]).split(2);
+    ///   assert_eq!(col.len().run(&GreedyScheduler::new()), Some(3));
+    /// ```
+    pub fn len(&self) -> Deferred<usize> {
+        let nps = batch_apply(&self.partitions, |_idx, vs| vs.len());
+        tree_reduce(&nps, |x, y| x + y).unwrap_or_else(|| Deferred::lift(0, None))
+    }
+
+    /// Returns whether the collection has no items.  Each partition stops looking as soon
+    /// as it finds an item, making this cheaper than checking `len`.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3usize]).filter(|x| *x > 5);
+    ///   assert_eq!(col.is_empty().run(&GreedyScheduler::new()), Some(true));
+    /// ```
+    pub fn is_empty(&self) -> Deferred<bool> {
+        let nps = batch_apply(&self.partitions, |_idx, vs| vs.is_empty());
+        tree_reduce(&nps, |x, y| *x && *y).unwrap_or_else(|| Deferred::lift(true, None))
+    }
+
+    /// Counts the number of items falling into each bucket returned by `f`, returning a
+    /// single map.  Each partition counts its own items before the maps are merged.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec!["a", "bb", "cc", "d"]).split(2);
+    ///   let buckets = col.count_buckets(|s| s.len()).run(&GreedyScheduler::new()).unwrap();
+    ///   assert_eq!(buckets[&1], 2);
+    ///   assert_eq!(buckets[&2], 2);
+    /// ```
+    pub fn count_buckets<
+        K: Any + Sync + Send + Clone + Hash + Eq,
+        F: 'static + Sync + Send + Clone + Fn(&A) -> K
+    >(&self, f: F) -> Deferred<HashMap<K, usize>> {
+        count_buckets(&self.partitions, f)
+    }
+
+    /// Reduces all items in the collection into one with `f`, returning the reducing
+    /// Deferred itself so it can be joined into other computations.  Items are reduced
+    /// within each partition before partitions are combined, so `f` should be associative
+    /// and commutative.  The Deferred computes None if the collection has no items.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 4usize]).split(2);
+    ///   let sum = col.reduce_to_deferred(|x, y| x + y);
+    ///   assert_eq!(sum.apply(|s| s.map(|s| s * 10)).run(&GreedyScheduler::new()), Some(Some(100)));
+    /// ```
+    pub fn reduce_to_deferred<
+        F: 'static + Sync + Send + Clone + Fn(&A, &A) -> A
+    >(&self, f: F) -> Deferred<Option<A>> {
+        reduce(&self.partitions, f)
+    }
+}
+
+impl <A: Any + Send + Sync + Clone + PartialEq + Hash + Eq> MemoryCollection<A> {
+
+    /// Computes the frequencies of the items in collection.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 1, 5, 1, 2]);
+    ///   let freqs = col.frequencies(1).sort_by(|x| x.0);
+    ///   assert_eq!(freqs.run(&GreedyScheduler::new()), Some(vec![(1, 3), (2, 2), (5, 1)]));
+    /// ```
+pub fn frequencies(&self, partitions: usize) -> MemoryCollection<(A, usize)> {
+        //self.partition(chunks, |x| x);
+        self.fold_by(|s| s.clone(), 
+                     || 0usize, 
+                     |acc, _l| *acc += 1, 
+                     |x, y| *x += *y, 
+                     partitions)
+    }
+}
+
+impl <A: Any + Send + Sync + Clone + Ord> MemoryCollection<A> {
+
+    /// Sorts each partition by the natural ordering of its items.  Like `sort_by`, items
+    /// are not moved between partitions; see `sort_global` for a total ordering.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![3, 1, 2usize]);
+    ///   assert_eq!(col.sort().run(&GreedyScheduler::new()), Some(vec![1, 2, 3]));
+    /// ```
+    pub fn sort(&self) -> MemoryCollection<A> {
+        self.sort_by(|x| x.clone()).named("sort")
+    }
+
+    /// Sorts the collection into `partitions` partitions by the natural ordering of its 
+    /// items, so that concatenating the partitions in order yields a globally sorted 
+    /// result.  Items are range partitioned using a sample of the data, then each 
+    /// partition is sorted.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![5, 3, 9, 1, 7, 2usize]).split(3);
+    ///   let sorted = col.sort_global(2);
+    ///   assert_eq!(sorted.n_partitions(), 2);
+    ///   assert_eq!(sorted.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 5, 7, 9]));
+    /// ```
+    pub fn sort_global(&self, partitions: usize) -> MemoryCollection<A> {
+        check_partitions("MemoryCollection::sort_global", partitions);
+        let bounds = sample_bounds(&self.partitions, |x: &A| x.clone(), partitions);
+        let shards = range_partition(&self.partitions, &bounds, partitions, |x: &A| x.clone());
+        MemoryCollection { partitions: shards }.sort().named("sort_global")
+    }
+}
+
+impl <A: Any + Send + Sync + Clone + Into<f64>> MemoryCollection<A> {
+
+    /// Computes the arithmetic mean of the collection.  Each partition computes its sum
+    /// and count, which are combined before dividing.  Returns NaN for an empty collection.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec![1, 2, 3, 6u32]).split(2);
+    ///   assert_eq!(col.mean().run(&GreedyScheduler::new()), Some(3.));
+    /// ```
+    pub fn mean(&self) -> Deferred<f64> {
+        mean(&self.partitions)
+    }
+
+    /// Estimates the value at quantile `q` within [0, 1].  Each partition builds a 
+    /// t-digest of its values, which are then merged together, avoiding a global sort.
+    /// Returns NaN for an empty collection.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec((0..1000u32).collect()).split(4);
+    ///   let median = col.approx_quantile(0.5).run(&GreedyScheduler::new()).unwrap();
+    ///   assert!((median - 499.5).abs() < 5.);
+    /// ```
+    pub fn approx_quantile(&self, q: f64) -> Deferred<f64> {
+        self.approx_quantiles(&[q]).apply(|qs| qs[0])
+    }
+
+    /// Estimates the values at a set of quantiles, using a single t-digest.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec((0..1000u32).collect()).split(4);
+    ///   let qs = col.approx_quantiles(&[0., 1.]).run(&GreedyScheduler::new());
+    ///   assert_eq!(qs, Some(vec![0., 999.]));
+    /// ```
+    pub fn approx_quantiles(&self, qs: &[f64]) -> Deferred<Vec<f64>> {
+        let qs = qs.to_vec();
+        digest(&self.partitions, 100.).apply(move |d| {
+            qs.iter().map(|q| d.quantile(*q)).collect()
+        })
+    }
+}
+
+// Writes out data
+impl MemoryCollection<String> {
+
+    /// Writes each record in a collection to disk, newline delimited.
+    /// MemoryCollection will create a new file within the path for each partition.
+    pub fn sink(&self, path: &str) -> MemoryCollection<usize> {
+        self.sink_named(path, |idx| idx.to_string())
+    }
+
+    /// Writes each record in a collection to disk, newline delimited, like `sink`, but 
+    /// names the file written for each partition with `name_fn`, which receives the 
+    /// partition index.
+    pub fn sink_named<
+        N: 'static + Sync + Send + Clone + Fn(usize) -> String
+    >(&self, path: &str, name_fn: N) -> MemoryCollection<usize> {
+        let p: Arc<String> = Arc::new(path.to_owned());
+        let pats = batch_apply(&self.partitions, move |idx, vs| {
+            let p2: Arc<String> = p.clone();
+            let local: &str = &p2;
+            fs::create_dir_all(local)
+                .expect("Welp, something went terribly wrong when creating directory");
+
+            let file = fs::File::create(format!("{}/{}", local, name_fn(idx)))
+                .expect("Issues opening file!");
+            let mut bw = BufWriter::new(file);
+
+            let size = vs.len();
+            for line in vs {
+                bw.write_all(line.as_bytes()).expect("Error writing out line");
+                bw.write_all(b"\n").expect("Error writing out line");
+            }
+
+            vec![size]
+        });
+        
+        MemoryCollection { partitions: pats }
+    }
+
+    /// Writes each record in a collection to disk, newline delimited, like `sink`.  Each
+    /// partition is first written to a hidden temporary file within the path and only renamed
+    /// to its final name once completely written, so readers never observe partial files.
+    pub fn sink_atomic(&self, path: &str) -> MemoryCollection<usize> {
+        let p: Arc<String> = Arc::new(path.to_owned());
+        let pats = batch_apply(&self.partitions, move |idx, vs| {
+            let size = write_atomic(&p, idx, |bw| {
+                let mut size = 0usize;
+                for line in vs {
+                    bw.write_all(line.as_bytes())?;
+                    bw.write_all(b"\n")?;
+                    size += 1;
+                }
+                Ok(size)
+            });
+            vec![size]
+        });
+
+        MemoryCollection { partitions: pats }
+    }
+
+    /// Writes each record in a collection to disk, newline delimited, into at most
+    /// `max_files` files.  Neighboring partitions are coalesced before writing, avoiding
+    /// a large number of small files.
+    pub fn sink_coalesced(&self, path: &str, max_files: usize) -> MemoryCollection<usize> {
+        self.coalesce(max_files).sink(path)
+    }
+
+    /// Writes each record in a collection to disk, newline delimited, writing each 
+    /// contiguous group of `group` partitions into a single file named after the group's
+    /// index.  Returns the number of records written to each file.
+    pub fn sink_grouped(&self, path: &str, group: usize) -> MemoryCollection<usize> {
+        assert!(group > 0, "MemoryCollection::sink_grouped requires groups of at least one partition");
+        let groups = self.partitions.chunks(group)
+            .map(|g| concat(g).unwrap())
+            .collect();
+        MemoryCollection { partitions: groups }.sink(path)
+    }
+
+    /// Writes the entire collection into a single file at `path`, newline delimited, 
+    /// with partitions written in order.  If provided, `separator` is written as its own
+    /// line between each partition.  Returns the number of records written, not counting
+    /// separators.  Partitions are appended to the file one after another, in partition
+    /// order.
+    pub fn sink_single(&self, path: &str, separator: Option<String>) -> Deferred<usize> {
+        sink_single(&self.partitions, path, separator)
+    }
+
+    /// Writes the collection into `partitions` sorted files.  Records are range
+    /// partitioned using a sample of the data, then each shard is sorted before writing,
+    /// so that concatenating the files in index order yields globally sorted output.
+    pub fn sink_sorted(&self, path: &str, partitions: usize) -> MemoryCollection<usize> {
+        check_partitions("MemoryCollection::sink_sorted", partitions);
+        let bounds = sample_bounds(&self.partitions, |s: &String| s.clone(), partitions);
+        let shards = range_partition(&self.partitions, &bounds, partitions, |s: &String| s.clone());
+        let sharded = MemoryCollection { partitions: shards };
+        sharded.sort_by(|s| s.clone()).sink(path)
+    }
+
+    /// Feeds each partition through an external command, Unix pipe style.  A process is
+    /// started per partition, the partition's records are written to its stdin one per
+    /// line, and the lines it writes to stdout become the new partition.  Panics when the
+    /// partition is computed if the command can't be started or exits with a failure.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let col = MemoryCollection::from_vec(vec!["b".to_owned(), "a".into(), "c".into()]);
+    ///   let sorted = col.pipe("sort", Vec::new());
+    ///   assert_eq!(sorted.run(&GreedyScheduler::new()), 
+    ///     Some(vec!["a".into(), "b".into(), "c".into()]));
+    /// ```
+    pub fn pipe(&self, command: &str, args: Vec<String>) -> MemoryCollection<String> {
+        let parts = pipe(&self.partitions, Memory, command, args);
+
+        MemoryCollection { partitions: parts }.named("pipe")
+    }
+}
+
+impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> MemoryCollection<A> {
+
+    /// Copies the MemoryCollection to disk, returning a DiskCollection
+    pub fn to_disk(&self, path: String) -> DiskCollection<A> {
+        DiskCollection::from_memory(path, &self.partitions)
+    }
+
+    /// Marks each partition of the collection for retention at the given storage level, 
+    /// for schedulers with a cache such as `GreedyScheduler::with_cache_capacity`.  
+    /// `StorageLevel::Memory` is equivalent to `cache`.  `StorageLevel::Disk` writes each
+    /// partition to the system's temporary directory and only keeps the file, reading it 
+    /// back whenever the partition is used.  `StorageLevel::MemoryAndDisk` keeps both, so
+    /// a partition evicted from memory is read back from disk rather than recomputed.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::collection::StorageLevel;
+    ///   use tange_collection::collection::memory::MemoryCollection;
+    ///   
+    ///   let s = GreedyScheduler::new().with_cache_capacity(8);
+    ///   let squares = MemoryCollection::from_vec(vec![1,2,3usize]).map(|x| x * x)
+    ///     .persist(StorageLevel::Disk);
+    ///   assert_eq!(squares.run(&s), Some(vec![1, 4, 9]));
+    ///   assert_eq!(squares.map(|x| x + 1).run(&s), Some(vec![2, 5, 10]));
+    /// ```
+    pub fn persist(&self, level: StorageLevel) -> MemoryCollection<A> {
+        self.persist_in(level, ::std::env::temp_dir().to_string_lossy().into_owned())
+    }
+
+    // Persists the collection, writing partitions stored on disk to `dir`
+    fn persist_in(&self, level: StorageLevel, dir: String) -> MemoryCollection<A> {
+        if level == StorageLevel::Memory {
+            return self.cache()
+        }
+
+        let spill = Disk(Arc::new(dir));
+        let parts = self.partitions.iter().map(|d| {
+            let acc = spill.clone();
+            let stored = d.apply(move |vs| acc.write_vec(vs.clone())).cache();
+            let loaded = stored.apply(|fs| fs.stream().into_iter().collect::<Vec<A>>());
+            if level == StorageLevel::MemoryAndDisk { loaded.cache() } else { loaded }
+        }).collect();
+        MemoryCollection { partitions: parts }.named("persist")
+    }
+}
+
+#[cfg(test)]
+mod test_lib {
+    extern crate uuid;
+
+    use super::*;
+    use self::uuid::Uuid;
+    use tange::scheduler::LeveledScheduler;
+
+    // A directory under the temp dir unique to this test run, so that concurrent runs
+    // don't remove each other's output
+    fn test_dir(name: &str) -> ::std::path::PathBuf {
+        ::std::env::temp_dir().join(format!("tange-test-{}-{}", name, Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_fold_by() {
+        let col = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
+        let out = col.fold_by(|x| *x, || 0, |x, _y| *x += 1, |x, y| *x += y, 1);
+        let mut results = out.run(&mut LeveledScheduler::new()).unwrap();
+        results.sort();
+        assert_eq!(results, vec![(1, 2), (2, 2), (3, 1)]);
+    }
+
+    #[test]
+    fn test_fold_by_parts() {
+        let col = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
+        let out = col.fold_by(|x| *x, || 0, |x, _y| *x += 1, |x, y| *x += y, 2);
+        assert_eq!(out.partitions.len(), 2);
+        let mut results = out.run(&mut LeveledScheduler::new()).unwrap();
+        results.sort();
+        assert_eq!(results, vec![(1, 2), (2, 2), (3, 1)]);
+    }
+
+    #[test]
+    fn test_partition_by_key() {
+        let col = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
+        let computed = col.partition_by_key(2, |x| *x)
+            .sort_by(|x| *x);
+        assert_eq!(computed.partitions.len(), 2);
+        let results = computed.run(&mut LeveledScheduler::new()).unwrap();
+        assert_eq!(results, vec![2, 2, 3, 1, 1]);
+    }
+
+    #[test]
+    fn test_partition() {
+        let col = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
+        let computed = col.partition(2, |_idx, x| x % 2)
+            .sort_by(|x| *x);
+        assert_eq!(computed.partitions.len(), 2);
+        let results = computed.run(&mut LeveledScheduler::new()).unwrap();
+        assert_eq!(results, vec![2, 2, 1, 1, 3]);
+    }
+
+    #[test]
+    fn test_count() {
+        let col = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
+        let results = col.split(3).count().run(&mut LeveledScheduler::new()).unwrap();
+        assert_eq!(results, vec![5]);
+    }
+
+    #[test]
+    fn test_join() {
+        let col1 = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
+        let col2 = MemoryCollection::from_vec(
+            vec![(2, 1.23f64), (3usize, 2.34)]);
+        let out = col1.join_on(&col2, |x| *x, |y| y.0, |x, y| {
+            (*x, y.1)
+        }, 5).split(1).sort_by(|x| x.0);
+        let results = out.run(&mut LeveledScheduler::new()).unwrap();
+        let expected = vec![(2, (2, 1.23)), (2, (2, 1.23)), (3, (3, 2.34))];
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_emit() {
+        let results = MemoryCollection::from_vec(vec![1,2,3usize])
+            .emit(|num, emitter| {
+                for i in 0..*num {
+                    emitter(i);
+                }
+            })
+            .sort_by(|x| *x)
+            .run(&mut LeveledScheduler::new()).unwrap();
+        let expected = vec![0, 0, 0, 1, 1, 2];
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_sort() {
+        let results = MemoryCollection::from_vec(vec![1, 3, 2usize])
+            .sort_by(|x| *x)
+            .run(&mut LeveledScheduler::new()).unwrap();
+        let expected = vec![1, 2, 3];
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "MemoryCollection::partition requires at least one partition")]
+    fn test_partition_zero_partitions() {
+        MemoryCollection::from_vec(vec![1,2,3usize]).partition(0, |idx, _x| idx);
+    }
+
+    #[test]
+    #[should_panic(expected = "MemoryCollection::split requires at least one partition")]
+    fn test_split_zero_partitions() {
+        MemoryCollection::from_vec(vec![1,2,3usize]).split(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "MemoryCollection::fold_by requires at least one partition")]
+    fn test_fold_by_zero_partitions() {
+        MemoryCollection::from_vec(vec![1,2,3usize]).fold_by(|x| *x, || 0, |x, _y| *x += 1, |x, y| *x += y, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "MemoryCollection::partition_by_key requires at least one partition")]
+    fn test_partition_by_key_zero_partitions() {
+        MemoryCollection::from_vec(vec![1,2,3usize]).partition_by_key(0, |x| *x);
+    }
+
+    #[test]
+    fn test_flat_map_with_index() {
+        let results = MemoryCollection::from_vec(vec!['a', 'b', 'c', 'd', 'e'])
+            .split(2)
+            .flat_map_with_index(|idx, x| vec![(idx, *x)])
+            .run(&LeveledScheduler::new()).unwrap();
+        let expected = vec![(0, 'a'), (1, 'c'), (2, 'e'), (0, 'b'), (1, 'd')];
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_key_by() {
+        let words = vec!["one", "three", "two", "four"];
+        let results = MemoryCollection::from_vec(words)
+            .key_by(|w| w.len())
+            .sort_by(|x| x.0)
+            .run(&LeveledScheduler::new()).unwrap();
+        let expected = vec![(3, "one"), (3, "two"), (4, "four"), (5, "three")];
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_unkey() {
+        let results = MemoryCollection::from_vec(vec!["a", "bb", "cc"])
+            .key_by(|w| w.len())
+            .unkey()
+            .run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(results, vec!["a", "bb", "cc"]);
+    }
+
+    #[test]
+    fn test_swap() {
+        let results = MemoryCollection::from_vec(vec![(1usize, 'a'), (2, 'b')])
+            .swap()
+            .run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(results, vec![('a', 1), ('b', 2)]);
+    }
+
+    #[test]
+    fn test_approx_quantile() {
+        let col = MemoryCollection::from_vec((0..1000u32).collect()).split(3);
+        let median = col.approx_quantile(0.5).run(&LeveledScheduler::new()).unwrap();
+        assert!((median - 499.5).abs() < 1., "median estimate was {}", median);
+    }
+
+    #[test]
+    fn test_bloom_filter() {
+        let members = MemoryCollection::from_vec((0..1000usize).collect()).split(4);
+        let bf = members.build_bloom(|x| *x, 10_000, 4);
+        let candidates = MemoryCollection::from_vec((0..11000usize).collect()).split(3);
+        let kept = candidates.filter_by_bloom(&bf, |x| *x)
+            .run(&LeveledScheduler::new()).unwrap();
+
+        for i in 0..1000usize {
+            assert!(kept.contains(&i));
+        }
+        let false_positives = kept.iter().filter(|x| **x >= 1000).count();
+        assert!(false_positives < 500, "Too many false positives: {}", false_positives);
+    }
+
+    #[test]
+    fn test_collect_map() {
+        let col = MemoryCollection::from_vec(vec![("a", 1), ("b", 2)]);
+        let results = col.collect_map(&LeveledScheduler::new()).unwrap();
+        let mut expected = HashMap::new();
+        expected.insert("a", 1);
+        expected.insert("b", 2);
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_partition_by_key_keyed() {
+        let col = MemoryCollection::from_vec((0..20usize).collect()).split(3);
+        let keyed = col.partition_by_key_keyed(4, |x| x % 5);
+        assert_eq!(keyed.n_partitions(), 4);
+
+        let mut owners = HashMap::new();
+        let mut total = 0;
+        for (idx, part) in keyed.to_defs().iter().enumerate() {
+            for (k, v) in part.run(&LeveledScheduler::new()).unwrap() {
+                assert_eq!(k, v % 5);
+                assert_eq!(*owners.entry(k).or_insert(idx), idx);
+                total += 1;
+            }
+        }
+        assert_eq!(total, 20);
+    }
+
+    #[test]
+    fn test_anti_join() {
+        let left = MemoryCollection::from_vec(vec![(1, "x"), (2, "y")]);
+        let right = MemoryCollection::from_vec(vec![1]);
+        let results = left.anti_join(&right, |l| l.0, |r| *r, 3)
+            .run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(results, vec![(2, "y")]);
+    }
+
+    #[test]
+    fn test_semi_join() {
+        let left = MemoryCollection::from_vec(vec![(1, "x"), (2, "y")]);
+        let right = MemoryCollection::from_vec(vec![(1, 'a'), (1, 'b')]);
+        let results = left.semi_join(&right, |l| l.0, |r| r.0, 3)
+            .run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(results, vec![(1, "x")]);
+    }
+
+    #[test]
+    fn test_map_flatten() {
+        let col = MemoryCollection::from_vec(vec![1, 2, 3usize]).split(2);
+        let fused = col.map_flatten(|x| vec![*x; *x]);
+        let unfused = col.map(|x| vec![*x; *x]).flatten();
+
+        let count = |c: &MemoryCollection<usize>| -> usize {
+            c.partitions.iter().map(|p| p.node_count()).sum()
+        };
+        assert!(count(&fused) < count(&unfused));
+        assert_eq!(count(&unfused) - count(&fused), fused.n_partitions());
+
+        let expected = Some(vec![1, 3, 3, 3, 2, 2]);
+        assert_eq!(fused.run(&LeveledScheduler::new()), expected);
+        assert_eq!(unfused.run(&LeveledScheduler::new()), expected);
+    }
+
+    #[test]
+    fn test_explode() {
+        let col = MemoryCollection::from_vec(vec![("a", vec![1, 2])]);
+        let results = col.explode().run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(results, vec![("a", 1), ("a", 2)]);
+    }
+
+    #[test]
+    fn test_sorted() {
+        let parts = vec![vec![(1, 'a'), (4, 'b')], vec![(2, 'c'), (4, 'd')], vec![(0, 'e'), (3, 'f')]];
+        let col = MemoryCollection::from_defs(parts.into_iter().map(|p| Deferred::lift(p, None)).collect());
+        let results = col.sorted(|x| x.0).run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(results, vec![(0, 'e'), (1, 'a'), (2, 'c'), (3, 'f'), (4, 'b'), (4, 'd')]);
+    }
+
+    #[test]
+    fn test_top_k_by_key() {
+        let scores = vec![("fruit", "apple", 3), ("veg", "kale", 1), ("fruit", "pear", 9),
+                          ("veg", "leek", 4), ("fruit", "plum", 5), ("veg", "okra", 2)];
+        let col = MemoryCollection::from_vec(scores).split(3);
+        let mut results = col.top_k_by_key(2, |x| x.0, |x| x.2, 2)
+            .map(|x| (x.0, (x.1).1))
+            .run(&LeveledScheduler::new()).unwrap();
+        results.sort();
+        assert_eq!(results, vec![("fruit", "pear"), ("fruit", "plum"), ("veg", "leek"), ("veg", "okra")]);
+    }
+
+    #[test]
+    fn test_top_k_by_key_ties() {
+        // Tied items are kept in the order they were added, across partitions too
+        let col = MemoryCollection::from_defs(vec![
+            Deferred::lift(vec![("a", 1), ("a", 2)], None),
+            Deferred::lift(vec![("a", 3), ("a", 4), ("a", 5)], None)
+        ]);
+        let top = col.top_k_by_key(3, |x| x.0, |_x| 0usize, 1)
+            .map(|x| (x.1).1)
+            .run(&LeveledScheduler::new());
+        assert_eq!(top, Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn test_top_k_by_key_large_k() {
+        // Each heap only grows with the items it holds, so a huge k over many keys is cheap
+        let col = MemoryCollection::from_range(0, 100_000, 4);
+        let top = col.top_k_by_key(usize::MAX, |x| *x, |x| *x, 4)
+            .map(|x| x.1)
+            .run(&LeveledScheduler::new())
+            .unwrap();
+        assert_eq!(top.len(), 100_000);
+        assert_eq!(top.iter().sum::<i64>(), (0..100_000i64).sum());
+    }
+
+    #[test]
+    fn test_partition_out_of_range() {
+        let col = MemoryCollection::from_vec(vec![1, 2, 3, 4usize]);
+        let new_col = col.partition(3, |_idx, x| x + 2);
+        assert_eq!(new_col.n_partitions(), 3);
+        // 3 -> 0, 4 -> 1, 5 -> 2, 6 -> 0
+        assert_eq!(new_col.run(&LeveledScheduler::new()), Some(vec![1, 4, 2, 3]));
+    }
+
+    #[test]
+    fn test_partition_by_hash() {
+        let col = MemoryCollection::from_vec((0..100usize).collect());
+        let new_col = col.partition_by_hash(4, |x| x % 10);
+        assert_eq!(new_col.n_partitions(), 4);
+        let parts: Vec<_> = new_col.partitions.iter()
+            .map(|p| p.run(&LeveledScheduler::new()).unwrap())
+            .collect();
+        for part in parts.iter() {
+            let keys: Vec<_> = part.iter().map(|x| x % 10).collect();
+            for other in parts.iter().filter(|o| *o != part) {
+                assert!(other.iter().all(|x| !keys.contains(&(x % 10))));
+            }
+        }
+        assert_eq!(parts.iter().map(|p| p.len()).sum::<usize>(), 100);
+    }
+
+    #[test]
+    fn test_mean() {
+        let col = MemoryCollection::from_vec((0..101u32).collect()).split(7);
+        assert_eq!(col.mean().run(&LeveledScheduler::new()), Some(50.));
+
+        let empty: MemoryCollection<u32> = MemoryCollection::from_vec(Vec::new());
+        assert!(empty.mean().run(&LeveledScheduler::new()).unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_sink_coalesced() {
+        let path = test_dir("sink-coalesced");
+        let path = path.to_str().unwrap();
+
+        let lines: Vec<String> = (0..100).map(|i| format!("{}", i)).collect();
+        let col = MemoryCollection::from_vec(lines.clone()).split(10);
+        assert_eq!(col.n_partitions(), 10);
+
+        let counts = col.sink_coalesced(path, 3).run(&LeveledScheduler::new()).unwrap();
+        assert!(counts.len() <= 3);
+        assert_eq!(counts.iter().sum::<usize>(), 100);
+
+        let mut written = Vec::new();
+        let mut n_files = 0;
+        for entry in fs::read_dir(path).unwrap() {
+            n_files += 1;
+            let contents = fs::read_to_string(entry.unwrap().path()).unwrap();
+            written.extend(contents.lines().map(|l| l.to_owned()));
+        }
+        assert!(n_files <= 3);
+        written.sort();
+        let mut expected = lines;
+        expected.sort();
+        assert_eq!(written, expected);
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_zip_partitions() {
+        let left = MemoryCollection::from_vec(vec![1, 3, 5, 7usize]).split(2);
+        let right = MemoryCollection::from_vec(vec![2, 4, 6usize]).split(2);
+        let woven = left.zip_partitions(&right, |l, r| {
+            let mut out = Vec::new();
+            for i in 0..l.len().max(r.len()) {
+                out.extend(l.get(i).cloned());
+                out.extend(r.get(i).cloned());
+            }
+            out
+        });
+        assert_eq!(woven.n_partitions(), 2);
+        assert_eq!(woven.run(&LeveledScheduler::new()), Some(vec![1, 2, 5, 6, 3, 4, 7]));
+    }
+
+    #[test]
+    #[should_panic(expected = "MemoryCollection::zip_partitions requires equal partition counts")]
+    fn test_zip_partitions_mismatched() {
+        let left = MemoryCollection::from_vec(vec![1, 2usize]).split(2);
+        let right = MemoryCollection::from_vec(vec![3usize]);
+        left.zip_partitions(&right, |l, _r| l.clone());
+    }
+
+    #[test]
+    #[should_panic(expected = "Partition 1 is out of order at position 1: 4 followed by 3")]
+    fn test_assert_partitions_sorted() {
+        let parts = vec![vec![1, 2usize], vec![4, 3]];
+        let col = MemoryCollection::from_defs(parts.into_iter().map(|p| Deferred::lift(p, None)).collect());
+        col.assert_partitions_sorted(|x| *x).run(&LeveledScheduler::new());
+    }
+
+    #[test]
+    fn test_count_buckets() {
+        let col = MemoryCollection::from_vec((0..10usize).collect()).split(3);
+        let buckets = col.count_buckets(|x| x % 2 == 0).run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[&true], 5);
+        assert_eq!(buckets[&false], 5);
+    }
+
+    #[test]
+    fn test_sink_single() {
+        let dir = test_dir("sink-single");
+        let path = dir.join("out.txt");
+        let path = path.to_str().unwrap();
+
+        let parts = vec![vec!["a".to_owned(), "b".into()], vec!["c".into()], vec!["d".into()]];
+        let col = MemoryCollection::from_defs(parts.into_iter().map(|p| Deferred::lift(p, None)).collect());
+
+        let written = col.sink_single(path, None).run(&LeveledScheduler::new());
+        assert_eq!(written, Some(4));
+        assert_eq!(fs::read_to_string(path).unwrap(), "a\nb\nc\nd\n");
+
+        let written = col.sink_single(path, Some("--".into())).run(&LeveledScheduler::new());
+        assert_eq!(written, Some(4));
+        assert_eq!(fs::read_to_string(path).unwrap(), "a\nb\n--\nc\n--\nd\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_map_with_carry() {
+        let parts: Vec<_> = (1..6usize).map(|x| Deferred::lift(vec![x], None)).collect();
+        let col = MemoryCollection::from_defs(parts);
+        let running = col.map_with_carry(|| 0usize, 
+            |c, vs| c + vs.iter().sum::<usize>(), 
+            |c, x| c + x);
+        assert_eq!(running.n_partitions(), 5);
+        assert_eq!(running.run(&LeveledScheduler::new()), Some(vec![1, 3, 6, 10, 15]));
+    }
+
+    #[test]
+    fn test_len() {
+        let col = MemoryCollection::from_vec((0..250usize).collect()).split(7);
+        assert_eq!(col.len().run(&LeveledScheduler::new()), Some(250));
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let empty: MemoryCollection<usize> = MemoryCollection::from_vec(Vec::new()).split(3);
+        assert_eq!(empty.is_empty().run(&LeveledScheduler::new()), Some(true));
+
+        let col = MemoryCollection::from_vec(vec![1usize]).split(3);
+        assert_eq!(col.is_empty().run(&LeveledScheduler::new()), Some(false));
+    }
+
+    #[test]
+    fn test_partition_with() {
+        use partition::{HashPartitioner,RangePartitioner};
+
+        let col = MemoryCollection::from_vec((0..20usize).collect()).split(3);
+        let hashed = col.partition_with(HashPartitioner::new(4, |x: &usize| x % 5));
+        assert_eq!(hashed.n_partitions(), 4);
+        let mut results = hashed.run(&LeveledScheduler::new()).unwrap();
+        results.sort();
+        assert_eq!(results, (0..20usize).collect::<Vec<_>>());
+
+        let ranged = col.partition_with(RangePartitioner::new(vec![5, 15], |x: &usize| *x));
+        let parts: Vec<_> = ranged.partitions.iter()
+            .map(|p| {
+                let mut v = p.run(&LeveledScheduler::new()).unwrap();
+                v.sort();
+                v
+            }).collect();
+        assert_eq!(parts, vec![(0..5).collect::<Vec<_>>(), (5..15).collect(), (15..20).collect()]);
+    }
+
+    #[test]
+    fn test_for_each_partition() {
+        use std::sync::Mutex;
+
+        let col = MemoryCollection::from_vec((0..10usize).collect()).split(3);
+        let sizes = Arc::new(Mutex::new(HashMap::new()));
+        let s2 = sizes.clone();
+        let done = col.for_each_partition(move |idx, vs| {
+            s2.lock().unwrap().insert(idx, vs.len());
+        });
+        assert_eq!(done.run(&LeveledScheduler::new()), Some(()));
+
+        let sizes = sizes.lock().unwrap();
+        assert_eq!(sizes.len(), 3);
+        assert_eq!(sizes[&0], 4);
+        assert_eq!(sizes[&1], 3);
+        assert_eq!(sizes[&2], 3);
+    }
+
+    #[test]
+    fn test_distinct_by() {
+        let parts = vec![vec![(1, "ann", 30), (2, "bob", 41)], 
+                         vec![(1, "ann b.", 31), (3, "cy", 22), (2, "bobby", 42)]];
+        let col = MemoryCollection::from_defs(parts.into_iter().map(|p| Deferred::lift(p, None)).collect());
+        let mut results = col.distinct_by(|p| p.0, 3).run(&LeveledScheduler::new()).unwrap();
+        results.sort();
+        assert_eq!(results, vec![(1, "ann", 30), (2, "bob", 41), (3, "cy", 22)]);
+    }
+
+    #[test]
+    fn test_window_global() {
+        let parts = vec![vec![1, 2], vec![3, 4]];
+        let col = MemoryCollection::from_defs(parts.into_iter().map(|p| Deferred::lift(p, None)).collect());
+        let windows = col.window_global(2).run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(windows, vec![vec![1, 2], vec![2, 3], vec![3, 4]]);
+
+        // Windows can span more than one following partition
+        let parts = vec![vec![1], vec![2], vec![3, 4]];
+        let col = MemoryCollection::from_defs(parts.into_iter().map(|p| Deferred::lift(p, None)).collect());
+        let windows = col.window_global(3).run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(windows, vec![vec![1, 2, 3], vec![2, 3, 4]]);
+    }
+
+    #[test]
+    fn test_sink_sorted() {
+        let path = test_dir("sink-sorted");
+        let path = path.to_str().unwrap();
+
+        let lines: Vec<String> = (0..500).map(|i| format!("{}", (i * 7919) % 1000)).collect();
+        let col = MemoryCollection::from_vec(lines.clone()).split(5);
+        let counts = col.sink_sorted(path, 4).run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts.iter().sum::<usize>(), 500);
+        assert!(counts.iter().all(|c| *c > 0));
+
+        let mut written = Vec::new();
+        for idx in 0..4 {
+            let contents = fs::read_to_string(format!("{}/{}", path, idx)).unwrap();
+            written.extend(contents.lines().map(|l| l.to_owned()));
+        }
+        let mut expected = lines;
+        expected.sort();
+        assert_eq!(written, expected);
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_map_then() {
+        let col = MemoryCollection::from_vec(vec![1, 2, 3usize]);
+        let fused = col.map_then(|x| x + 1, |x| x * 10);
+        let unfused = col.map(|x| x + 1).map(|x| x * 10);
+
+        let base = col.partitions[0].node_count();
+        assert_eq!(fused.partitions[0].node_count() - base, 1);
+        assert_eq!(unfused.partitions[0].node_count() - base, 2);
+        assert_eq!(fused.run(&LeveledScheduler::new()), Some(vec![20, 30, 40]));
+        assert_eq!(fused.run(&LeveledScheduler::new()), unfused.run(&LeveledScheduler::new()));
+    }
+
+    #[test]
+    fn test_sample_n() {
+        let col = MemoryCollection::from_vec((0..1000usize).collect()).split(8);
+        let first = col.sample_n(100, 7).run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(first.len(), 100);
+        assert_eq!(first, col.sample_n(100, 7).run(&GreedyScheduler::new()).unwrap());
+        assert_ne!(first, col.sample_n(100, 8).run(&GreedyScheduler::new()).unwrap());
+
+        let mut unique = first.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), 100);
+
+        let small = MemoryCollection::from_vec(vec![1, 2, 3usize]).split(2);
+        let mut all = small.sample_n(100, 7).run(&LeveledScheduler::new()).unwrap();
+        all.sort();
+        assert_eq!(all, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sample_n_larger_than_collection() {
+        let col = MemoryCollection::from_vec((0..10usize).collect()).split(4);
+        for &n in &[1_000_000, usize::MAX] {
+            let mut all = col.sample_n(n, 7).run(&LeveledScheduler::new()).unwrap();
+            all.sort();
+            assert_eq!(all, (0..10usize).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_repartition_ordered() {
+        let parts = vec![vec![0, 1, 2, 3, 4, 5, 6], vec![7], vec![], vec![8, 9]];
+        let col = MemoryCollection::from_defs(parts.into_iter().map(|p| Deferred::lift(p, None)).collect());
+        let three = col.repartition_ordered(3);
+        assert_eq!(three.n_partitions(), 3);
+        assert_eq!(three.run(&LeveledScheduler::new()), Some((0..10).collect()));
+
+        let sizes: Vec<_> = three.partitions.iter()
+            .map(|p| p.run(&LeveledScheduler::new()).unwrap().len())
+            .collect();
+        assert_eq!(sizes, vec![3, 3, 4]);
+    }
+
+    #[test]
+    fn test_tumbling_reduce() {
+        use utils::time_bucket;
+
+        // (seconds, clicks)
+        let events = vec![(1u64, 1usize), (4, 2), (9, 1), (10, 5), (15, 1), (31, 7), (39, 1)];
+        let col = MemoryCollection::from_vec(events).split(3);
+        let mut results = col.tumbling_reduce(|e| time_bucket(e.0, 10), 
+                                              || 0usize, 
+                                              |acc, e| *acc += e.1, 
+                                              |x, y| *x += *y, 
+                                              2)
+            .run(&LeveledScheduler::new()).unwrap();
+        results.sort();
+        assert_eq!(results, vec![(0, 4), (10, 6), (30, 8)]);
+    }
+
+    #[test]
+    fn test_explain() {
+        let col = MemoryCollection::from_vec(vec![3, 1, 2usize]).split(2)
+            .map(|x| x * 2)
+            .filter(|x| *x > 2)
+            .sort_by(|x| *x);
+        let plan = col.explain();
+        assert!(plan.starts_with("MemoryCollection with 2 partition(s)\n"));
+        let lines: Vec<_> = plan.lines().skip(1).collect();
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            assert!(line.ends_with("Task<name=map> -> Task<name=filter> -> Task<name=sort_by>"),
+                    "Unexpected plan: {}", line);
+        }
+    }
+
+    #[test]
+    fn test_explain_named_combinators() {
+        let col = MemoryCollection::from_vec(vec![(1, vec![3, 1]), (2, vec![2usize])]).split(2)
+            .explode()
+            .coalesce(1)
+            .distinct_by(|x| x.1, 1)
+            .repartition_ordered(1);
+        let plan = col.explain();
+        let line = plan.lines().nth(1).unwrap();
+        for name in &["explode", "coalesce", "distinct_by", "repartition_ordered"] {
+            assert!(line.contains(&format!("Task<name={}>", name)), "Unexpected plan: {}", line);
+        }
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let col = MemoryCollection::from_vec((0..1000usize).collect()).split(5);
+        let first = col.shuffle(7, 4);
+        assert_eq!(first.n_partitions(), 4);
+        let first = first.run(&LeveledScheduler::new()).unwrap();
+        let second = col.shuffle(7, 4).run(&LeveledScheduler::new()).unwrap();
+        let other = col.shuffle(8, 4).run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(first, second);
+        assert!(first != other);
+
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..1000usize).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_map_with_broadcast() {
+        let col = MemoryCollection::from_vec((0..6usize).collect()).split(3);
+        let offset = Deferred::lift(vec![100, 200usize], None)
+            .apply(|vs| vs.iter().sum::<usize>());
+        let results = col.map_with_broadcast(&offset, |o, x| o + x)
+            .run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(results, vec![300, 303, 301, 304, 302, 305]);
+    }
+
+    #[test]
+    fn test_group_shuffle() {
+        let col = MemoryCollection::from_vec((0..20usize).collect()).split(4);
+        let fused = col.group_shuffle(3, |x| x % 5);
+        let mut results = fused.run(&LeveledScheduler::new()).unwrap();
+        results.sort();
+        let expected: Vec<_> = (0..5usize)
+            .map(|k| (k, (0..20usize).filter(|x| x % 5 == k).collect::<Vec<_>>()))
+            .collect();
+        for (_, vs) in &results {
+            assert_eq!(vs.len(), 4);
+        }
+        let mut sorted: Vec<_> = results.into_iter()
+            .map(|(k, mut vs)| { vs.sort(); (k, vs) })
+            .collect();
+        sorted.sort();
+        assert_eq!(sorted, expected);
+
+        // Grouping after partitioning takes an additional layer of nodes
+        let two_step = batch_apply(col.partition_by_key(3, |x| x % 5).to_defs(), |_idx, vs| {
+            let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+            for v in vs {
+                groups.entry(v % 5).or_default().push(*v);
+            }
+            groups.into_iter().collect::<Vec<_>>()
+        });
+        let fused_nodes: usize = fused.to_defs().iter().map(|d| d.node_count()).sum();
+        let two_step_nodes: usize = two_step.iter().map(|d| d.node_count()).sum();
+        assert!(fused_nodes < two_step_nodes);
+    }
+
+    #[test]
+    fn test_run_to_channel() {
+        let col = MemoryCollection::from_vec((0..100usize).collect()).split(4);
+        let (rx, handle) = col.run_to_channel(LeveledScheduler::new());
+        let mut parts: Vec<_> = rx.iter().collect();
+        assert_eq!(handle.join().unwrap(), Some(()));
+        assert_eq!(parts.len(), 4);
+        parts.sort_by_key(|p| p[0]);
+        for (idx, part) in parts.iter().enumerate() {
+            assert_eq!(part.len(), 25);
+            assert!(part.iter().all(|x| x % 4 == idx));
+        }
+        let mut all: Vec<_> = parts.into_iter().flatten().collect();
+        all.sort();
+        assert_eq!(all, (0..100usize).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_run_to_channel_shared_work() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c2 = calls.clone();
+        // Every output partition of the split depends on the same mapped input
+        let col = MemoryCollection::from_vec((0..100usize).collect())
+            .map(move |x| { c2.fetch_add(1, Ordering::SeqCst); *x })
+            .split(4);
+        let parts: Vec<_> = col.run_to_channel(GreedyScheduler::new()).0.iter().collect();
+        assert_eq!(parts.iter().map(|p| p.len()).sum::<usize>(), 100);
+        assert_eq!(calls.load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn test_run_to_channel_failure() {
+        let col = MemoryCollection::from_defs(vec![
+            Deferred::lift(vec![1, 2usize], None),
+            Deferred::lift(vec![3, 4usize], None)
+        ]);
+        let (rx, handle) = col
+            .map(|x| if *x == 3 { panic!("bad item {}", x) } else { *x })
+            .run_to_channel(GreedyScheduler::new());
+        // The stream ends early, and the handle reports why
+        let parts: Vec<_> = rx.iter().collect();
+        assert!(parts.len() < 2);
+        assert!(handle.join().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Panicked on element 2 of partition 1: bad item 7")]
+    fn test_map_panic_context() {
+        let col = MemoryCollection::from_defs(vec![
+            Deferred::lift(vec![1, 2, 3usize], None),
+            Deferred::lift(vec![5, 6, 7, 8usize], None)
+        ]);
+        col.map(|x| if *x == 7 { panic!("bad item {}", x) } else { *x })
+            .run(&LeveledScheduler::new());
+    }
+
+    #[test]
+    fn test_collect_with_offsets() {
+        let parts = [vec![1, 2, 3usize], vec![], vec![4], vec![5, 6]];
+        let col = MemoryCollection::from_defs(parts.iter()
+            .map(|p| Deferred::lift(p.clone(), None)).collect());
+        let s = LeveledScheduler::new();
+        let (items, offsets) = col.collect_with_offsets(&s).unwrap();
+        assert_eq!(offsets, vec![0, 3, 3, 4, 6]);
+        for (i, p) in parts.iter().enumerate() {
+            assert_eq!(&items[offsets[i]..offsets[i + 1]], &p[..]);
+        }
+        assert_eq!(col.collect_indexed(&s), Some(items));
+    }
+
+    #[test]
+    fn test_reduce_by_key() {
+        let col = MemoryCollection::from_vec((0..1000usize).collect()).split(4);
+        let mut results = col.reduce_by_key(|x| x % 3, |x, y| x + y, 2)
+            .run(&LeveledScheduler::new()).unwrap();
+        results.sort();
+        let expected: Vec<_> = (0..3usize)
+            .map(|k| (k, (0..1000usize).filter(|x| x % 3 == k).sum()))
+            .collect();
+        assert_eq!(results, expected);
+
+        // Only one item per key and source partition crosses the shuffle, rather than
+        // every item as with partitioning first
+        let combined = ::partitioned::combine_by_key(&col.partitions, |x| x % 3, |x, y| x + y, Vec::new());
+        let s = LeveledScheduler::new();
+        let shuffled: usize = combined.iter().map(|d| d.run(&s).unwrap().len()).sum();
+        let naive: usize = col.partition_by_key(2, |x| x % 3).to_defs().iter()
+            .map(|d| d.run(&s).unwrap().len()).sum();
+        assert_eq!(shuffled, 12);
+        assert_eq!(naive, 1000);
+    }
+
+    #[test]
+    fn test_assign_partitions() {
+        let col = MemoryCollection::from_vec((0..20usize).collect()).split(3);
+        let f = |idx: usize, x: &usize| idx * 7 + x;
+        let s = LeveledScheduler::new();
+        let tagged = col.assign_partitions(4, f);
+        assert_eq!(tagged.n_partitions(), 3);
+
+        let expected: Vec<_> = col.to_defs().iter().flat_map(|d| {
+            d.run(&s).unwrap().into_iter().enumerate()
+                .map(|(idx, x)| (f(idx, &x) % 4, x))
+                .collect::<Vec<_>>()
+        }).collect();
+        assert_eq!(tagged.run(&s), Some(expected));
+    }
+
+    #[test]
+    fn test_sorted_fold_by() {
+        let col = MemoryCollection::from_vec((0..500usize).collect()).split(5);
+        let sums = col.sorted_fold_by(|x| x % 37, || 0usize, |acc, x| *acc += x, 
+                                      |x, y| *x += *y, 3);
+        let mut all = Vec::new();
+        for part in sums.to_defs() {
+            let part = part.run(&LeveledScheduler::new()).unwrap();
+            let keys: Vec<_> = part.iter().map(|x| x.0).collect();
+            let mut sorted = keys.clone();
+            sorted.sort();
+            assert_eq!(keys, sorted);
+            all.extend_from_slice(&part);
+        }
+        all.sort();
+        assert_eq!(all.len(), 37);
+        assert_eq!(all[5], (5, (0..500usize).filter(|x| x % 37 == 5).sum()));
+    }
+
+    #[test]
+    fn test_rebalance_by_key() {
+        // One key with 40 items, one with 30 and six with 10
+        let mut items = Vec::new();
+        for (k, n) in vec![40, 30, 10, 10, 10, 10, 10, 10usize].into_iter().enumerate() {
+            for i in 0..n {
+                items.push((k, i));
+            }
+        }
+        let col = MemoryCollection::from_vec(items).split(4);
+        let s = LeveledScheduler::new();
+        let parts_of = |c: &MemoryCollection<(usize, usize)>| -> Vec<Vec<(usize, usize)>> {
+            c.to_defs().iter().map(|d| d.run(&s).unwrap()).collect()
+        };
+
+        let balanced = parts_of(&col.rebalance_by_key(3, |x| x.0));
+        let hashed = parts_of(&col.partition_by_key(3, |x| x.0));
+
+        // Every key lives in exactly one partition
+        for k in 0..8usize {
+            let holding = balanced.iter().filter(|p| p.iter().any(|x| x.0 == k)).count();
+            assert_eq!(holding, 1);
         }
+        assert_eq!(balanced.iter().map(|p| p.len()).sum::<usize>(), 130);
+
+        let largest = |parts: &Vec<Vec<(usize, usize)>>| parts.iter().map(|p| p.len()).max();
+        assert_eq!(largest(&balanced), Some(50));
+        assert!(largest(&balanced) <= largest(&hashed));
+    }
+
+    #[test]
+    fn test_from_range() {
+        let col = MemoryCollection::from_range(0, 1000, 4);
+        assert_eq!(col.n_partitions(), 4);
+        let s = LeveledScheduler::new();
+        let sizes: Vec<_> = col.to_defs().iter().map(|d| d.run(&s).unwrap().len()).collect();
+        assert_eq!(sizes, vec![250, 250, 250, 250]);
+        assert_eq!(col.run(&s).unwrap().iter().sum::<i64>(), 499500);
+
+        assert_eq!(MemoryCollection::from_range(-3, 4, 3).run(&s), Some((-3..4).collect()));
+        assert_eq!(MemoryCollection::from_range(5, 0, 2).run(&s), Some(vec![]));
+    }
+
+    #[test]
+    fn test_from_range_wide() {
+        let s = LeveledScheduler::new();
+        let col = MemoryCollection::from_range(i64::MAX - 10, i64::MAX, 4);
+        assert_eq!(col.run(&s), Some((i64::MAX - 10..i64::MAX).collect()));
+
+        // Only the bounds are checked, as the partitions are far too large to compute
+        let col = MemoryCollection::from_range(-1, i64::MAX, 2);
+        let names: Vec<_> = col.to_defs().iter().flat_map(|d| d.lineage()).collect();
+        assert_eq!(names, vec!["Input<name=Range: -1..4611686018427387903>",
+                               "Input<name=Range: 4611686018427387903..9223372036854775807>"]);
+
+        let col = MemoryCollection::from_range(0, i64::MAX, 4);
+        let last = col.to_defs()[3].lineage();
+        assert_eq!(last, vec!["Input<name=Range: 6917529027641081856..9223372036854775807>"]);
+    }
+
+    #[test]
+    fn test_first() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let col = MemoryCollection::from_defs((0..4usize)
+            .map(|p| Deferred::lift((p * 10..(p + 1) * 10).collect::<Vec<_>>(), None))
+            .collect());
+        let seen = Arc::new(AtomicUsize::new(0));
+        let s2 = seen.clone();
+        let counted = col.map(move |x| { s2.fetch_add(1, Ordering::SeqCst); *x });
+
+        let s = LeveledScheduler::new();
+        assert_eq!(counted.first(15, &s), (0..15usize).collect::<Vec<_>>());
+        assert_eq!(seen.load(Ordering::SeqCst), 20);
+        assert_eq!(counted.first(100, &s).len(), 40);
+        assert_eq!(counted.first(usize::MAX, &s).len(), 40);
+    }
+
+    #[test]
+    fn test_aggregate_by_key() {
+        let items: Vec<_> = (0..100i64).map(|x| (x % 3, (x * 37) % 101)).collect();
+        let col = MemoryCollection::from_vec(items.clone()).split(4);
+        let bounds = col.aggregate_by_key(|| (i64::MAX, i64::MIN),
+                                          |(lo, hi), v| (lo.min(*v), hi.max(*v)),
+                                          |(l1, h1), (l2, h2)| (l1.min(l2), h1.max(h2)),
+                                          2);
+        let mut results = bounds.run(&LeveledScheduler::new()).unwrap();
+        results.sort();
+        let expected: Vec<_> = (0..3i64).map(|k| {
+            let vs: Vec<_> = items.iter().filter(|x| x.0 == k).map(|x| x.1).collect();
+            (k, (*vs.iter().min().unwrap(), *vs.iter().max().unwrap()))
+        }).collect();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_partition_consistent() {
+        let col = MemoryCollection::from_vec((0..2000usize).collect()).split(4);
+        let owners = |n: usize| {
+            let mut owners = HashMap::new();
+            for (idx, part) in col.partition_consistent(n, |x| *x).to_defs().iter().enumerate() {
+                for x in part.run(&LeveledScheduler::new()).unwrap() {
+                    owners.insert(x, idx);
+                }
+            }
+            owners
+        };
+        let before = owners(8);
+        let after = owners(9);
+        assert_eq!(before.len(), 2000);
+        assert_eq!(after.len(), 2000);
+        let moved = before.iter().filter(|&(k, p)| after[k] != *p).count();
+        // Ideally 1/9th of the keys move; plain hashing would move most of them
+        assert!(moved > 0 && moved < 2000 / 9 * 2, "moved {} keys", moved);
+    }
+
+    #[test]
+    fn test_map_progress() {
+        use std::sync::Mutex;
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let c2 = calls.clone();
+        let col = MemoryCollection::from_vec((0..25_000usize).collect()).split(2);
+        let out = col.map_progress(|x| x + 1, move |idx, done, total| {
+            c2.lock().unwrap().push((idx, done, total));
+        }).run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(out.len(), 25_000);
+
+        let mut calls = calls.lock().unwrap().clone();
+        calls.sort();
+        assert_eq!(calls, vec![(0, 10_000, 12_500), (0, 12_500, 12_500),
+                               (1, 10_000, 12_500), (1, 12_500, 12_500)]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let col = MemoryCollection::from_vec(vec![1, -2, 3, -4, 5i64]).split(2);
+        let (valid, invalid) = col.validate(|x| {
+            if *x < 0 { Err(format!("{} is negative", x)) } else { Ok(()) }
+        });
+        let mut valid = valid.run(&LeveledScheduler::new()).unwrap();
+        valid.sort();
+        let mut invalid = invalid.run(&LeveledScheduler::new()).unwrap();
+        invalid.sort();
+        assert_eq!(valid, vec![1, 3, 5]);
+        assert_eq!(invalid, vec![(-4, "-4 is negative".into()), (-2, "-2 is negative".into())]);
+    }
+
+    #[test]
+    fn test_validate_cached() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c2 = calls.clone();
+        let col = MemoryCollection::from_vec(vec![1, -2, 3, -4, 5i64]).split(2);
+        let (valid, invalid) = col.validate(move |x| {
+            c2.fetch_add(1, Ordering::SeqCst);
+            if *x < 0 { Err(()) } else { Ok(()) }
+        });
+        let s = GreedyScheduler::new().with_cache_capacity(4);
+        assert_eq!(valid.run(&s).map(|v| v.len()), Some(3));
+        assert_eq!(invalid.run(&s).map(|v| v.len()), Some(2));
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_pipe() {
+        let lines: Vec<String> = (0..100usize).map(|i| format!("line {}", i)).collect();
+        let col = MemoryCollection::from_vec(lines.clone()).split(3);
+        let piped = col.pipe("cat", Vec::new());
+        assert_eq!(piped.n_partitions(), 3);
+        assert_eq!(piped.to_defs()[1].run(&LeveledScheduler::new()),
+                   col.to_defs()[1].run(&LeveledScheduler::new()));
+        let mut out = piped.run(&LeveledScheduler::new()).unwrap();
+        out.sort();
+        let mut expected = lines;
+        expected.sort();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "Command sh failed with exit status: 3: oops")]
+    fn test_pipe_failure() {
+        let col = MemoryCollection::from_vec(vec!["a".to_owned()]);
+        col.pipe("sh", vec!["-c".into(), "echo oops >&2; exit 3".into()])
+            .run(&LeveledScheduler::new());
+    }
 
-        MemoryCollection { partitions: new_parts }
+    #[test]
+    fn test_cache() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c2 = calls.clone();
+        let col = MemoryCollection::from_vec((0..10usize).collect()).split(2)
+            .map(move |x| { c2.fetch_add(1, Ordering::SeqCst); x * 2 })
+            .cache();
+        let s = GreedyScheduler::new().with_cache_capacity(2);
+        let expected: usize = (0..10usize).map(|x| x * 2).sum();
+        assert_eq!(col.fold_by(|_x| 0, || 0, |acc, x| *acc += x, |acc, x| *acc += x, 1)
+                   .run(&s), Some(vec![(0, expected)]));
+        assert_eq!(col.count().run(&s), Some(vec![10]));
+        assert_eq!(calls.load(Ordering::SeqCst), 10);
+
+        // Only one of the partitions fits, so the other is recomputed
+        let small = GreedyScheduler::new().with_cache_capacity(1);
+        col.count().run(&small);
+        col.count().run(&small);
+        assert_eq!(calls.load(Ordering::SeqCst), 25);
     }
 
-    /// Executes the Collection, returning the result of the computation
-    pub fn run<S: Scheduler>(&self, s: &S) -> Option<Vec<A>> {
-        let cat = tree_reduce(&self.partitions, |x, y| {
-            let mut v1: Vec<_> = (*x).clone();
-            for yi in y {
-                v1.push(yi.clone());
-            }
-            v1
+    #[test]
+    fn test_to_columns() {
+        let col = MemoryCollection::from_vec((0..10usize).map(|x| (x, format!("v{}", x))).collect())
+            .split(3);
+        let rows = col.run(&LeveledScheduler::new()).unwrap();
+        let (keys, values) = col.to_columns().run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(keys.len(), 10);
+        let zipped: Vec<_> = keys.into_iter().zip(values).collect();
+        assert_eq!(zipped, rows);
+
+        let empty: MemoryCollection<(usize, usize)> = MemoryCollection::from_defs(Vec::new());
+        assert_eq!(empty.to_columns().run(&LeveledScheduler::new()), Some((Vec::new(), Vec::new())));
+    }
+
+    #[test]
+    fn test_take_and_skip_while() {
+        let col = MemoryCollection::from_vec(vec![1, 2, 3, 4, 1usize]);
+        assert_eq!(col.take_while(|x| *x < 3).run(&LeveledScheduler::new()), Some(vec![1, 2]));
+        assert_eq!(col.skip_while(|x| *x < 3).run(&LeveledScheduler::new()), Some(vec![3, 4, 1]));
+
+        // The failing item sits in the second partition, and the third partition passes
+        let parts = MemoryCollection::from_defs(vec![
+            Deferred::lift(vec![1, 2usize], None),
+            Deferred::lift(vec![2, 5, 1], None),
+            Deferred::lift(vec![0, 1], None)
+        ]);
+        let taken = parts.take_while(|x| *x < 3);
+        assert_eq!(taken.n_partitions(), 3);
+        assert_eq!(taken.run(&LeveledScheduler::new()), Some(vec![1, 2, 2]));
+        assert_eq!(parts.skip_while(|x| *x < 3).run(&LeveledScheduler::new()), 
+                   Some(vec![5, 1, 0, 1]));
+        assert_eq!(parts.take_while(|_x| true).run(&LeveledScheduler::new()), 
+                   parts.run(&LeveledScheduler::new()));
+    }
+
+    #[test]
+    fn test_partition_by_key_compact() {
+        let col = MemoryCollection::from_vec((0..100usize).collect()).split(4);
+        // Find two keys landing in different buckets out of five
+        let bucket = |k: &usize| hash_key(k) % 5;
+        let other = (1..100usize).find(|k| bucket(k) != bucket(&0)).unwrap();
+        let compact = col.partition_by_key_compact(5, move |x| if x % 2 == 0 { 0 } else { other });
+        assert_eq!(compact.n_partitions(), 2);
+        let mut sizes: Vec<_> = compact.to_defs().iter()
+            .map(|d| d.run(&LeveledScheduler::new()).unwrap().len())
+            .collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![50, 50]);
+    }
+
+    #[test]
+    fn test_partition_by_key_compact_lazy() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c2 = calls.clone();
+        let col = MemoryCollection::from_vec((0..10usize).collect())
+            .map(move |x| { c2.fetch_add(1, Ordering::SeqCst); *x });
+        let compact = col.partition_by_key_compact(5, |_x| 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 10);
+
+        // The kept partitions are recomputed from the source, not lifted copies
+        compact.run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 20);
+    }
+
+    #[test]
+    fn test_count_distinct_by_key() {
+        let visits = vec![("a", 1), ("b", 1), ("a", 2), ("a", 1), ("c", 3), ("b", 1), ("a", 3)];
+        let col = MemoryCollection::from_vec(visits).split(3);
+        let mut counts = col.count_distinct_by_key(|x| x.0, |x| x.1, 2)
+            .run(&LeveledScheduler::new()).unwrap();
+        counts.sort();
+        assert_eq!(counts, vec![("a", 3), ("b", 1), ("c", 1)]);
+    }
+
+    #[test]
+    fn test_from_vec_split() {
+        let col = MemoryCollection::from_vec_split((0..10usize).collect(), 4);
+        assert_eq!(col.n_partitions(), 4);
+        let sizes: Vec<_> = col.to_defs().iter()
+            .map(|d| d.run(&LeveledScheduler::new()).unwrap().len())
+            .collect();
+        assert_eq!(sizes, vec![2, 3, 2, 3]);
+        assert_eq!(col.run(&LeveledScheduler::new()), Some((0..10usize).collect()));
+
+        let sparse = MemoryCollection::from_vec_split(vec![1usize], 3);
+        assert_eq!(sparse.n_partitions(), 3);
+        assert_eq!(sparse.run(&LeveledScheduler::new()), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_map_reduce_by_key() {
+        let words = MemoryCollection::from_vec(vec!["the cat sat", "the dog sat down", "a cat"])
+            .split(2)
+            .emit(|s, emitter| for w in s.split_whitespace() { emitter(w.to_owned()) })
+            .map_reduce_by_key(|w| (w.clone(), 1usize), |x, y| x + y, 3);
+        let mut counts = words.run(&LeveledScheduler::new()).unwrap();
+        counts.sort();
+        let expected: Vec<(String, usize)> = vec![("a", 1), ("cat", 2), ("dog", 1), ("down", 1), 
+                                                  ("sat", 2), ("the", 2)]
+            .into_iter().map(|(w, c)| (w.to_owned(), c)).collect();
+        assert_eq!(counts, expected);
+    }
+
+    #[test]
+    fn test_map_with_accumulator() {
+        let dropped = SideAccumulator::new(0usize, |total, partial| *total += partial);
+        let col = MemoryCollection::from_vec((0..100i64).map(|x| x - 30).collect()).split(4);
+        let kept = col.map_with_accumulator(&dropped, |x, dropped| {
+                if *x < 0 { *dropped += 1; None } else { Some(*x) }
+            })
+            .flat_map_with_index(|_idx, x| *x);
+        assert_eq!(kept.run(&LeveledScheduler::new()).map(|v| v.len()), Some(70));
+        assert_eq!(dropped.value(), 30);
+    }
+
+    #[test]
+    fn test_align_to() {
+        let col = MemoryCollection::from_vec((0..20usize).collect()).split(3);
+        let other = MemoryCollection::from_vec(vec!['a'; 10]).split(5);
+        let aligned = col.align_to(&other);
+        assert_eq!(aligned.n_partitions(), 5);
+        let mut items = aligned.run(&LeveledScheduler::new()).unwrap();
+        items.sort();
+        assert_eq!(items, (0..20usize).collect::<Vec<_>>());
+
+        let zipped = aligned.zip_partitions(&other, |l, r| vec![(l.len(), r.len())]);
+        assert_eq!(zipped.n_partitions(), 5);
+        assert_eq!(col.align_to(&col).explain(), col.explain());
+    }
+
+    #[test]
+    fn test_left_join_or() {
+        let left = MemoryCollection::from_vec(vec![(1, "a"), (2, "b"), (3, "c"), (1, "d")]).split(2);
+        let right = MemoryCollection::from_vec(vec![(1, 10), (3, 30), (3, 31), (4, 40)]);
+        let mut joined = left.left_join_or(&right, |l| l.0, |r| r.0, || (0, -1), 3)
+            .map(|(k, (l, r))| (*k, l.1, r.1))
+            .run(&LeveledScheduler::new()).unwrap();
+        joined.sort();
+        assert_eq!(joined, vec![(1, "a", 10), (1, "d", 10), (2, "b", -1), (3, "c", 30), (3, "c", 31)]);
+    }
+
+    #[test]
+    fn test_group_by_key_capped() {
+        let mut items: Vec<_> = (0..10usize).map(|v| ("big", v)).collect();
+        items.push(("small", 10));
+        let col = MemoryCollection::from_vec(items).split(3);
+        let groups = col.group_by_key_capped(2, |x| x.0, 3).run(&LeveledScheduler::new()).unwrap();
+
+        let mut big: Vec<_> = groups.iter().filter(|g| g.0 == "big").map(|g| g.1.len()).collect();
+        big.sort();
+        assert_eq!(big, vec![1, 3, 3, 3]);
+        let mut values: Vec<_> = groups.iter().flat_map(|g| g.1.iter().map(|x| x.1)).collect();
+        values.sort();
+        assert_eq!(values, (0..11usize).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_sink_atomic() {
+        let path = test_dir("sink-atomic");
+        let path = path.to_str().unwrap();
+
+        let lines: Vec<String> = (0..10).map(|i| format!("{}", i)).collect();
+        let col = MemoryCollection::from_vec(lines).split(2);
+        let counts = col.sink_atomic(path).run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(counts, vec![5, 5]);
+        let mut names: Vec<_> = fs::read_dir(path).unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["0", "1"]);
+
+        // A write failing partway through leaves neither a final nor a temporary file
+        let failed = ::std::panic::catch_unwind(|| {
+            write_atomic(path, 2, |bw| {
+                bw.write_all(b"partial\n")?;
+                Err(::std::io::Error::other("disk full"))
+            })
         });
-        cat.and_then(|x| x.run(s))
+        assert!(failed.is_err());
+        assert!(!::std::path::Path::new(&format!("{}/2", path)).exists());
+        assert!(!::std::path::Path::new(&format!("{}/.2.tmp", path)).exists());
+        fs::remove_dir_all(path).unwrap();
     }
-    
-    /// Executes the Collection, returning the result of the computation
-    pub fn eval(&self) -> Option<Vec<A>> {
-        self.run(&GreedyScheduler::new())
+
+    #[test]
+    fn test_interleave() {
+        let odds = MemoryCollection::from_vec(vec![1, 3, 5usize]);
+        let evens = MemoryCollection::from_vec(vec![2, 4, 6usize]);
+        let merged = odds.interleave(&evens).run(&LeveledScheduler::new());
+        assert_eq!(merged, Some(vec![1, 2, 3, 4, 5, 6]));
+
+        // Partitions are interleaved pairwise
+        let left = MemoryCollection::from_vec(vec![1, 2, 3, 4usize]).split(2);
+        let right = MemoryCollection::from_vec(vec![10, 20, 30usize]).split(2);
+        let parts: Vec<_> = left.interleave(&right).partitions.iter()
+            .map(|p| p.run(&LeveledScheduler::new()).unwrap()).collect();
+        assert_eq!(parts, vec![vec![1, 10, 3, 30], vec![2, 20, 4]]);
     }
 
-}
+    #[test]
+    fn test_run_ordered() {
+        // One partition per item, in reverse, so any reordering of partitions shows
+        let parts: Vec<_> = (0..53usize).rev()
+            .map(|x| MemoryCollection::from_vec(vec![x, x + 100]))
+            .collect();
+        let mut col = parts[0].clone();
+        for p in &parts[1..] {
+            col = col.concat(p);
+        }
+        assert_eq!(col.n_partitions(), 53);
 
-impl <A: Any + Send + Sync + Clone> MemoryCollection<Vec<A>> {
+        let expected: Vec<_> = (0..53usize).rev().flat_map(|x| vec![x, x + 100]).collect();
+        assert_eq!(col.run_ordered(&LeveledScheduler::new()), Some(expected));
+    }
 
-    /// Flattens a vector of values
-    /// ```rust
-    ///   extern crate tange;
-    ///   extern crate tange_collection;
-    ///   use tange::scheduler::GreedyScheduler;
-    ///   use tange_collection::collection::memory::MemoryCollection;
-    ///   
-    ///   let col = MemoryCollection::from_vec(vec![vec![1usize,2],vec![3,4]]);
-    ///   let flattened = col.flatten();
-    ///   assert_eq!(flattened.run(&GreedyScheduler::new()), Some(vec![1, 2, 3, 4]));
-    /// ```
+    #[test]
+    fn test_persist() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
 
-    pub fn flatten(&self) -> MemoryCollection<A> {
-        self.emit(move |x, emitter| {
-            for xi in x {
-                emitter(xi.clone());
-            }
-        })
+        for level in [StorageLevel::Memory, StorageLevel::Disk, StorageLevel::MemoryAndDisk] {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let c2 = calls.clone();
+            let col = MemoryCollection::from_vec((0..10usize).collect()).split(2)
+                .map(move |x| { c2.fetch_add(1, Ordering::SeqCst); x * 2 })
+                .persist(level);
+            let s = GreedyScheduler::new().with_cache_capacity(4);
+            let mut first = col.run(&s).unwrap();
+            let mut second = col.map(|x| *x).run(&s).unwrap();
+            first.sort();
+            second.sort();
+            assert_eq!(first, (0..10usize).map(|x| x * 2).collect::<Vec<_>>());
+            assert_eq!(first, second, "{:?}", level);
+            assert_eq!(calls.load(Ordering::SeqCst), 10, "{:?}", level);
+
+            // Without a cache nothing is retained
+            col.run(&GreedyScheduler::new());
+            assert_eq!(calls.load(Ordering::SeqCst), 20, "{:?}", level);
+        }
     }
-}
 
-impl <A: Any + Send + Sync + Clone> MemoryCollection<A> {
+    // Runs a persisted collection with room for `capacity` cached outputs, followed by
+    // `others` unrelated cached outputs, then once more after deleting its files.  Returns
+    // the number of map calls, the number of files kept before the last run and whether
+    // the last run succeeded.
+    fn persist_runs(level: StorageLevel, capacity: usize, others: usize) -> (usize, usize, bool) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
 
-    /// Returns the number of items in the collection.
-    /// ```rust
-    ///   extern crate tange;
-    ///   extern crate tange_collection;
-    ///   use tange::scheduler::GreedyScheduler;
-    ///   use tange_collection::collection::memory::MemoryCollection;
-    ///   
-    ///   let col = MemoryCollection::from_vec(vec![vec![1usize,2],vec![3,4]]);
-    ///   assert_eq!(col.count().run(&GreedyScheduler::new()), Some(vec![2]));
-    ///   let flattened = col.flatten();
-    ///   assert_eq!(flattened.count().run(&GreedyScheduler::new()), Some(vec![4]));
-    /// ```
-    pub fn count(&self) -> MemoryCollection<usize> {
-        let nps = batch_apply(&self.partitions, |_idx, vs| vs.len());
-        let count = tree_reduce(&nps, |x, y| x + y).unwrap();
-        let out = count.apply(|x| vec![*x]);
-        MemoryCollection { partitions: vec![out] }
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c2 = calls.clone();
+        let dir = test_dir("persist");
+        let col = MemoryCollection::from_vec((0..10usize).collect())
+            .map(move |x| { c2.fetch_add(1, Ordering::SeqCst); x * 2 })
+            .persist_in(level, dir.to_str().unwrap().to_owned());
+        let s = GreedyScheduler::new().with_cache_capacity(capacity);
+        let expected = (0..10usize).map(|x| x * 2).collect::<Vec<_>>();
+        assert_eq!(col.run(&s), Some(expected.clone()));
+        for i in 0..others {
+            Deferred::lift(i, None).apply(|x| x + 1).cache().run(&s);
+        }
+
+        let files: Vec<_> = fs::read_dir(&dir).map(|d| d.map(|e| e.unwrap().path()).collect())
+            .unwrap_or_default();
+        for f in files.iter() {
+            fs::remove_file(f).unwrap();
+        }
+        let rerun = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| col.run(&s)));
+        let _ = fs::remove_dir_all(&dir);
+        (calls.load(Ordering::SeqCst), files.len(), rerun.map(|out| out == Some(expected)).unwrap_or(false))
     }
-}
 
-impl <A: Any + Send + Sync + Clone + PartialEq + Hash + Eq> MemoryCollection<A> {
+    #[test]
+    fn test_persist_levels() {
+        // Memory keeps the computed partition, without writing it out
+        assert_eq!(persist_runs(StorageLevel::Memory, 1, 0), (10, 0, true));
 
-    /// Computes the frequencies of the items in collection.
-    /// ```rust
-    ///   extern crate tange;
-    ///   extern crate tange_collection;
-    ///   use tange::scheduler::GreedyScheduler;
-    ///   use tange_collection::collection::memory::MemoryCollection;
-    ///   
-    ///   let col = MemoryCollection::from_vec(vec![1, 2, 1, 5, 1, 2]);
-    ///   let freqs = col.frequencies(1).sort_by(|x| x.0);
-    ///   assert_eq!(freqs.run(&GreedyScheduler::new()), Some(vec![(1, 3), (2, 2), (5, 1)]));
-    /// ```
-pub fn frequencies(&self, partitions: usize) -> MemoryCollection<(A, usize)> {
-        //self.partition(chunks, |x| x);
-        self.fold_by(|s| s.clone(), 
-                     || 0usize, 
-                     |acc, _l| *acc += 1, 
-                     |x, y| *x += *y, 
-                     partitions)
+        // Disk only keeps the file, reading it back on every run
+        assert_eq!(persist_runs(StorageLevel::Disk, 1, 0), (10, 1, false));
+
+        // Without room for both, the newly computed in-memory copy is kept
+        assert_eq!(persist_runs(StorageLevel::MemoryAndDisk, 1, 0), (10, 0, true));
+
+        // With room for both, the in-memory copy is used
+        assert_eq!(persist_runs(StorageLevel::MemoryAndDisk, 2, 0), (10, 1, true));
+
+        // Once other outputs need the room, the in-memory copy is evicted first, and is
+        // read back from disk rather than recomputed
+        assert_eq!(persist_runs(StorageLevel::MemoryAndDisk, 2, 1), (10, 1, false));
     }
-}
 
-// Writes out data
-impl MemoryCollection<String> {
+    #[test]
+    fn test_diff() {
+        let v1: Vec<_> = (0..20usize).map(|k| (k, format!("v{}", k))).collect();
+        let v2: Vec<_> = (5..25usize)
+            .map(|k| (k, if k % 5 == 0 && k < 20 { format!("w{}", k) } else { format!("v{}", k) }))
+            .collect();
+        let old = MemoryCollection::from_vec(v1).split(3);
+        let new = MemoryCollection::from_vec(v2).split(2);
+        let mut changes = old.diff(&new, 4).run(&LeveledScheduler::new()).unwrap();
+        changes.sort_by_key(|c| c.0);
+        assert_eq!(changes.len(), 25);
+        for (k, change) in changes {
+            let expected = match k {
+                _ if k < 5      => Change::Removed(format!("v{}", k)),
+                _ if k >= 20    => Change::Added(format!("v{}", k)),
+                _ if k % 5 == 0 => Change::Modified(format!("v{}", k), format!("w{}", k)),
+                _ => Change::Unchanged
+            };
+            assert_eq!(change, expected, "key {}", k);
+        }
+    }
 
-    /// Writes each record in a collection to disk, newline delimited.
-    /// MemoryCollection will create a new file within the path for each partition.
-    pub fn sink(&self, path: &str) -> MemoryCollection<usize> {
-        let p: Arc<String> = Arc::new(path.to_owned());
-        let pats = batch_apply(&self.partitions, move |idx, vs| {
-            let p2: Arc<String> = p.clone();
-            let local: &str = &p2;
-            fs::create_dir_all(local)
-                .expect("Welp, something went terribly wrong when creating directory");
+    #[test]
+    fn test_sink_grouped() {
+        let path = test_dir("sink-grouped");
+        let path = path.to_str().unwrap();
 
-            let file = fs::File::create(&format!("{}/{}", local, idx))
-                .expect("Issues opening file!");
-            let mut bw = BufWriter::new(file);
+        let lines: Vec<String> = (0..60).map(|i| format!("{}", i)).collect();
+        let col = MemoryCollection::from_vec(lines).split(6);
+        let counts = col.sink_grouped(path, 2).run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(counts, vec![20, 20, 20]);
 
-            let size = vs.len();
-            for line in vs {
-                bw.write(line.as_bytes()).expect("Error writing out line");
-                bw.write(b"\n").expect("Error writing out line");
+        let mut names = Vec::new();
+        let mut total = 0;
+        for entry in fs::read_dir(path).unwrap() {
+            let entry = entry.unwrap();
+            names.push(entry.file_name().into_string().unwrap());
+            total += fs::read_to_string(entry.path()).unwrap().lines().count();
+        }
+        names.sort();
+        assert_eq!(names, vec!["0", "1", "2"]);
+        assert_eq!(total, 60);
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_trace_shuffle() {
+        let col = MemoryCollection::from_vec((0..20usize).collect()).split(4);
+        let key = |_idx: usize, x: &usize| x * 7;
+        let traced = col.trace_shuffle(3, key);
+        assert_eq!(traced.n_partitions(), 4);
+
+        let s = LeveledScheduler::new();
+        for (src, part) in traced.partitions.iter().enumerate() {
+            let expected = col.partitions[src].run(&s).unwrap();
+            let triples = part.run(&s).unwrap();
+            assert_eq!(triples.len(), expected.len());
+            for ((source, target, x), e) in triples.into_iter().zip(expected) {
+                assert_eq!((source, target, x), (src, (e * 7) % 3, e));
             }
+        }
 
-            vec![size]
+        // Targets agree with where partition actually moves each item
+        let moved = col.partition(3, key);
+        for (target, part) in moved.partitions.iter().enumerate() {
+            for x in part.run(&s).unwrap() {
+                assert_eq!((x * 7) % 3, target);
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_no_partitions() {
+        let col: MemoryCollection<u32> = MemoryCollection::from_defs(Vec::new());
+        assert_eq!(col.n_partitions(), 0);
+        let s = LeveledScheduler::new();
+        assert_eq!(col.count().run(&s), Some(vec![0]));
+        assert_eq!(col.len().run(&s), Some(0));
+        assert_eq!(col.is_empty().run(&s), Some(true));
+        assert!(col.mean().run(&s).unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_map_fanout() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c2 = calls.clone();
+        let col = MemoryCollection::from_vec((0..10usize).collect()).split(3);
+        let (xs, squares) = col.map_fanout(move |x| {
+            c2.fetch_add(1, Ordering::SeqCst);
+            (*x, x * x)
         });
-        
-        MemoryCollection { partitions: pats }
+        assert_eq!(xs.n_partitions(), 3);
+        assert_eq!(squares.n_partitions(), 3);
+
+        // Both outputs share the single pass over the source
+        let pairs = xs.zip_partitions(&squares, |l, r| {
+            l.iter().cloned().zip(r.iter().cloned()).collect()
+        });
+        let mut pairs = pairs.run(&LeveledScheduler::new()).unwrap();
+        pairs.sort();
+        assert_eq!(pairs, (0..10usize).map(|x| (x, x * x)).collect::<Vec<_>>());
+        assert_eq!(calls.load(Ordering::SeqCst), 10);
     }
-}
 
-impl <A: Any + Send + Sync + Clone + Serialize + for<'de>Deserialize<'de>> MemoryCollection<A> {
+    #[test]
+    fn test_zip_with_index_skipping_empty() {
+        let col = MemoryCollection::from_defs(vec![
+            Deferred::lift(vec![1, 2], None),
+            Deferred::lift(Vec::new(), None),
+            Deferred::lift(vec![3], None)
+        ]);
+        let indexed = col.zip_with_index_skipping_empty();
+        let parts: Vec<_> = indexed.partitions.iter()
+            .map(|p| p.run(&LeveledScheduler::new()).unwrap()).collect();
+        assert_eq!(parts, vec![vec![(0, 1), (1, 2)], vec![], vec![(2, 3)]]);
 
-    /// Copies the MemoryCollection to disk, returning a DiskCollection
-    pub fn to_disk(&self, path: String) -> DiskCollection<A> {
-        DiskCollection::from_memory(path, &self.partitions)
+        let leading = MemoryCollection::from_defs(vec![
+            Deferred::lift(Vec::new(), None),
+            Deferred::lift(Vec::new(), None),
+            Deferred::lift(vec!["x", "y"], None)
+        ]);
+        assert_eq!(leading.zip_with_index_skipping_empty().run(&LeveledScheduler::new()),
+                   Some(vec![(0, "x"), (1, "y")]));
     }
-}
 
-#[cfg(test)]
-mod test_lib {
-    use super::*;
-    use tange::scheduler::LeveledScheduler;
+    #[test]
+    fn test_scan_global() {
+        let col = MemoryCollection::from_vec((0..4usize).collect())
+            .concat(&MemoryCollection::from_vec((4..7usize).collect()))
+            .concat(&MemoryCollection::from_vec((7..10usize).collect()));
+        assert_eq!(col.n_partitions(), 3);
+
+        let sums = col.scan_global(0, |acc, x| acc + x, |c, x| c + x);
+        assert_eq!(sums.n_partitions(), 3);
+        let expected: Vec<usize> = (0..10).map(|i| (0..i + 1).sum()).collect();
+        assert_eq!(sums.run(&LeveledScheduler::new()), Some(expected));
+
+        // Non-numeric running values carry over too
+        let words = MemoryCollection::from_vec(vec!["a", "b"])
+            .concat(&MemoryCollection::from_vec(vec!["c"]));
+        let prefixes = words.scan_global(String::new(), 
+                                         |acc, w| format!("{}{}", acc, w), 
+                                         |c, s| format!("{}{}", c, s));
+        assert_eq!(prefixes.run(&LeveledScheduler::new()), 
+                   Some(vec!["a".to_owned(), "ab".into(), "abc".into()]));
+    }
 
     #[test]
-    fn test_fold_by() {
-        let col = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
-        let out = col.fold_by(|x| *x, || 0, |x, _y| *x += 1, |x, y| *x += y, 1);
-        let mut results = out.run(&mut LeveledScheduler).unwrap();
-        results.sort();
-        assert_eq!(results, vec![(1, 2), (2, 2), (3, 1)]);
+    fn test_coalesce_balanced() {
+        let col = MemoryCollection::from_vec((0..10usize).collect())
+            .concat(&MemoryCollection::from_vec(vec![10]))
+            .concat(&MemoryCollection::from_vec(vec![11]))
+            .concat(&MemoryCollection::from_vec((12..22usize).collect()));
+        let s = LeveledScheduler::new();
+        let sizes = |c: &MemoryCollection<usize>| -> Vec<usize> {
+            c.partitions.iter().map(|p| p.run(&s).unwrap().len()).collect()
+        };
+        assert_eq!(sizes(&col), vec![10, 1, 1, 10]);
+        assert_eq!(sizes(&col.coalesce(2)), vec![11, 11]);
+
+        let balanced = col.coalesce_balanced(2);
+        assert_eq!(sizes(&balanced), vec![11, 11]);
+        let mut items = balanced.run(&s).unwrap();
+        items.sort();
+        assert_eq!(items, (0..22usize).collect::<Vec<_>>());
+
+        // Neighbor merging would pair the two large partitions
+        let skewed = MemoryCollection::from_vec((0..10usize).collect())
+            .concat(&MemoryCollection::from_vec((10..20usize).collect()))
+            .concat(&MemoryCollection::from_vec(vec![20]))
+            .concat(&MemoryCollection::from_vec(vec![21]));
+        assert_eq!(sizes(&skewed.coalesce(2)), vec![20, 2]);
+        assert_eq!(sizes(&skewed.coalesce_balanced(2)), vec![11, 11]);
     }
 
     #[test]
-    fn test_fold_by_parts() {
-        let col = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
-        let out = col.fold_by(|x| *x, || 0, |x, _y| *x += 1, |x, y| *x += y, 2);
-        assert_eq!(out.partitions.len(), 2);
-        let mut results = out.run(&mut LeveledScheduler).unwrap();
-        results.sort();
-        assert_eq!(results, vec![(1, 2), (2, 2), (3, 1)]);
+    fn test_repartition_cached() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let shuffled = Arc::new(AtomicUsize::new(0));
+        let c2 = shuffled.clone();
+        let col = MemoryCollection::from_vec((0..10usize).collect()).split(2)
+            .map(move |x| { c2.fetch_add(1, Ordering::SeqCst); *x })
+            .repartition_cached(3);
+        assert_eq!(col.n_partitions(), 3);
+
+        let s = GreedyScheduler::new().with_cache_capacity(3);
+        let mut items = col.run(&s).unwrap();
+        items.sort();
+        assert_eq!(items, (0..10usize).collect::<Vec<_>>());
+        assert_eq!(col.count().run(&s), Some(vec![10]));
+        assert_eq!(shuffled.load(Ordering::SeqCst), 10);
     }
 
     #[test]
-    fn test_partition_by_key() {
-        let col = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
-        let computed = col.partition_by_key(2, |x| *x)
-            .sort_by(|x| *x);
-        assert_eq!(computed.partitions.len(), 2);
-        let results = computed.run(&mut LeveledScheduler).unwrap();
-        assert_eq!(results, vec![2, 2, 3, 1, 1]);
+    fn test_sort_natural() {
+        let col = MemoryCollection::from_vec(vec![3, 1, 2usize]);
+        assert_eq!(col.n_partitions(), 1);
+        assert_eq!(col.sort().run(&LeveledScheduler::new()), Some(vec![1, 2, 3]));
+
+        let items: Vec<_> = (0..200usize).map(|x| (x * 37) % 101).collect();
+        let sorted = MemoryCollection::from_vec(items.clone()).split(4).sort_global(3);
+        assert_eq!(sorted.n_partitions(), 3);
+        let mut expected = items;
+        expected.sort();
+        assert_eq!(sorted.run_ordered(&LeveledScheduler::new()), Some(expected));
     }
 
     #[test]
-    fn test_partition() {
-        let col = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
-        let computed = col.partition(2, |_idx, x| x % 2)
-            .sort_by(|x| *x);
-        assert_eq!(computed.partitions.len(), 2);
-        let results = computed.run(&mut LeveledScheduler).unwrap();
-        assert_eq!(results, vec![2, 2, 1, 1, 3]);
+    fn test_sink_named() {
+        let path = test_dir("sink-named");
+        let path = path.to_str().unwrap();
+
+        let col = MemoryCollection::from_vec(vec!["a".to_owned(), "b".into(), "c".into()])
+            .concat(&MemoryCollection::from_vec(vec!["d".to_owned()]));
+        let counts = col.sink_named(path, |idx| format!("part-{:05}.txt", idx))
+            .run(&LeveledScheduler::new()).unwrap();
+        assert_eq!(counts, vec![3, 1]);
+
+        let mut names: Vec<_> = fs::read_dir(path).unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["part-00000.txt", "part-00001.txt"]);
+        assert_eq!(fs::read_to_string(format!("{}/part-00000.txt", path)).unwrap(), "a\nb\nc\n");
+        assert_eq!(fs::read_to_string(format!("{}/part-00001.txt", path)).unwrap(), "d\n");
+        fs::remove_dir_all(path).unwrap();
     }
 
     #[test]
-    fn test_count() {
-        let col = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
-        let results = col.split(3).count().run(&mut LeveledScheduler).unwrap();
-        assert_eq!(results, vec![5]);
+    fn test_filter_keys_in() {
+        let rows: Vec<_> = (0..12usize).map(|i| (i % 4, format!("row{}", i))).collect();
+        let col = MemoryCollection::from_vec(rows).split(3);
+        let keys: HashSet<usize> = vec![1, 3].into_iter().collect();
+        let mut kept = col.filter_keys_in(keys, |x| x.0).run(&LeveledScheduler::new()).unwrap();
+        kept.sort();
+        assert_eq!(kept.len(), 6);
+        assert!(kept.iter().all(|x| x.0 == 1 || x.0 == 3));
+        assert_eq!(kept[0], (1, "row1".into()));
+
+        let none = col.filter_keys_in(HashSet::new(), |x| x.0);
+        assert_eq!(none.run(&LeveledScheduler::new()), Some(Vec::new()));
     }
 
     #[test]
-    fn test_join() {
-        let col1 = MemoryCollection::from_vec(vec![1,2,3,1,2usize]);
-        let col2 = MemoryCollection::from_vec(
-            vec![(2, 1.23f64), (3usize, 2.34)]);
-        let out = col1.join_on(&col2, |x| *x, |y| y.0, |x, y| {
-            (*x, y.1)
-        }, 5).split(1).sort_by(|x| x.0);
-        let results = out.run(&mut LeveledScheduler).unwrap();
-        let expected = vec![(2, (2, 1.23)), (2, (2, 1.23)), (3, (3, 2.34))];
-        assert_eq!(results, expected);
+    fn test_auto_rebalance() {
+        let s = LeveledScheduler::new();
+        let sizes = |c: &MemoryCollection<usize>| -> Vec<usize> {
+            c.partitions.iter().map(|p| p.run(&s).unwrap().len()).collect()
+        };
+
+        let skewed = MemoryCollection::from_vec((0..90usize).collect())
+            .concat(&MemoryCollection::from_vec((90..95usize).collect()))
+            .concat(&MemoryCollection::from_vec((95..100usize).collect()));
+        let rebalanced = skewed.auto_rebalance(&s, 2.0);
+        assert_eq!(sizes(&rebalanced), vec![33, 33, 34]);
+        assert_eq!(rebalanced.run(&s), skewed.run(&s));
+
+        let even = MemoryCollection::from_vec((0..90usize).collect()).split(3);
+        let kept = even.auto_rebalance(&s, 2.0);
+        assert_eq!(kept.explain(), even.explain());
+
+        // The threshold decides whether the same collection is rebalanced
+        assert_eq!(skewed.auto_rebalance(&s, 3.0).explain(), skewed.explain());
     }
 
     #[test]
-    fn test_emit() {
-        let results = MemoryCollection::from_vec(vec![1,2,3usize])
-            .emit(|num, emitter| {
-                for i in 0..*num {
-                    emitter(i);
-                }
-            })
-            .sort_by(|x| *x)
-            .run(&mut LeveledScheduler).unwrap();
-        let expected = vec![0, 0, 0, 1, 1, 2];
-        assert_eq!(results, expected);
+    fn test_split_balanced() {
+        let s = LeveledScheduler::new();
+        let col = MemoryCollection::from_vec((0..100usize).collect()).split(4);
+        let parts: Vec<_> = col.partitions.iter().map(|p| p.run(&s).unwrap()).collect();
+        assert_eq!(parts.iter().map(|p| p.len()).collect::<Vec<_>>(), vec![25; 4]);
+        assert_eq!(parts[1], (0..25).map(|i| i * 4 + 1).collect::<Vec<_>>());
     }
 
     #[test]
-    fn test_sort() {
-        let results = MemoryCollection::from_vec(vec![1, 3, 2usize])
-            .sort_by(|x| *x)
-            .run(&mut LeveledScheduler).unwrap();
-        let expected = vec![1, 2, 3];
-        assert_eq!(results, expected);
+    fn test_reduce_to_deferred() {
+        let s = LeveledScheduler::new();
+        let left = MemoryCollection::from_vec((1..=10usize).collect()).split(3);
+        let right = MemoryCollection::from_vec(vec![100usize, 200])
+            .concat(&MemoryCollection::from_vec(Vec::new()));
+        let l = left.reduce_to_deferred(|x, y| x + y);
+        let r = right.reduce_to_deferred(|x, y| x + y);
+        let total = l.join(&r, |a, b| a.and_then(|a| b.map(|b| a + b)));
+        assert_eq!(total.run(&s), Some(Some(355)));
+
+        // Partitions exist, but hold no items
+        let empty = left.filter(|_| false);
+        assert_eq!(empty.n_partitions(), 3);
+        assert_eq!(empty.reduce_to_deferred(|x, y| x + y).run(&s), Some(None));
+
+        let none: MemoryCollection<usize> = MemoryCollection { partitions: Vec::new() };
+        assert_eq!(none.reduce_to_deferred(|x, y| x + y).run(&s), Some(None));
     }
 
+    #[test]
+    fn test_shuffles_no_partitions() {
+        // Building the graph used to panic; with nothing to shuffle, no partitions come out
+        let none: MemoryCollection<usize> = MemoryCollection { partitions: Vec::new() };
+        assert_eq!(none.group_shuffle(3, |x| x % 2).n_partitions(), 0);
+        assert_eq!(none.reduce_by_key(|x| x % 2, |x, y| x + y, 3).n_partitions(), 0);
+        assert_eq!(none.map_reduce_by_key(|x| (x % 2, *x), |x, y| x + y, 3).n_partitions(), 0);
+    }
 }
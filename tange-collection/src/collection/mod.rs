@@ -7,9 +7,445 @@ pub mod memory;
 pub mod disk;
 
 use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fs;
+use std::io::prelude::*;
+use std::io::{self,BufReader,BufWriter};
+use std::process::{Command,Stdio};
+use std::sync::{Arc,Mutex};
+use std::panic::{self,AssertUnwindSafe};
+use std::sync::mpsc;
+use std::thread;
 
-use tange::deferred::{Deferred, batch_apply};
+use tange::deferred::{Deferred, batch_apply, tree_reduce, collect_deferreds};
+use tange::scheduler::Scheduler;
 use interfaces::{Accumulator,ValueWriter,Stream};
+use partitioned::concat;
+use utils::SideAccumulator;
+
+/// Describes how a persisted collection's partitions are retained between runs by
+/// schedulers with a cache.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum StorageLevel {
+    /// Keeps the computed partitions in memory
+    Memory,
+
+    /// Serializes the computed partitions to disk, reading them back on each use
+    Disk,
+
+    /// Keeps the computed partitions in memory, falling back to a serialized copy on disk
+    /// once they are evicted
+    MemoryAndDisk
+}
+
+/// Describes how the value of a key differs between two versions of a keyed collection
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub enum Change<V> {
+    /// The key only exists in the new version
+    Added(V),
+
+    /// The key only exists in the old version
+    Removed(V),
+
+    /// The key's value differs, holding the old and new values
+    Modified(V, V),
+
+    /// The key has the same value in both versions
+    Unchanged
+}
+
+/// Guards against requesting an empty set of output partitions
+fn check_partitions(method: &str, partitions: usize) {
+    assert!(partitions > 0, "{} requires at least one partition, got 0", method);
+}
+
+/// Labels the final node of every partition with the operation which created it, so
+/// that `explain` can describe the plan.
+fn label<A: Any + Send + Sync>(defs: &[Deferred<A>], name: &str) -> Vec<Deferred<A>> {
+    defs.iter().map(|d| d.named(name)).collect()
+}
+
+/// Describes the chain of tasks computing each partition, one partition per line.
+fn explain<A: Any + Send + Sync>(kind: &str, defs: &[Deferred<A>]) -> String {
+    let mut out = format!("{} with {} partition(s)\n", kind, defs.len());
+    for (idx, d) in defs.iter().enumerate() {
+        out.push_str(&format!("  {}: {}\n", idx, d.lineage().join(" -> ")));
+    }
+    out
+}
+
+/// An item with its rank, ordered so that a `BinaryHeap` pops the lowest ranked item
+/// first.  Among equal ranks, the item added last (highest `seq`) pops first.
+#[derive(Clone,Serialize,Deserialize)]
+struct Ranked<S, A> {
+    rank: S,
+    seq: usize,
+    item: A
+}
+
+impl <S: Ord, A> Ord for Ranked<S, A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.rank.cmp(&self.rank).then_with(|| self.seq.cmp(&other.seq))
+    }
+}
+
+impl <S: Ord, A> PartialOrd for Ranked<S, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl <S: Ord, A> PartialEq for Ranked<S, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl <S: Ord, A> Eq for Ranked<S, A> {}
+
+/// Keeps the `k` highest ranked items added so far in a min-heap, so each item costs
+/// O(log k).  Items tied with ones already present are kept in the order they were added.
+#[derive(Clone,Serialize,Deserialize)]
+struct TopK<S: Ord, A> {
+    k: usize,
+    added: usize,
+    heap: BinaryHeap<Ranked<S, A>>
+}
+
+impl <S: Ord + Clone, A: Clone> TopK<S, A> {
+    fn new(k: usize) -> Self {
+        TopK { k, added: 0, heap: BinaryHeap::new() }
+    }
+
+    fn push(&mut self, rank: S, item: A) {
+        self.heap.push(Ranked { rank, seq: self.added, item });
+        self.added += 1;
+        if self.heap.len() > self.k {
+            self.heap.pop();
+        }
+    }
+
+    /// Adds the items of `other`, as if they were added after the items of `self`
+    fn merge(&mut self, other: &TopK<S, A>) {
+        for r in other.heap.iter() {
+            self.heap.push(Ranked { rank: r.rank.clone(), seq: self.added + r.seq, item: r.item.clone() });
+            if self.heap.len() > self.k {
+                self.heap.pop();
+            }
+        }
+        self.added += other.added;
+    }
+
+    /// Returns the items by descending rank
+    fn sorted(&self) -> Vec<A> {
+        self.heap.clone().into_sorted_vec().into_iter().map(|r| r.item).collect()
+    }
+}
+
+/// Steps a SplitMix64 generator, returning a uniform value in [0, 1).
+fn next_uniform(state: &mut u64) -> f64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Draws a uniform sample of up to `n` items from every partition.  Each item is given a 
+/// random priority and the `n` items with the highest priorities are kept, first within
+/// each partition and then across the merged reservoirs.
+fn reservoir<
+    A: Any + Send + Sync + Clone,
+    Col: Any + Send + Sync + Clone + Stream<A>
+>(defs: &[Deferred<Col>], n: usize, seed: u64) -> Deferred<Vec<A>> {
+    let reservoirs = batch_apply(defs, move |idx, vs| {
+        let mut state = partition_seed(seed, idx);
+        let mut top = TopK::new(n);
+        for v in vs.stream() {
+            // Priorities are non-negative, so their bits sort in the same order
+            top.push(next_uniform(&mut state).to_bits(), v);
+        }
+        top
+    });
+    tree_reduce(&reservoirs, |left, right| {
+        let mut top = left.clone();
+        top.merge(right);
+        top
+    })
+    .map(|top| top.apply(|top| top.sorted()))
+    .unwrap_or_else(|| Deferred::lift(Vec::new(), None))
+}
+
+/// Seeds the generator for a partition, so independent partitions draw distinct streams.
+fn partition_seed(seed: u64, idx: usize) -> u64 {
+    seed ^ (idx as u64).wrapping_mul(0xD6E8_FEB8_6659_FD93)
+}
+
+/// Scatters items into `partitions` random buckets, then shuffles each bucket in place.
+/// Both steps draw from generators seeded by `seed` and the partition index, so the
+/// output is deterministic for a given seed and input partitioning.
+fn shuffle<
+    A: Clone,
+    Col: Any + Sync + Send + Clone + Accumulator<A> + Stream<A>
+>(defs: &[Deferred<Col>], partitions: usize, seed: u64) -> Vec<Deferred<Col>>
+        where Col::VW: ValueWriter<A,Out=Col> {
+
+    let stage1 = batch_apply(defs, move |idx, vs| {
+        let mut state = partition_seed(seed, idx);
+        let mut parts: Vec<_> = (0..partitions).map(|_| vs.writer()).collect();
+        for x in vs.stream() {
+            let p = (next_uniform(&mut state) * partitions as f64) as usize;
+            parts[p.min(partitions - 1)].add(x);
+        }
+        parts.into_iter().map(|x| x.finish()).collect::<Vec<_>>()
+    });
+
+    let mut out = Vec::with_capacity(partitions);
+    for p in 0..partitions {
+        let group: Vec<_> = stage1.iter()
+            .map(|s| s.apply(move |parts| parts[p].copy()))
+            .collect();
+        if let Some(d) = concat(&group) {
+            out.push(d.apply(move |vs| {
+                // Fisher-Yates, using a different stream than the scatter step
+                let mut state = partition_seed(!seed, p);
+                let mut items: Vec<_> = vs.stream().into_iter().collect();
+                for i in (1..items.len()).rev() {
+                    let j = (next_uniform(&mut state) * (i + 1) as f64) as usize;
+                    items.swap(i, j.min(i));
+                }
+                let mut w = vs.writer();
+                for x in items {
+                    w.add(x);
+                }
+                w.finish()
+            }));
+        }
+    }
+    out
+}
+
+/// Computes the partitions in a single run on a background thread, sending each down
+/// the returned channel as soon as it is computed, so work shared between partitions is
+/// only done once.  The channel closes once the run finishes or a task fails; the
+/// returned handle tells the two apart.
+fn run_to_channel<
+    A: 'static + Send,
+    Col: Any + Send + Sync + Clone + Stream<A>,
+    S: 'static + Scheduler + Send
+>(defs: Vec<Deferred<Col>>, s: S) -> (mpsc::Receiver<Vec<A>>, thread::JoinHandle<Option<()>>) {
+    let (tx, rx) = mpsc::channel();
+    let tx = Arc::new(Mutex::new(tx));
+    let sent: Vec<_> = defs.iter().map(|d| {
+        let tx = tx.clone();
+        d.apply(move |vs| {
+            let part = vs.stream().into_iter().collect();
+            // The receiver may have hung up, in which case the partition is dropped
+            let _ = tx.lock().unwrap().send(part);
+        })
+    }).collect();
+
+    let handle = thread::spawn(move || {
+        match tree_reduce(&sent, |_, _| ()) {
+            Some(done) => done.run(&s),
+            None => Some(())
+        }
+    });
+    (rx, handle)
+}
+
+/// Feeds each partition through an external process, one process per partition: items
+/// are written to its stdin one per line and each line of its stdout becomes an item of
+/// the new partition.  Panics if the process can't be started or exits unsuccessfully.
+fn pipe<
+    Col: Any + Send + Sync + Clone + Stream<String>,
+    Acc: 'static + Accumulator<String>
+>(defs: &[Deferred<Col>], acc: Acc, command: &str, args: Vec<String>) -> Vec<Deferred<<<Acc as Accumulator<String>>::VW as ValueWriter<String>>::Out>> {
+    let command = Arc::new(command.to_owned());
+    let args = Arc::new(args);
+    batch_apply(defs, move |_idx, vs| {
+        let mut child = Command::new(&*command)
+            .args(&*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|e| panic!("Unable to start command {}: {}", command, e));
+
+        // Drain stdout and stderr on their own threads so a full pipe can't stall the
+        // process while we are still writing to it.
+        let stdout = child.stdout.take().expect("Child stdout was not piped");
+        let lines = thread::spawn(move || {
+            BufReader::new(stdout).lines()
+                .map(|l| l.expect("Error reading line from command"))
+                .collect::<Vec<_>>()
+        });
+        let mut stderr = child.stderr.take().expect("Child stderr was not piped");
+        let errors = thread::spawn(move || {
+            let mut msg = String::new();
+            let _ = stderr.read_to_string(&mut msg);
+            msg
+        });
+
+        // Dropping stdin at the end of this block flushes it and closes the pipe, letting
+        // the process see the end of its input before we wait on its output.
+        {
+            let mut stdin = BufWriter::new(child.stdin.take().expect("Child stdin was not piped"));
+            for line in vs.stream() {
+                // The process may exit without reading all of its input; its exit
+                // status decides whether that is an error.
+                if stdin.write_all(line.as_bytes()).is_err() || stdin.write_all(b"\n").is_err() {
+                    break
+                }
+            }
+        }
+
+        let lines = lines.join().expect("Reading from command failed");
+        let errors = errors.join().expect("Reading from command failed");
+        let status = child.wait().expect("Command wasn't running");
+        if !status.success() {
+            panic!("Command {} failed with {}: {}", command, status, errors.trim());
+        }
+
+        let mut out = acc.writer();
+        for line in lines {
+            out.add(line);
+        }
+        out.finish()
+    })
+}
+
+/// Writes a single partition file to `{dir}/{idx}` without ever exposing a partial file:
+/// `write` fills `{dir}/.{idx}.tmp`, which is only renamed into place once it has been
+/// flushed successfully.  On failure the temporary file is removed and the task panics.
+fn write_atomic<
+    F: FnOnce(&mut BufWriter<fs::File>) -> io::Result<usize>
+>(dir: &str, idx: usize, write: F) -> usize {
+    fs::create_dir_all(dir)
+        .expect("Welp, something went terribly wrong when creating directory");
+
+    let tmp = format!("{}/.{}.tmp", dir, idx);
+    let result = fs::File::create(&tmp).and_then(|file| {
+        let mut bw = BufWriter::new(file);
+        let size = write(&mut bw)?;
+        bw.flush()?;
+        Ok(size)
+    });
+
+    match result.and_then(|size| fs::rename(&tmp, format!("{}/{}", dir, idx)).map(|_| size)) {
+        Ok(size) => size,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            panic!("Error writing partition {} to {}: {}", idx, dir, e)
+        }
+    }
+}
+
+/// Runs a user function on a single item, re-raising any panic with the partition index
+/// and the item's position within the partition added to the message.
+fn with_context<R, F: FnOnce() -> R>(partition: usize, element: usize, f: F) -> R {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(r) => r,
+        Err(e) => {
+            let msg = e.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| e.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "non-string panic payload".into());
+            panic!("Panicked on element {} of partition {}: {}", element, partition, msg)
+        }
+    }
+}
+
+/// Concatenates the partitions into a single Vec, along with the offsets delimiting each
+/// partition: partition `i` spans `offsets[i]..offsets[i + 1]`.
+fn with_offsets<
+    A: Any + Send + Sync + Clone,
+    Col: Any + Send + Sync + Clone + Stream<A>
+>(defs: &[Deferred<Col>]) -> Deferred<(Vec<A>, Vec<usize>)> {
+    let parts = batch_apply(defs, |_idx, vs| {
+        let items: Vec<_> = vs.stream().into_iter().collect();
+        let len = items.len();
+        (items, vec![0, len])
+    });
+    tree_reduce(&parts, |left, right| {
+        let mut items = left.0.clone();
+        items.extend_from_slice(&right.0);
+        let base = left.0.len();
+        let mut offsets = left.1.clone();
+        for o in right.1[1..].iter() {
+            offsets.push(base + o);
+        }
+        (items, offsets)
+    }).unwrap_or_else(|| Deferred::lift((Vec::new(), vec![0]), None))
+}
+
+/// Gathers the first and second fields of every pair into two parallel Vecs, with 
+/// partitions concatenated in order.
+fn to_columns<
+    A: Any + Send + Sync + Clone,
+    B: Any + Send + Sync + Clone,
+    Col: Any + Send + Sync + Clone + Stream<(A, B)>
+>(defs: &[Deferred<Col>]) -> Deferred<(Vec<A>, Vec<B>)> {
+    let parts = batch_apply(defs, |_idx, vs| {
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        for (a, b) in vs.stream() {
+            left.push(a);
+            right.push(b);
+        }
+        (left, right)
+    });
+    tree_reduce(&parts, |l, r| {
+        let mut left = l.0.clone();
+        left.extend_from_slice(&r.0);
+        let mut right = l.1.clone();
+        right.extend_from_slice(&r.1);
+        (left, right)
+    }).unwrap_or_else(|| Deferred::lift((Vec::new(), Vec::new()), None))
+}
+
+/// Drops the empty partitions, found by a pass with `s` which only checks whether each
+/// partition has an item.  The remaining partitions are kept as they are, so they stay
+/// lazy.  If the pass fails, every partition is kept.
+fn non_empty<
+    A,
+    Col: Any + Send + Sync + Clone + Stream<A>,
+    S: Scheduler
+>(defs: &[Deferred<Col>], s: &S) -> Vec<Deferred<Col>> {
+    let has_items = batch_apply(defs, |_idx, vs| vs.stream().into_iter().next().is_some());
+    match collect_deferreds(&has_items).run(s) {
+        Some(flags) => defs.iter().zip(flags)
+            .filter(|&(_, has_items)| has_items)
+            .map(|(d, _)| d.clone())
+            .collect(),
+        None => defs.to_vec()
+    }
+}
+
+/// Computes partitions in order, one at a time, until at least `n` items are gathered.
+/// Later partitions are never computed.
+fn first_n<
+    A,
+    Col: Any + Send + Sync + Clone + Stream<A>,
+    S: Scheduler
+>(defs: &[Deferred<Col>], n: usize, s: &S) -> Vec<A> {
+    let mut out = Vec::new();
+    for d in defs {
+        if out.len() >= n {
+            break
+        }
+        match d.run(s) {
+            Some(vs) => {
+                for v in vs.stream().into_iter().take(n - out.len()) {
+                    out.push(v);
+                }
+            },
+            None => break
+        }
+    }
+    out
+}
 
 fn emit<
     A,
@@ -19,12 +455,84 @@ fn emit<
     Acc: 'static + Accumulator<B>
 >(defs: &[Deferred<Col>], acc: Acc, f: F) -> Vec<Deferred<<<Acc as Accumulator<B>>::VW as ValueWriter<B>>::Out>> {
 
-    batch_apply(&defs, move |_idx, vs| {
+    batch_apply(defs, move |idx, vs| {
+        let mut out = acc.writer();
+        for (i, v) in vs.stream().into_iter().enumerate() {
+            with_context(idx, i, || f(&v, &mut |r| out.add(r)));
+        }
+        out.finish()
+    })
+}
+
+
+/// How many items `map_progress` processes between progress reports
+const PROGRESS_EVERY: usize = 10_000;
+
+/// Maps each item with `f`, calling `progress(partition, processed, total)` every
+/// `PROGRESS_EVERY` items and once more when the partition is done.  The partition is
+/// streamed once up front to count its items.
+fn map_progress<
+    A,
+    Col: Any + Send + Sync + Clone + Stream<A>,
+    B: Any + Send + Sync + Clone,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> B,
+    P: 'static + Sync + Send + Clone + Fn(usize, usize, usize),
+    Acc: 'static + Accumulator<B>
+>(defs: &[Deferred<Col>], acc: Acc, f: F, progress: P) -> Vec<Deferred<<<Acc as Accumulator<B>>::VW as ValueWriter<B>>::Out>> {
+
+    batch_apply(defs, move |idx, vs| {
+        let total = vs.stream().into_iter().count();
         let mut out = acc.writer();
-        for v in vs.stream().into_iter() {
-            f(&v, &mut |r| out.add(r));
+        for (i, v) in vs.stream().into_iter().enumerate() {
+            out.add(with_context(idx, i, || f(&v)));
+            if (i + 1) % PROGRESS_EVERY == 0 && i + 1 < total {
+                progress(idx, i + 1, total);
+            }
         }
+        progress(idx, total, total);
         out.finish()
     })
 }
 
+/// Maps each item with `f`, which may also update the partition's partial value for
+/// `side`.  The partial is merged into `side` once the partition is done.
+fn map_accumulating<
+    A,
+    Col: Any + Send + Sync + Clone + Stream<A>,
+    B: Any + Send + Sync + Clone,
+    T: 'static + Send + Sync + Clone,
+    F: 'static + Sync + Send + Clone + Fn(&A, &mut T) -> B,
+    Acc: 'static + Accumulator<B>
+>(defs: &[Deferred<Col>], acc: Acc, side: &SideAccumulator<T>, f: F) -> Vec<Deferred<<<Acc as Accumulator<B>>::VW as ValueWriter<B>>::Out>> {
+
+    let side = side.clone();
+    batch_apply(defs, move |idx, vs| {
+        let mut partial = side.zero();
+        let mut out = acc.writer();
+        for (i, v) in vs.stream().into_iter().enumerate() {
+            out.add(with_context(idx, i, || f(&v, &mut partial)));
+        }
+        side.merge(partial);
+        out.finish()
+    })
+}
+
+fn flat_map_indexed<
+    A,
+    Col: Any + Send + Sync + Clone + Stream<A>,
+    B: Any + Send + Sync + Clone,
+    I: IntoIterator<Item=B>,
+    F: 'static + Sync + Send + Clone + Fn(usize, &A) -> I,
+    Acc: 'static + Accumulator<B>
+>(defs: &[Deferred<Col>], acc: Acc, f: F) -> Vec<Deferred<<<Acc as Accumulator<B>>::VW as ValueWriter<B>>::Out>> {
+
+    batch_apply(defs, move |idx, vs| {
+        let mut out = acc.writer();
+        for (i, v) in vs.stream().into_iter().enumerate() {
+            for r in with_context(idx, i, || f(i, &v)) {
+                out.add(r);
+            }
+        }
+        out.finish()
+    })
+}
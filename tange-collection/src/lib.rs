@@ -91,6 +91,8 @@
 #![warn(missing_docs)]
 
 extern crate tange;
+#[macro_use]
+extern crate serde_derive;
 
 /// Defines useful utilities, such as reading files
 pub mod utils;
@@ -101,5 +103,11 @@ pub mod interfaces;
 /// Defines the two major primitives: MemoryColleciton and DiskCollection
 pub mod collection;
 
+/// Mergeable summaries for approximate aggregations
+pub mod sketch;
+
+/// Reusable strategies for assigning items to partitions
+pub mod partition;
+
 mod partitioned;
 
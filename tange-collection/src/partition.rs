@@ -0,0 +1,181 @@
+//! Reusable strategies for assigning items to partitions.
+use std::hash::Hash;
+
+use partitioned::hash_key;
+
+/// Assigns items to one of a fixed number of partitions.
+pub trait Partitioner<A> {
+
+    /// Number of partitions items are assigned to
+    fn num_partitions(&self) -> usize;
+
+    /// Partition index for an item, within `0..num_partitions()`
+    fn partition_of(&self, a: &A) -> usize;
+}
+
+/// Assigns items to partitions by the hash of a key extracted from each item.
+/// ```rust
+///   extern crate tange_collection;
+///   use tange_collection::partition::{Partitioner,HashPartitioner};
+///
+///   let p = HashPartitioner::new(4, |x: &(usize, &str)| x.0);
+///   assert_eq!(p.num_partitions(), 4);
+///   assert_eq!(p.partition_of(&(1, "a")), p.partition_of(&(1, "b")));
+/// ```
+#[derive(Clone)]
+pub struct HashPartitioner<F> {
+    partitions: usize,
+    key: F
+}
+
+impl <F> HashPartitioner<F> {
+
+    /// Creates a new HashPartitioner over `partitions` partitions.
+    pub fn new(partitions: usize, key: F) -> Self {
+        assert!(partitions > 0, "HashPartitioner requires at least one partition, got 0");
+        HashPartitioner { partitions, key }
+    }
+}
+
+impl <A, K: Hash, F: Fn(&A) -> K> Partitioner<A> for HashPartitioner<F> {
+    fn num_partitions(&self) -> usize {
+        self.partitions
+    }
+
+    fn partition_of(&self, a: &A) -> usize {
+        hash_key(&(self.key)(a)) % self.partitions
+    }
+}
+
+/// Assigns items to contiguous ranges of a key, split by a sorted set of boundaries.  
+/// Keys less than the first boundary are assigned to partition 0, keys at least the first 
+/// but less than the second to partition 1, and so on, yielding `bounds.len() + 1` 
+/// partitions.
+/// ```rust
+///   extern crate tange_collection;
+///   use tange_collection::partition::{Partitioner,RangePartitioner};
+///
+///   let p = RangePartitioner::new(vec![10, 20], |x: &usize| *x);
+///   assert_eq!(p.num_partitions(), 3);
+///   assert_eq!(p.partition_of(&5), 0);
+///   assert_eq!(p.partition_of(&10), 1);
+///   assert_eq!(p.partition_of(&25), 2);
+/// ```
+#[derive(Clone)]
+pub struct RangePartitioner<K, F> {
+    bounds: Vec<K>,
+    key: F
+}
+
+impl <K: Ord, F> RangePartitioner<K, F> {
+
+    /// Creates a new RangePartitioner.  Panics if the boundaries are not sorted.
+    pub fn new(bounds: Vec<K>, key: F) -> Self {
+        assert!(bounds.windows(2).all(|w| w[0] <= w[1]), 
+                "RangePartitioner requires sorted boundaries");
+        RangePartitioner { bounds, key }
+    }
+}
+
+impl <A, K: Ord, F: Fn(&A) -> K> Partitioner<A> for RangePartitioner<K, F> {
+    fn num_partitions(&self) -> usize {
+        self.bounds.len() + 1
+    }
+
+    fn partition_of(&self, a: &A) -> usize {
+        let k = (self.key)(a);
+        self.bounds.partition_point(|b| *b <= k)
+    }
+}
+
+/// Assigns items to partitions by placing the hash of a key on a consistent-hashing ring.
+/// Each partition owns a number of points on the ring, and a key belongs to the partition
+/// owning the first point at or after its hash.  Changing the number of partitions from 
+/// `n` to `n + 1` only moves about `1 / (n + 1)` of the keys, where plain hash partitioning
+/// moves almost all of them.
+/// ```rust
+///   extern crate tange_collection;
+///   use tange_collection::partition::{Partitioner,ConsistentHashPartitioner};
+///
+///   let p = ConsistentHashPartitioner::new(4, |x: &(usize, &str)| x.0);
+///   assert_eq!(p.num_partitions(), 4);
+///   assert_eq!(p.partition_of(&(1, "a")), p.partition_of(&(1, "b")));
+/// ```
+#[derive(Clone)]
+pub struct ConsistentHashPartitioner<F> {
+    partitions: usize,
+    ring: Vec<(usize, usize)>,
+    key: F
+}
+
+/// Number of points each partition owns on the ring
+const RING_POINTS: usize = 128;
+
+impl <F> ConsistentHashPartitioner<F> {
+
+    /// Creates a new ConsistentHashPartitioner over `partitions` partitions.
+    pub fn new(partitions: usize, key: F) -> Self {
+        assert!(partitions > 0, 
+                "ConsistentHashPartitioner requires at least one partition, got 0");
+        let mut ring = Vec::with_capacity(partitions * RING_POINTS);
+        for p in 0..partitions {
+            for point in 0..RING_POINTS {
+                ring.push((hash_key(&(p, point)), p));
+            }
+        }
+        ring.sort();
+        ConsistentHashPartitioner { partitions, ring, key }
+    }
+}
+
+impl <A, K: Hash, F: Fn(&A) -> K> Partitioner<A> for ConsistentHashPartitioner<F> {
+    fn num_partitions(&self) -> usize {
+        self.partitions
+    }
+
+    fn partition_of(&self, a: &A) -> usize {
+        let h = hash_key(&(self.key)(a));
+        let idx = self.ring.partition_point(|&(point, _)| point < h);
+        self.ring[idx % self.ring.len()].1
+    }
+}
+
+#[cfg(test)]
+mod test_partition {
+    use super::*;
+
+    #[test]
+    fn test_hash_partitioner() {
+        let p = HashPartitioner::new(3, |x: &(usize, char)| x.0);
+        for i in 0..100usize {
+            let idx = p.partition_of(&(i, 'a'));
+            assert!(idx < 3);
+            assert_eq!(idx, p.partition_of(&(i, 'b')));
+        }
+    }
+
+    #[test]
+    fn test_range_partitioner() {
+        let p = RangePartitioner::new(vec![0, 10, 10, 20], |x: &i32| *x);
+        assert_eq!(p.num_partitions(), 5);
+        let idxs: Vec<_> = [-5, 0, 9, 10, 19, 20, 100].iter().map(|x| p.partition_of(x)).collect();
+        assert_eq!(idxs, vec![0, 1, 1, 3, 3, 4, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "RangePartitioner requires sorted boundaries")]
+    fn test_range_partitioner_unsorted() {
+        RangePartitioner::new(vec![2, 1], |x: &i32| *x);
+    }
+
+    #[test]
+    fn test_consistent_hash_partitioner() {
+        let p = ConsistentHashPartitioner::new(5, |x: &usize| *x);
+        let mut counts = vec![0usize; 5];
+        for i in 0..10000usize {
+            counts[p.partition_of(&i)] += 1;
+        }
+        // Every partition owns a reasonable share of the keys
+        assert!(counts.iter().all(|c| *c > 1000), "{:?}", counts);
+    }
+}
@@ -3,11 +3,17 @@ extern crate tange;
 use std::any::Any;
 use std::hash::{Hasher,Hash};
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
+use std::collections::{HashMap,HashSet};
 use std::sync::Arc;
+use std::fs;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
 
-use tange::deferred::{Deferred, batch_apply, tree_reduce};
+use tange::deferred::{Deferred, batch_apply, batch_join, tree_reduce, collect_deferreds};
 use interfaces::*;
+use sketch::{TDigest,BloomFilter};
+use collection::Change;
 
 pub fn block_reduce<
     A,
@@ -37,6 +43,134 @@ pub fn block_reduce<
     })
 }
 
+pub fn combine_by_key<
+    A: Any + Sync + Send + Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    K: Any + Sync + Send + Clone + Hash + Eq,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K,
+    R: 'static + Sync + Send + Clone + Fn(&A, &A) -> A,
+    Acc: 'static + Accumulator<(K, A)> + Stream<(K, A)>
+>(
+    defs: &[Deferred<Col>],
+    key: F,
+    reduce: R,
+    acc: Acc
+) -> Vec<Deferred<Acc>>
+        where Acc::VW: ValueWriter<(K, A), Out=Acc> {
+    block_reduce(defs, key, || None, move |agg: &mut Option<A>, v: &A| {
+        let next = match agg.take() {
+            Some(prev) => reduce(&prev, v),
+            None       => v.clone()
+        };
+        *agg = Some(next);
+    }, move |groups| {
+        let mut out = acc.writer();
+        for (k, v) in groups {
+            if let Some(v) = v {
+                out.add((k, v));
+            }
+        }
+        out.finish()
+    })
+}
+
+pub fn reduce_by_key<
+    A: Any + Sync + Send + Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    K: Any + Sync + Send + Clone + Hash + Eq,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K,
+    R: 'static + Sync + Send + Clone + Fn(&A, &A) -> A,
+    Acc: 'static + Accumulator<(K, A)> + Stream<(K, A)>
+>(
+    defs: &[Deferred<Col>],
+    key: F,
+    reduce: R,
+    acc: Acc,
+    partitions: usize
+) -> Vec<Deferred<Acc>>
+        where Acc::VW: ValueWriter<(K, A), Out=Acc> {
+
+    // Combine values within each partition so only one value per key is shuffled
+    let combined = combine_by_key(defs, key, reduce.clone(), acc);
+    merge_by_key(&combined, reduce, partitions)
+}
+
+/// Maps each item to a key and value, then reduces all values sharing a key.  Values
+/// are reduced as they are mapped, so the mapped pairs are never stored.
+pub fn map_reduce_by_key<
+    A: Any + Sync + Send + Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    K: Any + Sync + Send + Clone + Hash + Eq,
+    V: Any + Sync + Send + Clone,
+    M: 'static + Sync + Send + Clone + Fn(&A) -> (K, V),
+    R: 'static + Sync + Send + Clone + Fn(&V, &V) -> V,
+    Acc: 'static + Accumulator<(K, V)> + Stream<(K, V)>
+>(
+    defs: &[Deferred<Col>],
+    map: M,
+    reduce: R,
+    acc: Acc,
+    partitions: usize
+) -> Vec<Deferred<Acc>>
+        where Acc::VW: ValueWriter<(K, V), Out=Acc> {
+
+    let r = reduce.clone();
+    let combined = batch_apply(defs, move |_idx, vs| {
+        let mut hm = HashMap::new();
+        for x in vs.stream() {
+            let (k, v) = map(&x);
+            reduce_into(&mut hm, k, v, &r);
+        }
+        let mut out = acc.writer();
+        for item in hm {
+            out.add(item);
+        }
+        out.finish()
+    });
+    merge_by_key(&combined, reduce, partitions)
+}
+
+// Reduces a value into the running value for its key
+fn reduce_into<K: Hash + Eq, V, R: Fn(&V, &V) -> V>(hm: &mut HashMap<K, V>, k: K, v: V, reduce: &R) {
+    let next = match hm.remove(&k) {
+        Some(prev) => reduce(&prev, &v),
+        None       => v
+    };
+    hm.insert(k, next);
+}
+
+// Co-partitions pairs already combined within each partition, reducing the values for
+// each key into one
+fn merge_by_key<
+    K: Any + Sync + Send + Clone + Hash + Eq,
+    V: Any + Sync + Send + Clone,
+    R: 'static + Sync + Send + Clone + Fn(&V, &V) -> V,
+    Acc: 'static + Accumulator<(K, V)> + Stream<(K, V)>
+>(
+    combined: &[Deferred<Acc>],
+    reduce: R,
+    partitions: usize
+) -> Vec<Deferred<Acc>>
+        where Acc::VW: ValueWriter<(K, V), Out=Acc> {
+    let chunks = partition_by_key::<Acc,_,_,_>(combined, partitions, |x| x.0.clone());
+
+    // Without any input partitions there is nothing to merge, so no partitions come out
+    chunks.into_iter().filter_map(|chunk| {
+        let reduce = reduce.clone();
+        concat(&chunk).map(|merged| merged.apply(move |vs| {
+            let mut hm: HashMap<K, V> = HashMap::new();
+            for (k, v) in vs.stream() {
+                reduce_into(&mut hm, k, v, &reduce);
+            }
+            let mut out = vs.writer();
+            for item in hm {
+                out.add(item);
+            }
+            out.finish()
+        }))
+    }).collect()
+}
+
 pub fn split_by_key<
     Col: Any + Sync + Send + Clone + Accumulator<A> + Stream<A>,
     A: Clone,
@@ -184,14 +318,104 @@ pub fn partition_by_key<
     key: F
 ) -> Vec<Vec<Deferred<C>>>
         where C::VW: ValueWriter<A,Out=C> {
-    split_by_key(defs, n_chunks, move |_idx, v| {
-        let k = key(v);
-        let mut hasher = DefaultHasher::new();
-        k.hash(&mut hasher);
-        hasher.finish() as usize
+    split_by_key(defs, n_chunks, move |_idx, v| hash_key(&key(v)))
+}
+
+pub fn group_shuffle<
+    A: Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    K: Any + Sync + Send + Clone + Hash + Eq,
+    GCol: Any + Sync + Send + Clone + Accumulator<(K, Vec<A>)> + Stream<(K, Vec<A>)>,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K,
+    Acc: 'static + Accumulator<(K, Vec<A>)>
+>(
+    defs: &[Deferred<Col>], 
+    partitions: usize, 
+    key: F,
+    acc: Acc
+) -> Vec<Deferred<GCol>>
+        where Acc::VW: ValueWriter<(K, Vec<A>), Out=GCol>,
+              GCol::VW: ValueWriter<(K, Vec<A>), Out=GCol> {
+
+    // Group each chunk locally, bucketing the groups by the hash of their key
+    let stage1 = batch_apply(defs, move |_idx, vs| {
+        let mut buckets: Vec<HashMap<K, Vec<A>>> = (0..partitions).map(|_| HashMap::new()).collect();
+        for v in vs.stream() {
+            let k = key(&v);
+            let p = hash_key(&k) % partitions;
+            buckets[p].entry(k).or_default().push(v);
+        }
+        buckets.into_iter().map(|groups| {
+            let mut out = acc.writer();
+            for kv in groups {
+                out.add(kv);
+            }
+            out.finish()
+        }).collect::<Vec<_>>()
+    });
+
+    // Merge the groups for each bucket while concatenating the chunks.  Without any input
+    // partitions there is nothing to merge, so no partitions come out
+    (0..partitions).filter_map(|idx| {
+        let parts: Vec<_> = stage1.iter()
+            .map(|s| s.apply(move |buckets| buckets[idx].copy()))
+            .collect();
+        tree_reduce(&parts, |x, y| {
+            let mut groups: HashMap<K, Vec<A>> = HashMap::new();
+            for (k, mut vs) in x.stream().into_iter().chain(y.stream()) {
+                groups.entry(k).or_default().append(&mut vs);
+            }
+            let mut out = x.writer();
+            for kv in groups {
+                out.add(kv);
+            }
+            out.finish()
+        })
+    }).collect()
+}
+
+/// Groups the values within each partition by key, emitting a group as soon as it holds
+/// `max_group` values so no more than `max_group` values per key are held at once.  Keys
+/// with more values are emitted as several groups.
+pub fn group_capped<
+    A: Any + Sync + Send + Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    K: Any + Sync + Send + Clone + Hash + Eq,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K,
+    Acc: 'static + Accumulator<(K, Vec<A>)>
+>(
+    defs: &[Deferred<Col>], 
+    key: F,
+    max_group: usize,
+    acc: Acc
+) -> Vec<Deferred<<<Acc as Accumulator<(K, Vec<A>)>>::VW as ValueWriter<(K, Vec<A>)>>::Out>> {
+    batch_apply(defs, move |_idx, vs| {
+        let mut out = acc.writer();
+        let mut groups: HashMap<K, Vec<A>> = HashMap::new();
+        for v in vs.stream() {
+            let k = key(&v);
+            let group = groups.entry(k.clone()).or_default();
+            group.push(v);
+            if group.len() >= max_group {
+                out.add((k, ::std::mem::take(group)));
+            }
+        }
+        for (k, group) in groups {
+            if !group.is_empty() {
+                out.add((k, group));
+            }
+        }
+        out.finish()
     })
 }
 
+/// Hashes a key into a partition target, which `split_by_key` wraps into range
+pub fn hash_key<K: Hash>(k: &K) -> usize {
+    let mut hasher = DefaultHasher::new();
+    k.hash(&mut hasher);
+    hasher.finish() as usize
+}
+
 pub fn concat<
     Col: Any + Sync + Send + Accumulator<A> + Stream<A>,
     A: Clone,
@@ -212,6 +436,34 @@ pub fn concat<
     })
 }
 
+/// Merges aligned pairs of partitions, alternating between the left and right partition
+/// one item at a time.  Once either side runs out, the rest of the other is appended.
+pub fn interleave<
+    A: Any + Send + Sync + Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    Acc: 'static + Accumulator<A>
+>(
+    d1: &[Deferred<Col>], 
+    d2: &[Deferred<Col>], 
+    acc: Acc
+) -> Vec<Deferred<<<Acc as Accumulator<A>>::VW as ValueWriter<A>>::Out>> {
+    batch_join(d1, d2, move |_idx, l, r| {
+        let mut out = acc.writer();
+        let mut left = l.stream().into_iter();
+        let mut right = r.stream().into_iter();
+        loop {
+            match (left.next(), right.next()) {
+                (None, None) => break,
+                (x, y) => {
+                    if let Some(x) = x { out.add(x); }
+                    if let Some(y) = y { out.add(y); }
+                }
+            }
+        }
+        out.finish()
+    })
+}
+
 pub fn join_on_key<
     A, 
     B,
@@ -247,3 +499,760 @@ pub fn join_on_key<
     })
 }
 
+
+/// Left joins two co-partitioned collections: every left value is paired with each right
+/// value sharing its key, or with `default()` when there are none.
+pub fn left_join_on_key<
+    A: Any + Send + Sync + Clone, 
+    B: Any + Send + Sync + Clone,
+    Col1: Any + Sync + Send + Clone + Stream<(K, A)>,
+    Col2: Any + Sync + Send + Clone + Stream<(K, B)>,
+    K: Any + Send + Sync + Clone + Hash + Eq,
+    D: 'static + Sync + Send + Clone + Fn() -> B,
+    Acc: 'static + Accumulator<(K, (A, B))>
+>(
+    d1: &Deferred<Col1>, 
+    d2: &Deferred<Col2>, 
+    acc: Acc,
+    default: D
+) -> Deferred<<<Acc as Accumulator<(K, (A, B))>>::VW as ValueWriter<(K, (A, B))>>::Out> {
+
+    d1.join(d2, move |left, right| {
+        // Slurp up right into a hashmap
+        let mut hm = HashMap::new();
+        for (k, rv) in right.stream() {
+            let e = hm.entry(k).or_insert_with(|| Vec::with_capacity(1)); 
+            e.push(rv);
+        }
+        let mut ret = acc.writer();
+        for (k, lv) in left.stream() {
+            match hm.get(&k) {
+                Some(rvs) => for rv in rvs.iter() {
+                    ret.add((k.clone(), (lv.clone(), rv.clone())))
+                },
+                None => ret.add((k, (lv, default())))
+            }
+        }
+        ret.finish()
+    })
+}
+
+/// Compares the values of two co-partitioned versions of a keyed collection, classifying
+/// each key by how its value changed from the old version, `d1`, to the new one, `d2`.
+/// Keys are expected to be unique within each version; otherwise the last value wins.
+pub fn diff_on_key<
+    K: Any + Send + Sync + Clone + Hash + Eq,
+    V: Any + Send + Sync + Clone + PartialEq,
+    Col: Any + Sync + Send + Clone + Stream<(K, V)>,
+    Acc: 'static + Accumulator<(K, Change<V>)>
+>(
+    d1: &Deferred<Col>, 
+    d2: &Deferred<Col>, 
+    acc: Acc
+) -> Deferred<<<Acc as Accumulator<(K, Change<V>)>>::VW as ValueWriter<(K, Change<V>)>>::Out> {
+
+    d1.join(d2, move |old, new| {
+        let mut hm: HashMap<K, V> = old.stream().into_iter().collect();
+        let mut ret = acc.writer();
+        for (k, nv) in new.stream() {
+            let change = match hm.remove(&k) {
+                Some(ref ov) if *ov == nv => Change::Unchanged,
+                Some(ov) => Change::Modified(ov, nv),
+                None => Change::Added(nv)
+            };
+            ret.add((k, change));
+        }
+        for (k, ov) in hm {
+            ret.add((k, Change::Removed(ov)));
+        }
+        ret.finish()
+    })
+}
+
+pub fn merge_sorted<
+    A: Any + Send + Sync + Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    K: Ord,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K
+>(
+    defs: &[Deferred<Col>],
+    key: F
+) -> Deferred<Vec<A>> {
+    let k2 = key.clone();
+    let sorted = batch_apply(defs, move |_idx, vs| {
+        let mut v: Vec<_> = vs.stream().into_iter().collect();
+        v.sort_by_key(|x| k2(x));
+        v
+    });
+
+    // Merges two sorted runs, preferring the left side on ties to keep the sort stable
+    tree_reduce(&sorted, move |left, right| {
+        let mut out = Vec::with_capacity(left.len() + right.len());
+        let mut l = left.iter().peekable();
+        let mut r = right.iter().peekable();
+        loop {
+            let take_left = match (l.peek(), r.peek()) {
+                (Some(lv), Some(rv)) => key(lv) <= key(rv),
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break
+            };
+            let next = if take_left { l.next() } else { r.next() };
+            if let Some(v) = next {
+                out.push(v.clone());
+            }
+        }
+        out
+    }).unwrap_or_else(|| Deferred::lift(Vec::new(), None))
+}
+
+pub fn mean<
+    A: Into<f64>,
+    Col: Any + Sync + Send + Clone + Stream<A>
+>(
+    defs: &[Deferred<Col>]
+) -> Deferred<f64> {
+    let sums = batch_apply(defs, |_idx, vs| {
+        let mut total = (0f64, 0usize);
+        for v in vs.stream() {
+            total.0 += v.into();
+            total.1 += 1;
+        }
+        total
+    });
+    tree_reduce(&sums, |left, right| (left.0 + right.0, left.1 + right.1))
+        .unwrap_or_else(|| Deferred::lift((0., 0), None))
+        .apply(|&(sum, count)| if count == 0 { f64::NAN } else { sum / count as f64 })
+}
+
+pub fn count_buckets<
+    A,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    K: Any + Sync + Send + Clone + Hash + Eq,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K
+>(
+    defs: &[Deferred<Col>],
+    key: F
+) -> Deferred<HashMap<K, usize>> {
+    let counts = batch_apply(defs, move |_idx, vs| {
+        let mut hm = HashMap::new();
+        for v in vs.stream() {
+            *hm.entry(key(&v)).or_insert(0usize) += 1;
+        }
+        hm
+    });
+    tree_reduce(&counts, |left, right| {
+        let mut hm = left.clone();
+        for (k, v) in right.iter() {
+            *hm.entry(k.clone()).or_insert(0usize) += *v;
+        }
+        hm
+    }).unwrap_or_else(|| Deferred::lift(HashMap::new(), None))
+}
+
+pub fn reduce<
+    A: Any + Send + Sync + Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    F: 'static + Sync + Send + Clone + Fn(&A, &A) -> A
+>(
+    defs: &[Deferred<Col>],
+    f: F
+) -> Deferred<Option<A>> {
+    let f2 = f.clone();
+    let partials = batch_apply(defs, move |_idx, vs| {
+        vs.stream().into_iter().fold(None, |acc: Option<A>, x| match acc {
+            Some(a) => Some(f(&a, &x)),
+            None    => Some(x)
+        })
+    });
+    tree_reduce(&partials, move |left, right| match (left, right) {
+        (Some(l), Some(r)) => Some(f2(l, r)),
+        (l, r) => l.clone().or_else(|| r.clone())
+    }).unwrap_or_else(|| Deferred::lift(None, None))
+}
+
+pub fn digest<
+    A: Into<f64>,
+    Col: Any + Sync + Send + Clone + Stream<A>
+>(
+    defs: &[Deferred<Col>],
+    compression: f64
+) -> Deferred<TDigest> {
+    let digests = batch_apply(defs, move |_idx, vs| {
+        let mut digest = TDigest::new(compression);
+        for v in vs.stream() {
+            digest.add(v.into());
+        }
+        digest
+    });
+    tree_reduce(&digests, |left, right| left.merge(right))
+        .unwrap_or_else(|| Deferred::lift(TDigest::new(compression), None))
+}
+
+pub fn bloom<
+    A,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    K: Hash,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K
+>(
+    defs: &[Deferred<Col>],
+    key: F,
+    n_bits: usize,
+    n_hashes: usize
+) -> Deferred<BloomFilter> {
+    let filters = batch_apply(defs, move |_idx, vs| {
+        let mut bf = BloomFilter::new(n_bits, n_hashes);
+        for v in vs.stream() {
+            bf.insert(&key(&v));
+        }
+        bf
+    });
+    tree_reduce(&filters, |left, right| left.union(right))
+        .unwrap_or_else(|| Deferred::lift(BloomFilter::new(n_bits, n_hashes), None))
+}
+
+pub fn filter_by_bloom<
+    A,
+    Col: Any + Sync + Send + Clone + Accumulator<A> + Stream<A>,
+    K: Hash,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K
+>(
+    defs: &[Deferred<Col>],
+    bf: &Deferred<BloomFilter>,
+    key: F
+) -> Vec<Deferred<Col>>
+        where Col::VW: ValueWriter<A,Out=Col> {
+    defs.iter().map(|d| {
+        let key = key.clone();
+        d.join(bf, move |vs, bf| {
+            let mut out = vs.writer();
+            for v in vs.stream() {
+                if bf.contains(&key(&v)) {
+                    out.add(v);
+                }
+            }
+            out.finish()
+        })
+    }).collect()
+}
+
+pub fn map_with_broadcast<
+    A,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    B: Any + Send + Sync + Clone,
+    C: Any + Send + Sync + Clone,
+    F: 'static + Sync + Send + Clone + Fn(&C, &A) -> B,
+    Acc: 'static + Accumulator<B>
+>(
+    defs: &[Deferred<Col>],
+    ctx: &Deferred<C>,
+    acc: Acc,
+    f: F
+) -> Vec<Deferred<<<Acc as Accumulator<B>>::VW as ValueWriter<B>>::Out>> {
+    let acc = Arc::new(acc);
+    defs.iter().map(|d| {
+        let f = f.clone();
+        let acc = acc.clone();
+        d.join(ctx, move |vs, c| {
+            let mut out = acc.writer();
+            for v in vs.stream() {
+                out.add(f(c, &v));
+            }
+            out.finish()
+        })
+    }).collect()
+}
+
+/// Pairs every item with its position in the collection as a whole.  The length of each
+/// partition is computed first; each partition's offset is the total length of the
+/// partitions before it, so empty partitions take up no indices.
+pub fn zip_with_index<
+    A: Any + Send + Sync + Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    Acc: 'static + Accumulator<(usize, A)>
+>(
+    defs: &[Deferred<Col>],
+    acc: Acc
+) -> Vec<Deferred<<<Acc as Accumulator<(usize, A)>>::VW as ValueWriter<(usize, A)>>::Out>> {
+    let lengths = batch_apply(defs, |_idx, vs| vs.stream().into_iter().count());
+    let offsets = collect_deferreds(&lengths).apply(|lens| {
+        let mut offsets = Vec::with_capacity(lens.len());
+        let mut total = 0;
+        for len in lens {
+            offsets.push(total);
+            total += len;
+        }
+        offsets
+    });
+
+    let acc = Arc::new(acc);
+    defs.iter().enumerate().map(|(idx, d)| {
+        let acc = acc.clone();
+        d.join(&offsets, move |vs, offsets| {
+            let mut out = acc.writer();
+            for (i, v) in vs.stream().into_iter().enumerate() {
+                out.add((offsets[idx] + i, v));
+            }
+            out.finish()
+        })
+    }).collect()
+}
+
+/// Computes the running value of `f` across the whole collection, in partition order.  
+/// Each partition is first folded from `init` into its total; the totals are combined in 
+/// order into the carry entering each partition, and each partition then re-scans its 
+/// items from `init`, combining every running value with its carry.  `init` must be the 
+/// identity of `combine`.
+pub fn scan_global<
+    A,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    B: Any + Send + Sync + Clone,
+    F: 'static + Sync + Send + Clone + Fn(&B, &A) -> B,
+    R: 'static + Sync + Send + Clone + Fn(&B, &B) -> B,
+    Acc: 'static + Accumulator<B>
+>(
+    defs: &[Deferred<Col>],
+    init: B,
+    f: F,
+    combine: R,
+    acc: Acc
+) -> Vec<Deferred<<<Acc as Accumulator<B>>::VW as ValueWriter<B>>::Out>> {
+    let i2 = init.clone();
+    let f2 = f.clone();
+    let totals = batch_apply(defs, move |_idx, vs| {
+        let mut total = i2.clone();
+        for v in vs.stream() {
+            total = f2(&total, &v);
+        }
+        total
+    });
+
+    let acc = Arc::new(acc);
+    let mut carry = Deferred::lift(init.clone(), None);
+    let mut out = Vec::with_capacity(defs.len());
+    for (d, total) in defs.iter().zip(totals.iter()) {
+        let (init, f, merge, acc) = (init.clone(), f.clone(), combine.clone(), acc.clone());
+        out.push(d.join(&carry, move |vs, c| {
+            let mut writer = acc.writer();
+            let mut running = init.clone();
+            for v in vs.stream() {
+                running = f(&running, &v);
+                writer.add(merge(c, &running));
+            }
+            writer.finish()
+        }));
+        let combine = combine.clone();
+        carry = carry.join(total, move |c, t| combine(c, t));
+    }
+    out
+}
+
+pub fn first_by_key<
+    A: Any + Send + Sync + Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    K: Hash + Eq,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K,
+    Acc: 'static + Accumulator<A>
+>(
+    defs: &[Deferred<Col>],
+    key: F,
+    acc: Acc
+) -> Vec<Deferred<<<Acc as Accumulator<A>>::VW as ValueWriter<A>>::Out>> {
+    batch_apply(defs, move |_idx, vs| {
+        let mut seen = HashSet::new();
+        let mut out = acc.writer();
+        for v in vs.stream() {
+            if seen.insert(key(&v)) {
+                out.add(v);
+            }
+        }
+        out.finish()
+    })
+}
+
+pub fn window_global<
+    A: Any + Send + Sync + Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    Acc: 'static + Accumulator<Vec<A>>
+>(
+    defs: &[Deferred<Col>],
+    size: usize,
+    acc: Acc
+) -> Vec<Deferred<<<Acc as Accumulator<Vec<A>>>::VW as ValueWriter<Vec<A>>>::Out>> {
+    let parts = batch_apply(defs, |_idx, vs| vs.stream().into_iter().collect::<Vec<_>>());
+
+    // Walking backwards, collect the first `size - 1` items following each partition,
+    // which may span several short partitions.
+    let mut tails = Vec::with_capacity(parts.len());
+    let mut tail: Deferred<Vec<A>> = Deferred::lift(Vec::new(), None);
+    for part in parts.iter().rev() {
+        tails.push(tail.clone());
+        tail = part.join(&tail, move |p, t| {
+            p.iter().chain(t.iter()).take(size - 1).cloned().collect()
+        });
+    }
+    tails.reverse();
+
+    let acc = Arc::new(acc);
+    parts.iter().zip(tails.iter()).map(|(part, tail)| {
+        let acc = acc.clone();
+        part.join(tail, move |p, t| {
+            let mut out = acc.writer();
+            let all: Vec<_> = p.iter().chain(t.iter()).cloned().collect();
+            for start in 0..p.len() {
+                if start + size <= all.len() {
+                    out.add(all[start..start + size].to_vec());
+                }
+            }
+            out.finish()
+        })
+    }).collect()
+}
+
+/// Splits the collection, in global partition order, at the first item failing `f`.  
+/// When `keep_prefix` is true, keeps the items before it; otherwise keeps that item and
+/// everything after it.  Each partition depends on whether all partitions before it 
+/// passed `f`.
+pub fn prefix_while<
+    A: Any + Send + Sync + Clone,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> bool,
+    Acc: 'static + Accumulator<A>
+>(
+    defs: &[Deferred<Col>],
+    f: F,
+    acc: Acc,
+    keep_prefix: bool
+) -> Vec<Deferred<<<Acc as Accumulator<A>>::VW as ValueWriter<A>>::Out>> {
+    // Whether every item before each partition passed the predicate
+    let mut states = Vec::with_capacity(defs.len());
+    let mut state = Deferred::lift(true, None);
+    for d in defs {
+        states.push(state.clone());
+        let f = f.clone();
+        state = d.join(&state, move |vs, passing| {
+            *passing && vs.stream().into_iter().all(|x| f(&x))
+        });
+    }
+
+    let acc = Arc::new(acc);
+    defs.iter().zip(states.iter()).map(|(d, state)| {
+        let acc = acc.clone();
+        let f = f.clone();
+        d.join(state, move |vs, passing| {
+            let mut out = acc.writer();
+            let mut prefix = *passing;
+            for x in vs.stream() {
+                prefix = prefix && f(&x);
+                if prefix == keep_prefix {
+                    out.add(x);
+                } else if keep_prefix {
+                    break
+                }
+            }
+            out.finish()
+        })
+    }).collect()
+}
+
+/// Number of keys each partition samples per output partition when estimating ranges
+const SAMPLES_PER_PARTITION: usize = 20;
+
+/// Estimates `partitions - 1` boundaries splitting the keys into ranges of roughly 
+/// equal size.  Each partition contributes an evenly spaced sample of its sorted keys,
+/// weighted by the partition size.
+pub fn sample_bounds<
+    A,
+    Col: Any + Sync + Send + Clone + Stream<A>,
+    K: Any + Sync + Send + Clone + Ord,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K
+>(
+    defs: &[Deferred<Col>],
+    key: F,
+    partitions: usize
+) -> Deferred<Vec<K>> {
+    let n_samples = SAMPLES_PER_PARTITION * partitions;
+    let samples = batch_apply(defs, move |_idx, vs| {
+        let mut keys: Vec<_> = vs.stream().into_iter().map(|v| key(&v)).collect();
+        keys.sort();
+        let step = (keys.len() as f64 / n_samples as f64).max(1.);
+        let n = (keys.len() as f64 / step) as usize;
+        (0..n).map(|i| (keys[(i as f64 * step) as usize].clone(), step)).collect::<Vec<_>>()
+    });
+
+    let all = tree_reduce(&samples, |left, right| {
+        let mut out = left.clone();
+        out.extend_from_slice(right);
+        out
+    }).unwrap_or_else(|| Deferred::lift(Vec::new(), None));
+
+    all.apply(move |samples| {
+        let mut samples = samples.clone();
+        samples.sort_by(|a, b| a.0.cmp(&b.0));
+        let total: f64 = samples.iter().map(|s| s.1).sum();
+        let mut bounds = Vec::with_capacity(partitions - 1);
+        let mut seen = 0.;
+        for (k, w) in samples {
+            seen += w;
+            while bounds.len() < partitions - 1 && 
+                    seen >= total * (bounds.len() + 1) as f64 / partitions as f64 {
+                bounds.push(k.clone());
+            }
+        }
+        bounds
+    })
+}
+
+/// Scatters items into `partitions` partitions, choosing each item's target with the
+/// help of a shared context computed by another Deferred.
+fn scatter_with<
+    Col: Any + Sync + Send + Clone + Accumulator<A> + Stream<A>,
+    A: Clone,
+    C: Any + Sync + Send + Clone,
+    F: 'static + Sync + Send + Clone + Fn(&C, &A) -> usize
+>(
+    defs: &[Deferred<Col>], 
+    ctx: &Deferred<C>,
+    partitions: usize, 
+    target: F
+) -> Vec<Deferred<Col>> 
+        where Col::VW: ValueWriter<A,Out=Col> {
+
+    let stage1: Vec<_> = defs.iter().map(|d| {
+        let target = target.clone();
+        d.join(ctx, move |vs, c| {
+            let mut parts: Vec<_> = (0..partitions).map(|_| vs.writer()).collect();
+            for x in vs.stream() {
+                let p = target(c, &x).min(partitions - 1);
+                parts[p].add(x);
+            }
+            parts.into_iter().map(|x| x.finish()).collect::<Vec<_>>()
+        })
+    }).collect();
+
+    let mut new_chunks = Vec::with_capacity(partitions);
+    for idx in 0usize..partitions {
+        let group: Vec<_> = stage1.iter()
+            .map(|s| s.apply(move |parts| parts[idx].copy()))
+            .collect();
+        if let Some(d) = concat(&group) {
+            new_chunks.push(d);
+        }
+    }
+    new_chunks
+}
+
+/// Partitions items into `bounds.len() + 1` contiguous key ranges, such that every key
+/// in partition `i` is less than every key in partition `i + 1`.
+pub fn range_partition<
+    Col: Any + Sync + Send + Clone + Accumulator<A> + Stream<A>,
+    A: Clone,
+    K: Any + Sync + Send + Clone + Ord,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K
+>(
+    defs: &[Deferred<Col>], 
+    bounds: &Deferred<Vec<K>>,
+    partitions: usize, 
+    key: F
+) -> Vec<Deferred<Col>> 
+        where Col::VW: ValueWriter<A,Out=Col> {
+    scatter_with(defs, bounds, partitions, move |bs, x| {
+        let k = key(x);
+        bs.partition_point(|b| *b <= k)
+    })
+}
+
+/// Assigns whole keys to partitions, balancing the number of items per partition: keys
+/// are placed largest first onto the partition with the fewest items so far.
+pub fn balance_keys<K: Clone + Hash + Eq>(
+    counts: &HashMap<K, usize>, 
+    partitions: usize
+) -> HashMap<K, usize> {
+    let mut keys: Vec<_> = counts.iter().collect();
+    keys.sort_by_key(|&(k, c)| (::std::cmp::Reverse(*c), hash_key(k)));
+    let mut loads = vec![0usize; partitions];
+    let mut assignment = HashMap::with_capacity(keys.len());
+    for (k, c) in keys {
+        let (p, _) = loads.iter().enumerate()
+            .min_by_key(|&(idx, load)| (*load, idx))
+            .unwrap();
+        loads[p] += *c;
+        assignment.insert(k.clone(), p);
+    }
+    assignment
+}
+
+/// Re-partitions items such that all items sharing a key land in the same partition,
+/// while balancing the partition sizes with `balance_keys`.
+pub fn rebalance_by_key<
+    Col: Any + Sync + Send + Clone + Accumulator<A> + Stream<A>,
+    A: Clone,
+    K: Any + Sync + Send + Clone + Hash + Eq,
+    F: 'static + Sync + Send + Clone + Fn(&A) -> K
+>(
+    defs: &[Deferred<Col>], 
+    partitions: usize, 
+    key: F
+) -> Vec<Deferred<Col>> 
+        where Col::VW: ValueWriter<A,Out=Col> {
+    let assignment = count_buckets(defs, key.clone())
+        .apply(move |counts| balance_keys(counts, partitions));
+    scatter_with(defs, &assignment, partitions, move |a, x| a[&key(x)])
+}
+
+/// Splits the globally ordered sequence of items into `partitions` contiguous chunks of
+/// near equal size.  Since chunk boundaries depend on the sizes of every partition, each
+/// partition first needs all of the partition lengths.
+pub fn repartition_ordered<
+    Col: Any + Sync + Send + Clone + Accumulator<A> + Stream<A>,
+    A: Clone
+>(
+    defs: &[Deferred<Col>], 
+    partitions: usize
+) -> Vec<Deferred<Col>> 
+        where Col::VW: ValueWriter<A,Out=Col> {
+
+    let lengths = batch_apply(defs, |_idx, vs| vec![vs.stream().into_iter().count()]);
+    let lengths = match tree_reduce(&lengths, |x, y| {
+        let mut out = x.clone();
+        out.extend_from_slice(y);
+        out
+    }) {
+        Some(l) => l,
+        None => return Vec::new()
+    };
+
+    let stage1: Vec<_> = defs.iter().enumerate().map(|(i, d)| {
+        d.join(&lengths, move |vs, lens| {
+            let offset: usize = lens[..i].iter().sum();
+            let total: usize = lens.iter().sum();
+            let mut parts: Vec<_> = (0..partitions).map(|_| vs.writer()).collect();
+            for (k, x) in vs.stream().into_iter().enumerate() {
+                // Chunk j holds global positions [j * total / n, (j + 1) * total / n)
+                let j = (partitions * (offset + k + 1) - 1) / total;
+                parts[j].add(x);
+            }
+            parts.into_iter().map(|x| x.finish()).collect::<Vec<_>>()
+        })
+    }).collect();
+
+    let mut new_chunks = Vec::with_capacity(partitions);
+    for idx in 0usize..partitions {
+        let group: Vec<_> = stage1.iter()
+            .map(|s| s.apply(move |parts| parts[idx].copy()))
+            .collect();
+        if let Some(d) = concat(&group) {
+            new_chunks.push(d);
+        }
+    }
+    new_chunks
+}
+
+/// Merges whole partitions into `partitions` new ones of near equal size.  Partitions are
+/// assigned largest first, each to the new partition with the fewest items so far; each
+/// new partition concatenates its assigned partitions in their original order.  Since the
+/// assignment depends on the sizes of every partition, each new partition first needs all
+/// of the partition lengths.
+pub fn coalesce_balanced<
+    Col: Any + Sync + Send + Clone + Accumulator<A> + Stream<A>,
+    A: Clone
+>(
+    defs: &[Deferred<Col>], 
+    partitions: usize
+) -> Vec<Deferred<Col>> 
+        where Col::VW: ValueWriter<A,Out=Col> {
+
+    let lengths = batch_apply(defs, |_idx, vs| vs.stream().into_iter().count());
+    let assignment = collect_deferreds(&lengths).apply(move |lens| {
+        let mut order: Vec<_> = (0..lens.len()).collect();
+        order.sort_by_key(|&i| ::std::cmp::Reverse(lens[i]));
+        let mut loads = vec![0usize; partitions];
+        let mut targets = vec![0usize; lens.len()];
+        for i in order {
+            let target = (0..partitions).min_by_key(|&j| loads[j]).unwrap();
+            loads[target] += lens[i];
+            targets[i] = target;
+        }
+        targets
+    });
+
+    let mut new_chunks = Vec::with_capacity(partitions);
+    for target in 0usize..partitions {
+        let group: Vec<_> = defs.iter().enumerate().map(|(i, d)| {
+            d.join(&assignment, move |vs, targets| {
+                if targets[i] == target { vs.copy() } else { vs.writer().finish() }
+            })
+        }).collect();
+        if let Some(d) = concat(&group) {
+            new_chunks.push(d);
+        }
+    }
+    new_chunks
+}
+
+pub fn filter_on_keys<
+    A: Any + Send + Sync + Clone,
+    Col1: Any + Sync + Send + Clone + Stream<(K, A)>,
+    Col2: Any + Sync + Send + Clone + Stream<K>,
+    K: Any + Send + Sync + Clone + Hash + Eq,
+    Acc: 'static + Accumulator<A>
+>(
+    d1: &Deferred<Col1>, 
+    d2: &Deferred<Col2>, 
+    acc: Acc,
+    keep_matched: bool
+) -> Deferred<<<Acc as Accumulator<A>>::VW as ValueWriter<A>>::Out> {
+
+    d1.join(d2, move |left, right| {
+        let keys: HashSet<K> = right.stream().into_iter().collect();
+        let mut ret = acc.writer();
+        for (k, lv) in left.stream() {
+            if keys.contains(&k) == keep_matched {
+                ret.add(lv);
+            }
+        }
+        ret.finish()
+    })
+}
+
+pub fn sink_single<
+    Col: Any + Sync + Send + Clone + Stream<String>
+>(
+    defs: &[Deferred<Col>],
+    path: &str,
+    separator: Option<String>
+) -> Deferred<usize> {
+    let path = path.to_owned();
+    let p = path.clone();
+    let created = Deferred::lift((), None).apply(move |_| {
+        if let Some(parent) = Path::new(&p).parent() {
+            fs::create_dir_all(parent)
+                .expect("Welp, something went terribly wrong when creating directory");
+        }
+        fs::File::create(&p).expect("Issues opening file!");
+        0usize
+    });
+
+    // Chain the partitions so each is appended only after the previous one is written
+    defs.iter().enumerate().fold(created, |written, (idx, d)| {
+        let path = path.clone();
+        let separator = separator.clone();
+        d.join(&written, move |vs, total| {
+            let file = fs::OpenOptions::new().append(true).open(&path)
+                .expect("Issues opening file!");
+            let mut bw = BufWriter::new(file);
+            if let Some(sep) = separator.as_ref().filter(|_| idx > 0) {
+                bw.write_all(sep.as_bytes()).expect("Error writing out line");
+                bw.write_all(b"\n").expect("Error writing out line");
+            }
+            let mut size = 0usize;
+            for line in vs.stream() {
+                bw.write_all(line.as_bytes()).expect("Error writing out line");
+                bw.write_all(b"\n").expect("Error writing out line");
+                size += 1;
+            }
+            bw.flush().expect("Error writing out line");
+            total + size
+        })
+    }).named("sink_single")
+}
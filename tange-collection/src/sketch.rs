@@ -0,0 +1,245 @@
+//! Mergeable summaries used for approximate aggregations over collections.
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash,Hasher};
+
+/// A t-digest, which summarizes a stream of values into a small number of weighted
+/// centroids.  Centroids are kept small near the tails of the distribution and are allowed
+/// to grow near the median, making quantile estimates most accurate at the extremes.
+///
+/// Digests can be merged, which lets each partition build its own digest before combining
+/// them in a `tree_reduce`.
+/// ```rust
+///   extern crate tange_collection;
+///   use tange_collection::sketch::TDigest;
+///
+///   let mut digest = TDigest::new(100.);
+///   for i in 0..100 {
+///       digest.add(i as f64);
+///   }
+///   assert!((digest.quantile(0.5) - 49.5).abs() < 1.);
+/// ```
+#[derive(Clone,Debug)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<(f64, f64)>,
+    buffer: Vec<f64>,
+    min: f64,
+    max: f64
+}
+
+impl TDigest {
+
+    /// Creates an empty TDigest.  Higher compression values yield more centroids and
+    /// better accuracy; 100 is a reasonable default.
+    pub fn new(compression: f64) -> Self {
+        TDigest {
+            compression,
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY
+        }
+    }
+
+    /// Adds a value to the digest
+    pub fn add(&mut self, x: f64) {
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        self.buffer.push(x);
+        if self.buffer.len() as f64 >= 10. * self.compression {
+            self.flush();
+        }
+    }
+
+    /// Total number of values added to the digest
+    pub fn count(&self) -> f64 {
+        self.centroids.iter().map(|c| c.1).sum::<f64>() + self.buffer.len() as f64
+    }
+
+    /// Combines two digests into a new digest summarizing both.
+    pub fn merge(&self, other: &TDigest) -> TDigest {
+        let mut out = self.clone();
+        out.min = out.min.min(other.min);
+        out.max = out.max.max(other.max);
+        let mut points: Vec<_> = out.buffer.drain(..).map(|x| (x, 1.))
+            .chain(other.buffer.iter().map(|x| (*x, 1.)))
+            .chain(other.centroids.iter().cloned())
+            .collect();
+        points.append(&mut out.centroids);
+        out.compress(points);
+        out
+    }
+
+    /// Estimates the value at quantile `q`, which should be within [0, 1].  Returns NaN
+    /// for an empty digest.
+    pub fn quantile(&self, q: f64) -> f64 {
+        if !self.buffer.is_empty() {
+            let mut flushed = self.clone();
+            flushed.flush();
+            return flushed.quantile(q)
+        }
+
+        if self.centroids.is_empty() {
+            return f64::NAN
+        }
+
+        let total = self.count();
+        let target = q.clamp(0., 1.) * total;
+
+        // Walk the centroid centers, interpolating between the neighbors of the target
+        let mut prev = (self.min, 0f64);
+        let mut cum = 0f64;
+        for &(mean, weight) in self.centroids.iter() {
+            let center = cum + weight / 2.;
+            if target < center {
+                return interpolate(prev, (mean, center), target)
+            }
+            prev = (mean, center);
+            cum += weight;
+        }
+        interpolate(prev, (self.max, total), target)
+    }
+
+    fn flush(&mut self) {
+        let mut points: Vec<_> = self.buffer.drain(..).map(|x| (x, 1.)).collect();
+        points.append(&mut self.centroids);
+        self.compress(points);
+    }
+
+    // Sorts the points and greedily merges neighbors while they fit under the size bound
+    // for their position in the distribution.
+    fn compress(&mut self, mut points: Vec<(f64, f64)>) {
+        if points.is_empty() {
+            return
+        }
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        let total: f64 = points.iter().map(|p| p.1).sum();
+
+        let mut centroids = Vec::new();
+        let mut so_far = 0f64;
+        let mut cur = points[0];
+        for &(mean, weight) in points[1..].iter() {
+            let proposed = cur.1 + weight;
+            let q = (so_far + proposed / 2.) / total;
+            let limit = 4. * total * q * (1. - q) / self.compression;
+            if proposed <= limit {
+                cur = ((cur.0 * cur.1 + mean * weight) / proposed, proposed);
+            } else {
+                so_far += cur.1;
+                centroids.push(cur);
+                cur = (mean, weight);
+            }
+        }
+        centroids.push(cur);
+        self.centroids = centroids;
+    }
+}
+
+fn interpolate(left: (f64, f64), right: (f64, f64), target: f64) -> f64 {
+    if right.1 <= left.1 {
+        right.0
+    } else {
+        left.0 + (right.0 - left.0) * (target - left.1) / (right.1 - left.1)
+    }
+}
+
+/// A Bloom filter for approximate set membership: `contains` never returns false for an
+/// inserted item, but may return true for items which were never inserted.  Filters of
+/// the same dimensions can be unioned, which lets each partition build its own filter.
+/// ```rust
+///   extern crate tange_collection;
+///   use tange_collection::sketch::BloomFilter;
+///
+///   let mut bf = BloomFilter::new(1024, 3);
+///   bf.insert(&"hello");
+///   assert!(bf.contains(&"hello"));
+/// ```
+#[derive(Clone,Debug)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    n_bits: usize,
+    n_hashes: usize
+}
+
+impl BloomFilter {
+
+    /// Creates an empty BloomFilter with `n_bits` bits, setting `n_hashes` bits per item.
+    pub fn new(n_bits: usize, n_hashes: usize) -> Self {
+        assert!(n_bits > 0 && n_hashes > 0, "BloomFilter requires at least one bit and hash");
+        BloomFilter {
+            bits: vec![0u64; n_bits.div_ceil(64)],
+            n_bits,
+            n_hashes
+        }
+    }
+
+    /// Adds an item to the filter
+    pub fn insert<K: Hash + ?Sized>(&mut self, k: &K) {
+        for idx in self.indices(k) {
+            self.bits[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+
+    /// Tests whether the item might have been inserted into the filter.
+    pub fn contains<K: Hash + ?Sized>(&self, k: &K) -> bool {
+        self.indices(k).into_iter().all(|idx| self.bits[idx / 64] & (1 << (idx % 64)) != 0)
+    }
+
+    /// Creates a new filter containing the items of both filters.  Panics if the filters
+    /// were created with different sizes.
+    pub fn union(&self, other: &BloomFilter) -> BloomFilter {
+        assert!(self.n_bits == other.n_bits && self.n_hashes == other.n_hashes,
+                "Can only union BloomFilters of the same dimensions");
+        let bits = self.bits.iter().zip(other.bits.iter()).map(|(l, r)| l | r).collect();
+        BloomFilter { bits, n_bits: self.n_bits, n_hashes: self.n_hashes }
+    }
+
+    // Double hashing: derives all `n_hashes` bit positions from a single 64 bit hash
+    fn indices<K: Hash + ?Sized>(&self, k: &K) -> Vec<usize> {
+        let mut hasher = DefaultHasher::new();
+        k.hash(&mut hasher);
+        let h = hasher.finish();
+        let (h1, h2) = (h & 0xffff_ffff, (h >> 32) | 1);
+        (0..self.n_hashes as u64).map(|i| {
+            (h1.wrapping_add(i.wrapping_mul(h2)) % self.n_bits as u64) as usize
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod test_sketch {
+    use super::*;
+
+    #[test]
+    fn test_tdigest_merge() {
+        let mut left = TDigest::new(100.);
+        let mut right = TDigest::new(100.);
+        for i in 0..5000 {
+            left.add(i as f64);
+            right.add((i + 5000) as f64);
+        }
+        let merged = left.merge(&right);
+        assert_eq!(merged.count(), 10000.);
+        assert!((merged.quantile(0.5) - 4999.5).abs() < 50.);
+        assert!((merged.quantile(0.99) - 9899.).abs() < 10.);
+        assert_eq!(merged.quantile(0.), 0.);
+        assert_eq!(merged.quantile(1.), 9999.);
+    }
+
+    #[test]
+    fn test_tdigest_empty() {
+        assert!(TDigest::new(100.).quantile(0.5).is_nan());
+    }
+
+    #[test]
+    fn test_bloom_union() {
+        let mut left = BloomFilter::new(1000, 3);
+        let mut right = BloomFilter::new(1000, 3);
+        left.insert(&1usize);
+        right.insert(&2usize);
+        let both = left.union(&right);
+        assert!(both.contains(&1usize));
+        assert!(both.contains(&2usize));
+    }
+}
@@ -1,11 +1,17 @@
 //! Utilities for creating collections
+extern crate serde;
+
 use std::io::prelude::*;
 use std::io::{SeekFrom,BufReader,Error};
 use std::fs::{File,metadata};
+use std::any::Any;
+use std::sync::{Arc,Mutex};
 
+use self::serde::{Serialize,Deserialize};
 use tange::deferred::{Deferred, batch_apply};
 
 use collection::memory::MemoryCollection;
+use interfaces::{Accumulator,Disk,FileStore,Stream};
 
 #[derive(Clone)]
 struct Chunk { path: String, start: u64, end: u64 }
@@ -65,3 +71,123 @@ fn read(_idx: usize, chunk: &Chunk) -> Vec<String> {
     lines.shrink_to_fit();
     lines
 }
+
+/// Maps a timestamp to the start of its tumbling window of `window` units, for use as
+/// the bucketing function for `tumbling_reduce`.
+/// ```rust
+///   extern crate tange_collection;
+///   use tange_collection::utils::time_bucket;
+///
+///   assert_eq!(time_bucket(0, 10), 0);
+///   assert_eq!(time_bucket(19, 10), 10);
+///   assert_eq!(time_bucket(20, 10), 20);
+/// ```
+pub fn time_bucket(ts: u64, window: u64) -> u64 {
+    assert!(window > 0, "time_bucket requires a non-zero window");
+    ts - ts % window
+}
+
+/// Adds spilling to disk for large intermediate values.  This is an extension trait in
+/// tange-collection rather than a method on `Deferred` because spilling reuses the
+/// collection crate's serde and bincode based `FileStore`; tange-core has no
+/// serialization dependencies or serde feature.
+pub trait Spill<A> {
+    /// Returns a Deferred which, at compute time, serializes the value to a temporary file
+    /// within `dir` and reads it back in the downstream task.  Between the two tasks only
+    /// the file handle is held in memory, trading serialization and IO for memory.  The
+    /// file is deleted once the value has been read back.
+    /// ```rust
+    ///   extern crate tange;
+    ///   extern crate tange_collection;
+    ///   use tange::deferred::Deferred;
+    ///   use tange::scheduler::GreedyScheduler;
+    ///   use tange_collection::utils::Spill;
+    ///
+    ///   let big = Deferred::lift((0..1000usize).collect::<Vec<_>>(), None);
+    ///   let total = big.spill("/tmp").apply(|vs| vs.iter().sum::<usize>());
+    ///   assert_eq!(total.run(&GreedyScheduler::new()), Some(499500));
+    /// ```
+    fn spill(&self, dir: &str) -> Deferred<A>;
+}
+
+impl <A: Any + Send + Sync + Clone + Serialize + for<'de> Deserialize<'de>> Spill<A> for Deferred<A> {
+    fn spill(&self, dir: &str) -> Deferred<A> {
+        let disk = Disk::from_str(dir);
+        self.apply(move |a| disk.write_vec(vec![a.clone()]))
+            .apply(|fs: &Arc<FileStore<A>>| {
+                fs.stream().into_iter().next().expect("Spilled value is missing!")
+            })
+    }
+}
+
+/// Collects side metrics, such as the number of malformed records, while a collection is
+/// computed.  Each partition updates its own partial value, starting from `zero`, which is
+/// merged into the shared value with `merge` once the partition finishes.  Values are
+/// added every time a partition is computed, including when a collection is run more
+/// than once.
+/// ```rust
+///   extern crate tange_collection;
+///   use tange_collection::utils::SideAccumulator;
+///
+///   let acc = SideAccumulator::new(0usize, |total, partial| *total += partial);
+///   acc.merge(3);
+///   acc.merge(4);
+///   assert_eq!(acc.value(), 7);
+/// ```
+#[derive(Clone)]
+pub struct SideAccumulator<T> {
+    zero: T,
+    value: Arc<Mutex<T>>,
+    merge: MergeFn<T>
+}
+
+type MergeFn<T> = Arc<dyn Fn(&mut T, T) + Send + Sync>;
+
+impl <T: Clone> SideAccumulator<T> {
+
+    /// Creates a new SideAccumulator using `zero` for both its initial value and the 
+    /// initial value of each partial.
+    pub fn new<F: 'static + Send + Sync + Fn(&mut T, T)>(zero: T, merge: F) -> Self {
+        SideAccumulator { 
+            value: Arc::new(Mutex::new(zero.clone())), 
+            zero, 
+            merge: Arc::new(merge) 
+        }
+    }
+
+    /// Returns a fresh partial value
+    pub fn zero(&self) -> T {
+        self.zero.clone()
+    }
+
+    /// Merges a partial value into the shared value
+    pub fn merge(&self, partial: T) {
+        let mut value = self.value.lock().unwrap();
+        (self.merge)(&mut value, partial);
+    }
+
+    /// Returns the current shared value
+    pub fn value(&self) -> T {
+        self.value.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod test_utils {
+    extern crate uuid;
+
+    use super::*;
+    use self::uuid::Uuid;
+    use tange::scheduler::LeveledScheduler;
+
+    #[test]
+    fn test_spill() {
+        let dir = ::std::env::temp_dir().join(format!("tange-test-spill-{}", Uuid::new_v4()));
+        let dir = dir.to_str().unwrap();
+        let value: Vec<_> = (0..100usize).map(|i| (i, format!("item-{}", i))).collect();
+        let spilled = Deferred::lift(value.clone(), None).spill(dir);
+        assert_eq!(spilled.run(&LeveledScheduler::new()), Some(value));
+        assert_eq!(::std::fs::read_dir(dir).unwrap().count(), 0);
+        ::std::fs::remove_dir_all(dir).unwrap();
+    }
+}
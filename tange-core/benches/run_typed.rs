@@ -0,0 +1,32 @@
+//! Compares `Deferred::run`, which copies the result out of the scheduler's storage,
+//! with `Deferred::run_typed`, which moves it out when it isn't shared.
+//!
+//! Run with `cargo bench --bench run_typed`.
+extern crate tange;
+
+use std::time::{Duration, Instant};
+
+use tange::deferred::Deferred;
+use tange::scheduler::GreedyScheduler;
+
+const ITERS: u32 = 50;
+
+fn time<F: FnMut() -> Option<Vec<u64>>>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERS {
+        assert!(f().is_some());
+    }
+    start.elapsed() / ITERS
+}
+
+fn main() {
+    let s = GreedyScheduler::new();
+    for &len in &[1_000usize, 100_000, 10_000_000] {
+        let v = Deferred::lift(len, None).apply(|n| (0..*n as u64).collect::<Vec<_>>());
+        assert_eq!(v.run(&s), v.run_typed(&s));
+
+        let run = time(|| v.run(&s));
+        let run_typed = time(|| v.run_typed(&s));
+        println!("len {:>10}: run {:>12?}  run_typed {:>12?}", len, run, run_typed);
+    }
+}
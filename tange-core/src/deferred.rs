@@ -3,8 +3,10 @@
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::any::Any;
+use std::thread;
+use std::time::Duration;
 
-use task::{DynFn,DynFn2,BASS};
+use task::{DynFn,DynFn2,BASS,downcast_shared};
 use graph::*;
 use scheduler::Scheduler;
 
@@ -16,6 +18,22 @@ impl <A: Any + Send + Sync + Clone> Input for Lift<A> {
     }
 }
 
+struct LiftShared<A>(Arc<A>);
+
+impl <A: Any + Send + Sync> Input for LiftShared<A> {
+    fn read(&self) -> BASS {
+        Box::new(self.0.clone())
+    }
+}
+
+struct LiftLazy<F>(F);
+
+impl <A: Any + Send + Sync, F: Send + Sync + Fn() -> A> Input for LiftLazy<F> {
+    fn read(&self) -> BASS {
+        Box::new((self.0)())
+    }
+}
+
 /// A `Deferred` is the core struct defining how computations are composed
 /// The type parameter indicates the type of data contained within the `Deferred`
 #[derive(Clone)]
@@ -53,6 +71,28 @@ impl <A: Any + Send + Sync> Deferred<A> {
 
     }
 
+    /// Applies two functions in sequence within a single task, returning a new Deferred.
+    /// This is equivalent to `apply(f).apply(g)`, but adds one node to the graph rather
+    /// than two and never stores the intermediate value.
+    ///
+    /// ```
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let def = Deferred::lift(vec![1u8, 2, 3, 4], None);
+    /// let size = def.apply2(|v| v.len(), |n| n * 10);
+    /// assert_eq!(size.run(&GreedyScheduler::new()), Some(40usize));
+    /// ```
+    ///
+    pub fn apply2<
+        B: Any + Send + Sync, 
+        C: Any + Send + Sync,
+        F: Send + Sync + 'static + Fn(&A) -> B,
+        G: Send + Sync + 'static + Fn(&B) -> C
+    >(&self, f: F, g: G) -> Deferred<C> {
+        self.apply(move |a| g(&f(a)))
+    }
+
     /// Joins two Deferred objects with a function, creating a new Deferred object.
     ///
     /// ```
@@ -78,6 +118,126 @@ impl <A: Any + Send + Sync> Deferred<A> {
         }
 
     }
+
+    /// Returns the number of unique nodes in the dependency graph of this Deferred,
+    /// including itself.
+    ///
+    /// ```
+    /// use tange::deferred::Deferred;
+    ///
+    /// let a = Deferred::lift(1usize, "a".into());
+    /// let b = a.apply(|x| x + 1);
+    /// let c = a.join(&b, |x, y| x + y);
+    /// assert_eq!(c.node_count(), 3);
+    /// ```
+    ///
+    pub fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    /// Returns a Deferred computing the same value, labeled with `name`.  The labeled
+    /// Deferred is a distinct node from the original, so label a Deferred before building
+    /// on it elsewhere to avoid computing it twice.
+    ///
+    /// ```
+    /// use tange::deferred::Deferred;
+    ///
+    /// let a = Deferred::lift(1usize, "a".into());
+    /// let b = a.apply(|x| x + 1).named("Increment");
+    /// assert_eq!(b.lineage(), vec!["Input<name=a>", "Task<name=Increment>"]);
+    /// ```
+    ///
+    pub fn named(&self, name: &str) -> Deferred<A> {
+        Deferred {
+            graph: self.graph.renamed(name),
+            items: PhantomData
+        }
+    }
+
+    /// Returns the names of the tasks leading to this Deferred, starting from its
+    /// original input.  For joins, the lineage follows the left-hand input.
+    ///
+    /// ```
+    /// use tange::deferred::Deferred;
+    ///
+    /// let a = Deferred::lift(1usize, "a".into());
+    /// let b = Deferred::lift(2usize, "b".into());
+    /// let c = a.apply(|x| x + 1).join(&b, |x, y| x + y);
+    /// assert_eq!(c.lineage(), vec!["Input<name=a>", "Task<name=Apply>", "Task<name=Join>"]);
+    /// ```
+    ///
+    pub fn lineage(&self) -> Vec<String> {
+        self.graph.lineage()
+    }
+
+    /// Returns a Deferred computing the same value, whose output is kept by schedulers
+    /// with a cache, such as `GreedyScheduler::with_cache_capacity`, and reused by later
+    /// runs rather than recomputed.  Like `named`, the cached Deferred is a distinct node
+    /// from the original.
+    ///
+    /// ```
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let s = GreedyScheduler::new().with_cache_capacity(4);
+    /// let expensive = Deferred::lift(2usize, None).apply(|x| x * 100).cache();
+    /// assert_eq!(expensive.apply(|x| x + 1).run(&s), Some(201));
+    /// // Only adds one to the cached value
+    /// assert_eq!(expensive.apply(|x| x + 2).run(&s), Some(202));
+    /// ```
+    ///
+    pub fn cache(&self) -> Deferred<A> {
+        Deferred {
+            graph: self.graph.cached(),
+            items: PhantomData
+        }
+    }
+
+    /// Returns a Deferred computing the same value, which re-runs its task when it panics,
+    /// such as for flaky I/O.  The task is retried up to `attempts` times, sleeping 
+    /// `backoff_ms` before the first retry and doubling the wait before each following
+    /// one.  If every retry fails, the last panic propagates.  Only this Deferred's own 
+    /// task is retried, not the tasks it depends on.
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let tries = AtomicUsize::new(0);
+    /// let flaky = Deferred::lift(2usize, None).apply(move |x| {
+    ///     if tries.fetch_add(1, Ordering::SeqCst) == 0 { panic!("Connection reset") }
+    ///     x * 10
+    /// });
+    /// assert_eq!(flaky.retry(3, 1).run(&GreedyScheduler::new()), Some(20));
+    /// ```
+    ///
+    pub fn retry(&self, attempts: usize, backoff_ms: u64) -> Deferred<A> {
+        Deferred {
+            graph: self.graph.retried(attempts, backoff_ms),
+            items: PhantomData
+        }
+    }
+
+    /// Lifts a shared value into a Deferred object.  Unlike `lift`, reading the input
+    /// only clones the `Arc` rather than the value, which makes it cheap to feed a large,
+    /// read-only value into many graphs.  The value itself is still copied by `run`; use
+    /// `run_arc` to get the shared value back without a copy.
+    /// ```
+    /// use std::sync::Arc;
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let table = Arc::new(vec![1usize, 2, 3]);
+    /// let total = Deferred::lift_arc(table.clone(), "Table".into()).apply(|v| v.iter().sum());
+    /// assert_eq!(total.run(&GreedyScheduler::new()), Some(6usize));
+    /// ```
+    pub fn lift_arc(a: Arc<A>, name: Option<&str>) -> Self {
+        Deferred {
+            graph: Graph::create_input(LiftShared(a), name.unwrap_or("Input")),
+            items: PhantomData
+        }
+    }
 }
 
 impl <A: Any + Send + Sync + Clone> Deferred<A> {
@@ -98,6 +258,60 @@ impl <A: Any + Send + Sync + Clone> Deferred<A> {
         }
     }
 
+    /// Lifts a function into a Deferred object, which produces the value when the graph
+    /// is computed rather than when the graph is built.  This avoids materializing large
+    /// inputs up front.
+    /// ```
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let v = Deferred::lift_lazy(|| (0..100usize).collect::<Vec<_>>(), "Range".into());
+    /// assert_eq!(v.apply(|v| v.len()).run(&GreedyScheduler::new()), Some(100));
+    /// ```
+    pub fn lift_lazy<F: 'static + Send + Sync + Fn() -> A>(f: F, name: Option<&str>) -> Self {
+        Deferred {
+            graph: Graph::create_input(LiftLazy(f), name.unwrap_or("Input")),
+            items: PhantomData
+        }
+    }
+
+    /// Returns a Deferred which waits `millis` milliseconds before passing the value
+    /// through unchanged.  This is mostly useful for testing and benchmarking schedulers
+    /// with tasks of a known duration.
+    /// ```
+    /// use std::time::Instant;
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let start = Instant::now();
+    /// let a = Deferred::lift(2usize, None).delay(10);
+    /// assert_eq!(a.run(&GreedyScheduler::new()), Some(2));
+    /// assert!(start.elapsed().as_millis() >= 10);
+    /// ```
+    pub fn delay(&self, millis: u64) -> Deferred<A> {
+        self.apply(move |a| {
+            thread::sleep(Duration::from_millis(millis));
+            a.clone()
+        })
+    }
+
+    /// Runs a function on the computed value for its side effects, such as logging,
+    /// returning a new Deferred with the value unchanged.
+    /// ```
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let a = Deferred::lift(2usize, None).apply(|x| x * 2);
+    /// let logged = a.tap(|x| println!("Intermediate: {}", x));
+    /// assert_eq!(logged.run(&GreedyScheduler::new()), Some(4));
+    /// ```
+    pub fn tap<F: Send + Sync + 'static + Fn(&A)>(&self, f: F) -> Deferred<A> {
+        self.apply(move |a| {
+            f(a);
+            a.clone()
+        })
+    }
+
     /// Evaluates the Deferred object and dependency graph, returning the result 
     /// of the computation.  
     /// 
@@ -113,10 +327,101 @@ impl <A: Any + Send + Sync + Clone> Deferred<A> {
     /// ```
 
     pub fn run<S: Scheduler>(&self, s: &S) -> Option<A> {
+        // The output may also be held by a scheduler's cache, so copy it out of the Arc
         s.compute(self.graph.clone()).and_then(|v| { 
-            Arc::try_unwrap(v).ok().and_then(|ab| {
-                ab.downcast_ref::<A>().map(|x| x.clone())
-            })
+            downcast_shared::<A>(&v).map(|x| x.clone())
+        })
+    }
+
+    /// Evaluates the Deferred object, returning the result behind an `Arc`.  Unlike
+    /// `run`, the computed value is moved into the `Arc` rather than cloned, which is
+    /// cheaper for large results.
+    /// 
+    /// ```
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let v = Deferred::lift(3usize, None).apply(|n| vec![0u8; *n]);
+    /// let out = v.run_arc(&GreedyScheduler::new()).unwrap();
+    /// assert_eq!(*out, vec![0u8; 3]);
+    /// ```
+    pub fn run_arc<S: Scheduler>(&self, s: &S) -> Option<Arc<A>> {
+        s.compute(self.graph.clone()).and_then(|v| {
+            match Arc::try_unwrap(v) {
+                Ok(ab) => match ab.downcast::<A>() {
+                    Ok(a) => Some(Arc::from(a)),
+                    // Lifted with lift_arc, so the value is already shared
+                    Err(ab) => ab.downcast::<Arc<A>>().ok().map(|a| *a)
+                },
+                Err(shared) => match shared.downcast_ref::<Arc<A>>() {
+                    Some(a) => Some(a.clone()),
+                    // Still shared elsewhere, so fall back to a copy
+                    None => shared.downcast_ref::<A>().map(|x| Arc::new(x.clone()))
+                }
+            }
+        })
+    }
+
+    /// Evaluates the Deferred object like `run`, but moves the computed value out of the
+    /// scheduler's storage when nothing else holds on to it, skipping the copy `run`
+    /// makes.  Values still shared, such as those kept by a scheduler cache, are copied.
+    /// The `run_typed` benchmark compares the two for results of different sizes.
+    /// 
+    /// ```
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let v = Deferred::lift(3usize, None).apply(|n| vec![1u8; *n]);
+    /// assert_eq!(v.run_typed(&GreedyScheduler::new()), v.run(&GreedyScheduler::new()));
+    /// ```
+    pub fn run_typed<S: Scheduler>(&self, s: &S) -> Option<A> {
+        s.compute(self.graph.clone()).and_then(|v| {
+            match Arc::try_unwrap(v) {
+                Ok(ab) => match ab.downcast::<A>() {
+                    Ok(a) => Some(*a),
+                    Err(ab) => ab.downcast::<Arc<A>>().ok()
+                        .map(|a| Arc::try_unwrap(*a).unwrap_or_else(|a| (*a).clone()))
+                },
+                Err(shared) => downcast_shared::<A>(&shared).cloned()
+            }
+        })
+    }
+}
+
+impl <A: Any + Send + Sync + Clone, B: Any + Send + Sync + Clone> Deferred<(A, B)> {
+    /// Splits a Deferred pair into two Deferreds, each projecting one side of the tuple.
+    /// Branches which only need one side no longer carry the other along with them.
+    /// ```
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let pair = Deferred::lift((1usize, "one".to_owned()), None);
+    /// let (num, name) = pair.split();
+    /// assert_eq!(num.run(&GreedyScheduler::new()), Some(1usize));
+    /// assert_eq!(name.run(&GreedyScheduler::new()), Some("one".into()));
+    /// ```
+    pub fn split(&self) -> (Deferred<A>, Deferred<B>) {
+        (self.apply(|p| p.0.clone()), self.apply(|p| p.1.clone()))
+    }
+}
+
+impl <A: Any + Send + Sync + Clone, E: Any + Send + Sync + Clone> Deferred<Result<A, E>> {
+    /// Maps the error of a Result-valued Deferred, passing `Ok` values through unchanged.
+    /// ```
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let res: Deferred<Result<usize, &str>> = Deferred::lift(Err("bad"), None);
+    /// let len = res.map_err(|e| e.len());
+    /// assert_eq!(len.run(&GreedyScheduler::new()), Some(Err(3)));
+    /// ```
+    pub fn map_err<
+        E2: Any + Send + Sync + Clone, 
+        F: Send + Sync + 'static + Fn(&E) -> E2
+    >(&self, f: F) -> Deferred<Result<A, E2>> {
+        self.apply(move |r| match r {
+            Ok(a) => Ok(a.clone()),
+            Err(e) => Err(f(e))
         })
     }
 }
@@ -151,6 +456,40 @@ pub fn batch_apply<
     nps 
 }
 
+/// `batch_join` is the two input analog of `batch_apply`: it takes two equal length sets
+/// of `Deferred`s and joins each pair of `Deferred`s sharing the same index, passing in 
+/// the order index.  Panics if the lengths of the two sets differ.
+/// ```
+/// use tange::deferred::{Deferred, batch_join};
+/// use tange::scheduler::GreedyScheduler;
+///
+/// let left: Vec<_> = (0usize..10)
+///     .map(|v| Deferred::lift(v, None)).collect();
+/// let right: Vec<_> = (0usize..10)
+///     .map(|v| Deferred::lift(v * 10, None)).collect();
+/// let out = batch_join(&left, &right, |idx, l, r| idx + l + r);
+/// assert_eq!(out[1].run(&GreedyScheduler::new()), Some(12));
+/// assert_eq!(out[5].run(&GreedyScheduler::new()), Some(60));
+/// ```
+///
+pub fn batch_join<
+    A: Any + Send + Sync + Clone, 
+    B: Any + Send + Sync + Clone, 
+    C: Any + Send + Sync, 
+    F: 'static + Sync + Send + Clone + Fn(usize, &A, &B) -> C
+    >(left: &[Deferred<A>], right: &[Deferred<B>], f: F) 
+-> Vec<Deferred<C>> {
+    assert_eq!(left.len(), right.len(), "batch_join requires inputs of equal length");
+    let mut nps = Vec::with_capacity(left.len());
+    let fa = Arc::new(f);
+    for (idx, (l, r)) in left.iter().zip(right.iter()).enumerate() {
+        let mf = fa.clone();
+        let np = l.join(r, move |lv, rv| { mf(idx, lv, rv) }); 
+        nps.push(np);
+    }   
+    nps 
+}
+
 /// Often times, we want to combine a set of Deferred objects into a single Deferred.
 /// `tree_reduce` combines pairs of Deferred recursively using `f`, building a dependency
 /// tree which attempts to maximize parallelism.
@@ -162,7 +501,7 @@ pub fn batch_apply<
 ///     .map(|v| Deferred::lift(v, None)).collect();
 /// let out = tree_reduce(&vec, |left, right| left + right).unwrap();
 /// let expected = (0usize..10).fold(0, |acc, x| acc + x);
-/// assert_eq!(out.run(&LeveledScheduler), Some(expected));
+/// assert_eq!(out.run(&LeveledScheduler::new()), Some(expected));
 /// ```
 pub fn tree_reduce<A: Any + Send + Sync + Clone, 
                    F: 'static + Sync + Send + Clone + Fn(&A, &A) -> A
@@ -212,6 +551,60 @@ pub fn tree_reduce_until<A: Any + Send + Sync + Clone,
     }
 }
 
+/// `tree_reduce_ordered` combines a set of Deferred objects like `tree_reduce`, for
+/// functions which are associative but not commutative.  The set is split into halves and
+/// each half reduced recursively, so `f` always receives the reduction of a contiguous
+/// run of `defs` on the left and the run immediately following it on the right.
+/// ```
+/// use tange::deferred::{Deferred, tree_reduce_ordered};
+/// use tange::scheduler::GreedyScheduler;
+///
+/// let vec: Vec<_> = (0usize..5)
+///     .map(|v| Deferred::lift(v.to_string(), None)).collect();
+/// let out = tree_reduce_ordered(&vec, |left, right| format!("{}{}", left, right)).unwrap();
+/// assert_eq!(out.run(&GreedyScheduler::new()), Some("01234".into()));
+/// ```
+pub fn tree_reduce_ordered<A: Any + Send + Sync + Clone, 
+                   F: 'static + Sync + Send + Clone + Fn(&A, &A) -> A
+>(
+    defs: &[Deferred<A>], 
+    f: F
+) -> Option<Deferred<A>> {
+    match defs.len() {
+        0 => None,
+        1 => Some(defs[0].clone()),
+        n => {
+            let (left, right) = defs.split_at(n / 2);
+            let l = tree_reduce_ordered(left, f.clone()).unwrap();
+            let r = tree_reduce_ordered(right, f.clone()).unwrap();
+            Some(l.join(&r, f))
+        }
+    }
+}
+
+/// Gathers a set of Deferred objects into a single Deferred holding all of their values,
+/// in the same order as `defs`.  An empty set yields an empty Vec.
+/// ```
+/// use tange::deferred::{Deferred, collect_deferreds};
+/// use tange::scheduler::GreedyScheduler;
+///
+/// let vec: Vec<_> = (0usize..4)
+///     .map(|v| Deferred::lift(v * 2, None)).collect();
+/// let out = collect_deferreds(&vec);
+/// assert_eq!(out.run(&GreedyScheduler::new()), Some(vec![0, 2, 4, 6]));
+/// ```
+pub fn collect_deferreds<A: Any + Send + Sync + Clone>(
+    defs: &[Deferred<A>]
+) -> Deferred<Vec<A>> {
+    let wrapped = batch_apply(defs, |_idx, v| vec![v.clone()]);
+    tree_reduce(&wrapped, |left, right| {
+        let mut out = Vec::with_capacity(left.len() + right.len());
+        out.extend_from_slice(left);
+        out.extend_from_slice(right);
+        out
+    }).unwrap_or_else(|| Deferred::lift(Vec::new(), None))
+}
+
 #[cfg(test)]
 mod def_test {
     use super::*;
@@ -227,7 +620,7 @@ mod def_test {
         let res = (1..1000usize).sum();
 
         let agg = tree_reduce(&v, |x, y| x + y).unwrap();
-        let results = agg.run(&LeveledScheduler);
+        let results = agg.run(&LeveledScheduler::new());
         assert_eq!(results, Some(res));
     }
 
@@ -244,4 +637,225 @@ mod def_test {
         assert_eq!(results, Some(res));
     }
 
+    #[test]
+    fn test_batch_join() {
+        let left: Vec<_> = (0..5usize).map(|x| Deferred::lift(x, None)).collect();
+        let right: Vec<_> = (0..5usize).map(|x| Deferred::lift(x * 2, None)).collect();
+
+        let summed = batch_join(&left, &right, |_idx, l, r| l + r);
+        let results: Vec<_> = summed.iter()
+            .map(|d| d.run(&GreedyScheduler::new()).unwrap())
+            .collect();
+        assert_eq!(results, vec![0, 3, 6, 9, 12]);
+    }
+
+    #[test]
+    #[should_panic(expected = "batch_join requires inputs of equal length")]
+    fn test_batch_join_mismatched() {
+        let left = vec![Deferred::lift(1usize, None)];
+        let right: Vec<Deferred<usize>> = Vec::new();
+        batch_join(&left, &right, |_idx, l, r| l + r);
+    }
+
+    #[test]
+    fn test_split() {
+        let pair = Deferred::lift(2usize, None).apply(|x| (x * 2, format!("{}", x)));
+        let (left, right) = pair.split();
+        assert_eq!(left.apply(|x| x + 1).run(&LeveledScheduler::new()), Some(5usize));
+        assert_eq!(right.run(&GreedyScheduler::new()), Some("2".to_owned()));
+
+        let both = left.join(&right, |l, r| format!("{}{}", l, r));
+        assert_eq!(both.run(&GreedyScheduler::new()), Some("42".to_owned()));
+    }
+
+    #[derive(Clone,Debug,PartialEq)]
+    enum ParseError {
+        Invalid(String)
+    }
+
+    #[test]
+    fn test_map_err() {
+        let parse = |s: &&str| -> Result<usize, &'static str> {
+            s.parse().map_err(|_| "not a number")
+        };
+        let good = Deferred::lift("12", None).apply(parse);
+        let bad = Deferred::lift("twelve", None).apply(parse);
+
+        let to_enum = |e: &&str| ParseError::Invalid(e.to_string());
+        assert_eq!(good.map_err(to_enum).run(&GreedyScheduler::new()), Some(Ok(12)));
+        assert_eq!(bad.map_err(to_enum).run(&GreedyScheduler::new()), 
+                   Some(Err(ParseError::Invalid("not a number".into()))));
+    }
+
+    #[test]
+    fn test_lift_lazy() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let c2 = calls.clone();
+        let lazy = Deferred::lift_lazy(move || { c2.fetch_add(1, Ordering::SeqCst); 5usize }, None);
+        let out = lazy.apply(|x| x * 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(out.run(&LeveledScheduler::new()), Some(10));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_delay() {
+        use std::time::Instant;
+
+        let a = Deferred::lift(1usize, None).delay(50);
+        let b = Deferred::lift(2usize, None).delay(50);
+        let start = Instant::now();
+        assert_eq!(a.join(&b, |x, y| x + y).delay(50).run(&LeveledScheduler::new()), Some(3));
+        assert!(start.elapsed().as_millis() >= 100);
+    }
+
+    #[test]
+    fn test_tap() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let c2 = counter.clone();
+        let tapped = Deferred::lift(3usize, None)
+            .tap(move |_x| { c2.fetch_add(1, Ordering::SeqCst); })
+            .apply(|x| x + 1);
+
+        assert_eq!(tapped.run(&LeveledScheduler::new()), Some(4));
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_run_arc() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let addr = Arc::new(AtomicUsize::new(0));
+        let a2 = addr.clone();
+        let big = Deferred::lift(1000usize, None).apply(move |n| {
+            let v = vec![7u64; *n];
+            a2.store(v.as_ptr() as usize, Ordering::SeqCst);
+            v
+        });
+
+        let out = big.run_arc(&GreedyScheduler::new()).unwrap();
+        assert_eq!(out.len(), 1000);
+        assert_eq!(out.as_ptr() as usize, addr.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_collect_deferreds() {
+        let v: Vec<_> = (0..5usize).map(|x| Deferred::lift(x, None)).collect();
+        let out = collect_deferreds(&v).run(&LeveledScheduler::new());
+        assert_eq!(out, Some(vec![0, 1, 2, 3, 4]));
+
+        let empty: Vec<Deferred<usize>> = Vec::new();
+        assert_eq!(collect_deferreds(&empty).run(&LeveledScheduler::new()), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_apply2() {
+        let a = Deferred::lift(3usize, None);
+        let fused = a.apply2(|x| vec![*x; *x], |v| v.iter().sum::<usize>());
+        let chained = a.apply(|x| vec![*x; *x]).apply(|v| v.iter().sum::<usize>());
+        assert_eq!(fused.run(&LeveledScheduler::new()), Some(9));
+        assert_eq!(fused.graph.node_count(), 2);
+        assert_eq!(chained.graph.node_count(), 3);
+    }
+
+    #[test]
+    fn test_run_typed() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Counts how many times it is copied
+        struct Counted(Vec<usize>, Arc<AtomicUsize>);
+        impl Clone for Counted {
+            fn clone(&self) -> Self {
+                self.1.fetch_add(1, Ordering::SeqCst);
+                Counted(self.0.clone(), self.1.clone())
+            }
+        }
+
+        let copies = Arc::new(AtomicUsize::new(0));
+        let c2 = copies.clone();
+        let d = Deferred::lift(100_000usize, None)
+            .apply(move |n| Counted((0..*n).collect(), c2.clone()));
+
+        let s = GreedyScheduler::new();
+        let typed = d.run_typed(&s).unwrap();
+        assert_eq!(copies.load(Ordering::SeqCst), 0);
+        let copied = d.run(&s).unwrap();
+        assert_eq!(copies.load(Ordering::SeqCst), 1);
+        assert_eq!(typed.0, copied.0);
+
+        // Shared values fall back to a copy
+        let cached = d.cache();
+        let cs = GreedyScheduler::new().with_cache_capacity(1);
+        assert_eq!(cached.run_typed(&cs).map(|c| c.0.len()), Some(100_000));
+        assert_eq!(copies.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_tree_reduce_ordered() {
+        let defs: Vec<_> = (0..37usize).map(|x| Deferred::lift(vec![x], None)).collect();
+        let out = tree_reduce_ordered(&defs, |l, r| {
+            let mut v = l.clone();
+            v.extend_from_slice(r);
+            v
+        });
+        assert_eq!(out.unwrap().run(&LeveledScheduler::new()), Some((0..37).collect()));
+
+        let empty: Vec<Deferred<usize>> = Vec::new();
+        assert!(tree_reduce_ordered(&empty, |l, r| l + r).is_none());
+    }
+
+    #[test]
+    fn test_retry() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Instant;
+
+        let tries = Arc::new(AtomicUsize::new(0));
+        let t2 = tries.clone();
+        let flaky = Deferred::lift(4usize, None).apply(move |x| {
+            if t2.fetch_add(1, Ordering::SeqCst) < 2 {
+                panic!("Transient failure");
+            }
+            x + 1
+        });
+
+        let start = Instant::now();
+        assert_eq!(flaky.retry(5, 10).run(&LeveledScheduler::new()), Some(5));
+        assert_eq!(tries.load(Ordering::SeqCst), 3);
+        // Waited 10ms, then 20ms
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_lift_arc() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counted(Vec<usize>);
+
+        impl Clone for Counted {
+            fn clone(&self) -> Self {
+                CLONES.fetch_add(1, Ordering::SeqCst);
+                Counted(self.0.clone())
+            }
+        }
+
+        let shared = Arc::new(Counted((0..1_000_000).collect()));
+        let input = Deferred::lift_arc(shared.clone(), None);
+        let len = input.apply(|c| c.0.len());
+        let first = input.join(&len, |c, n| c.0[0] + n);
+        let s = LeveledScheduler::new();
+        assert_eq!(len.run(&s), Some(1_000_000));
+        assert_eq!(first.run(&s), Some(1_000_000));
+
+        // Comes back as the same allocation
+        let out = input.run_arc(&s).unwrap();
+        assert!(Arc::ptr_eq(&out, &shared));
+        assert_eq!(CLONES.load(Ordering::SeqCst), 0);
+    }
+
 }
@@ -3,9 +3,13 @@
 //! via Deferred objects.
 //!
 use std::sync::Arc;
+use std::collections::HashSet;
 use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+use std::panic::{self,AssertUnwindSafe};
+use std::thread;
+use std::time::Duration;
 
-use task::{BASS,DynRun};
+use task::{BASS,DynArgs,DynRun};
 
 static GLOBAL_HANDLE_COUNT: AtomicUsize = ATOMIC_USIZE_INIT;
 
@@ -24,6 +28,11 @@ impl Handle {
     fn new(name: String) -> Self {
         Handle(name, GLOBAL_HANDLE_COUNT.fetch_add(1, Ordering::SeqCst))
     }
+
+    /// Descriptive name of the node, such as `Task<name=Apply>`
+    pub fn name(&self) -> &str {
+        &self.0
+    }
 }
 
 /// ADT for handling either Tasks or reading data into the graph
@@ -47,8 +56,68 @@ pub enum FnArgs {
     Join(Arc<Graph>, Arc<Graph>)
 }
 
+impl FnArgs {
+    /// Number of inputs passed to the task
+    pub fn arity(&self) -> usize {
+        match self {
+            FnArgs::Single(_) => 1,
+            FnArgs::Join(_, _) => 2
+        }
+    }
+}
+
+/// Wraps a task, re-running it when it panics.  Each retry waits twice as long as the
+/// previous one; once the retries are exhausted, the final attempt's panic propagates.
+struct Retry {
+    task: Arc<Task>,
+    retries: usize,
+    backoff_ms: u64
+}
+
+impl Retry {
+    fn attempt<R, F: Fn() -> R>(&self, f: F) -> R {
+        let mut wait = self.backoff_ms;
+        for _ in 0..self.retries {
+            if let Ok(r) = panic::catch_unwind(AssertUnwindSafe(&f)) {
+                return r
+            }
+            thread::sleep(Duration::from_millis(wait));
+            wait = wait.saturating_mul(2);
+        }
+        f()
+    }
+}
+
+impl DynRun for Retry {
+    fn eval(&self, val: DynArgs) -> Option<BASS> {
+        match *self.task {
+            Task::Function(ref t) => self.attempt(|| t.eval(val)),
+            Task::Input(_) => None
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match *self.task {
+            Task::Function(ref t) => t.arity(),
+            Task::Input(_) => 0
+        }
+    }
+}
+
+impl Input for Retry {
+    fn read(&self) -> BASS {
+        match *self.task {
+            Task::Input(ref i) => self.attempt(|| i.read()),
+            Task::Function(_) => panic!("Retry of a function was read as an input")
+        }
+    }
+}
+
 /// Graphs contain the computational pieces needed to represent the data flow
 /// between multiple different tasks, their combination, and eventual output.
+///
+/// Since 0.2 Graph has a `cached` field, so struct literals building a Graph need
+/// to set it: `cached: false` keeps the old behavior.
 #[derive(Clone)]
 pub struct Graph {
 
@@ -59,7 +128,11 @@ pub struct Graph {
     pub task: Arc<Task>,
 
     /// Arguments consumed by defined Task
-    pub args: Option<FnArgs>
+    pub args: Option<FnArgs>,
+
+    /// Whether schedulers with a cache should keep the output of this node around 
+    /// between runs
+    pub cached: bool
 
 }
 
@@ -73,13 +146,19 @@ impl Graph {
         Arc::new(Graph {
             handle: handle,
             task: inp,
-            args: None
+            args: None,
+            cached: false
         })
     }
 
     /// Adds a task to the dataset with the given inputs.  No effort is made to ensure the
-    /// handles exist within the graph.
+    /// handles exist within the graph.  In debug builds, panics if the number of inputs
+    /// doesn't match the number of arguments the task takes, when the task's arity is known.
     pub fn create_task<D: 'static + DynRun>(inputs: FnArgs, t: D, name: &str) -> Arc<Graph> {
+        debug_assert!(t.arity() == 0 || inputs.arity() == t.arity(), 
+            "Task {} takes {} argument(s) but was given {} input(s)", 
+            name, t.arity(), inputs.arity());
+
         // Get new handle
         let h_name = format!("Task<name={}>", name);
         let handle = Arc::new(Handle::new(h_name));
@@ -87,9 +166,135 @@ impl Graph {
         Arc::new(Graph {
             handle: handle,
             task: task,
-            args: Some(inputs)
+            args: Some(inputs),
+            cached: false
+        })
+    }
+
+    /// Counts the number of unique tasks and inputs needed to compute this Graph.
+    pub fn node_count(&self) -> usize {
+        let mut seen = HashSet::new();
+        let mut stack = vec![self];
+        while let Some(g) = stack.pop() {
+            if !seen.insert(g.handle.clone()) {
+                continue
+            }
+            match g.args {
+                Some(FnArgs::Single(ref a)) => stack.push(a),
+                Some(FnArgs::Join(ref a, ref b)) => {
+                    stack.push(a);
+                    stack.push(b);
+                },
+                None => ()
+            }
+        }
+        seen.len()
+    }
+
+    /// Creates a copy of this node under a new task name, sharing the same task and
+    /// inputs.  The copy has its own handle, so it is a distinct node from the original:
+    /// rename nodes before they are shared with other tasks to avoid computing them twice.
+    pub fn renamed(&self, name: &str) -> Arc<Graph> {
+        let h_name = match *self.task {
+            Task::Function(_) => format!("Task<name={}>", name),
+            Task::Input(_)    => format!("Input<name={}>", name)
+        };
+        Arc::new(Graph {
+            handle: Arc::new(Handle::new(h_name)),
+            task: self.task.clone(),
+            args: self.args.clone(),
+            cached: self.cached
+        })
+    }
+
+    /// Creates a copy of this node flagged for caching, sharing the same task and inputs.
+    /// Like `renamed`, the copy has its own handle.
+    pub fn cached(&self) -> Arc<Graph> {
+        Arc::new(Graph {
+            handle: Arc::new(Handle::new(self.handle.name().to_owned())),
+            task: self.task.clone(),
+            args: self.args.clone(),
+            cached: true
+        })
+    }
+
+    /// Creates a copy of this node which re-runs its task up to `retries` more times if it
+    /// panics, sleeping `backoff_ms` before the first retry and doubling the wait after
+    /// each.  Like `renamed`, the copy has its own handle.
+    pub fn retried(&self, retries: usize, backoff_ms: u64) -> Arc<Graph> {
+        let retry = Retry { task: self.task.clone(), retries, backoff_ms };
+        let task = match *self.task {
+            Task::Function(_) => Task::Function(Box::new(retry)),
+            Task::Input(_)    => Task::Input(Box::new(retry))
+        };
+        Arc::new(Graph {
+            handle: Arc::new(Handle::new(self.handle.name().to_owned())),
+            task: Arc::new(task),
+            args: self.args.clone(),
+            cached: self.cached
         })
     }
 
+    /// Returns the names of the nodes along the primary chain of this Graph, starting from
+    /// its original input.  For joins, the chain follows the left-hand input.
+    pub fn lineage(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut cur = Some(self);
+        while let Some(g) = cur {
+            names.push(g.handle.name().to_owned());
+            cur = match g.args {
+                Some(FnArgs::Single(ref a)) | Some(FnArgs::Join(ref a, _)) => Some(a),
+                None => None
+            };
+        }
+        names.reverse();
+        names
+    }
+
 }
 
+#[cfg(test)]
+mod graph_test {
+    use super::*;
+    use task::{DynFn,DynFn2};
+
+    struct Num(usize);
+
+    impl Input for Num {
+        fn read(&self) -> BASS {
+            Box::new(self.0)
+        }
+    }
+
+    #[test]
+    fn test_create_task_arity() {
+        let a = Graph::create_input(Num(1), "a");
+        let b = Graph::create_input(Num(2), "b");
+        let single = Graph::create_task(FnArgs::Single(a.clone()), 
+                                        DynFn::new(|x: &usize| x + 1), "Apply");
+        let joined = Graph::create_task(FnArgs::Join(a, b), 
+                                        DynFn2::new(|x: &usize, y: &usize| x + y), "Join");
+        assert_eq!(single.node_count(), 2);
+        assert_eq!(joined.node_count(), 3);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Task Bad takes 1 argument(s) but was given 2 input(s)")]
+    fn test_create_task_arity_mismatch() {
+        let a = Graph::create_input(Num(1), "a");
+        let b = Graph::create_input(Num(2), "b");
+        Graph::create_task(FnArgs::Join(a, b), DynFn::new(|x: &usize| x + 1), "Bad");
+    }
+
+    #[test]
+    fn test_renamed() {
+        let a = Graph::create_input(Num(1), "a");
+        let inc = Graph::create_task(FnArgs::Single(a), 
+                                     DynFn::new(|x: &usize| x + 1), "Apply");
+        let named = inc.renamed("Increment");
+        assert!(named.handle != inc.handle);
+        assert_eq!(named.lineage(), vec!["Input<name=a>", "Task<name=Increment>"]);
+        assert_eq!(named.node_count(), 2);
+    }
+}
@@ -3,8 +3,12 @@ extern crate num_cpus;
 extern crate log;
 extern crate priority_queue;
 extern crate jobpool;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 
 use std::sync::{Mutex,Arc,mpsc};
+use std::panic::{self,AssertUnwindSafe};
+use std::thread;
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 
@@ -17,6 +21,7 @@ use graph::{Graph,Task,Handle,FnArgs};
 
 type DepGraph = HashMap<Arc<Handle>, HashSet<Arc<Handle>>>; 
 type ChainGraph = HashMap<Vec<Arc<Handle>>, HashSet<Arc<Handle>>>; 
+type Progress = Box<dyn Fn(usize, usize) + Send + Sync>;
 
 // Keeps track of data that are needed by downstream computations
 #[derive(Debug)]
@@ -56,6 +61,70 @@ impl <K: PartialEq + Hash + Eq, V: Clone> DataStore<K,V> {
     }
 }
 
+// Keeps the outputs of the most recently used cached nodes, up to a fixed number of
+// entries
+struct LruCache {
+    capacity: usize,
+    tick: usize,
+    entries: HashMap<Arc<Handle>, (usize, Arc<BASS>)>
+}
+
+impl LruCache {
+    fn new(capacity: usize) -> Self {
+        LruCache { capacity, tick: 0, entries: HashMap::new() }
+    }
+
+    // Gets the output for a node, marking it as the most recently used
+    fn get(&mut self, handle: &Arc<Handle>) -> Option<Arc<BASS>> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(handle).map(|e| {
+            e.0 = tick;
+            e.1.clone()
+        })
+    }
+
+    // Marks the cached outputs of `handles` as the most recently used
+    fn touch(&mut self, handles: &[Arc<Handle>]) {
+        for handle in handles {
+            self.tick += 1;
+            if let Some(e) = self.entries.get_mut(handle) {
+                e.0 = self.tick;
+            }
+        }
+    }
+
+    // Adds the output for a node, evicting the least recently used outputs when over
+    // capacity.  The cached `sources` it was computed from are marked as used after it,
+    // so that a derived copy, such as a partition read back from disk, is evicted before
+    // the output it can be recomputed from.  The new output itself is only evicted when
+    // nothing else is left, so that the next step of an iterative computation can
+    // always reuse it.
+    fn insert(&mut self, handle: Arc<Handle>, data: Arc<BASS>, sources: &[Arc<Handle>]) {
+        self.tick += 1;
+        self.entries.insert(handle.clone(), (self.tick, data));
+        self.touch(sources);
+        while self.entries.len() > self.capacity {
+            let oldest = self.entries.iter()
+                .min_by_key(|&(h, e)| (*h == handle, e.0))
+                .map(|(h, _)| h.clone())
+                .unwrap();
+            trace!("Evicting {:?} from cache", oldest);
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+// Handles of the cached nodes which a node reads directly
+fn cached_inputs(args: &Option<FnArgs>) -> Vec<Arc<Handle>> {
+    let inputs = match *args {
+        Some(FnArgs::Single(ref g)) => vec![g],
+        Some(FnArgs::Join(ref l, ref r)) => vec![l, r],
+        None => Vec::new()
+    };
+    inputs.into_iter().filter(|g| g.cached).map(|g| g.handle.clone()).collect()
+}
+
 /// Defines the Scheduler object.  Schedulers take in Graphs and return the result
 /// of their computation.
 pub trait Scheduler {
@@ -74,26 +143,58 @@ struct DAG {
     pub tasks: HashMap<Arc<Handle>, Arc<Task>>,
 
     /// Dependencies between tasks
-    pub dependencies: HashMap<Arc<Handle>, Option<FnArgs>>
+    pub dependencies: HashMap<Arc<Handle>, Option<FnArgs>>,
+
+    /// Outputs of cached tasks which don't need to be computed again
+    pub preloaded: HashMap<Arc<Handle>, Arc<BASS>>,
+
+    /// Cached tasks whose outputs should be added to `cache` once computed
+    pub keep: HashSet<Arc<Handle>>,
+
+    /// Cache which receives the outputs of the tasks in `keep` as they are computed
+    pub cache: Option<Arc<Mutex<LruCache>>>
  
 }
 
 impl DAG {
     /// Converts a Graph into a Directed Acyclic Graph.
     fn new(g: Arc<Graph>) -> Self {
+        DAG::with_cache(g, None)
+    }
+
+    /// Converts a Graph into a Directed Acyclic Graph, looking up the outputs of cached
+    /// nodes in `cache`.  Nodes with a known output are preloaded and their inputs 
+    /// are left out of the DAG, unless needed elsewhere.  Without a cache, cached nodes
+    /// are treated like any other.
+    fn with_cache(g: Arc<Graph>, cache: Option<Arc<Mutex<LruCache>>>) -> Self {
         let mut tasks = HashMap::new();
         let mut dependencies = HashMap::new();
+        let mut preloaded = HashMap::new();
+        let mut keep = HashSet::new();
 
         let mut stack = vec![g];
 
         let mut hs = HashSet::new();
 
+        // Held for the whole traversal, so that lookups see a consistent cache
+        let mut lru = cache.as_ref().map(|c| c.lock().unwrap());
         while !stack.is_empty() {
             trace!("Stack size: {}", stack.len());
             let ag = stack.pop().unwrap();
             if !hs.contains(&ag.handle) {
                 hs.insert(ag.handle.clone());
                 tasks.insert(ag.handle.clone(), ag.task.clone());
+                if ag.cached {
+                    if let Some(ref mut lru) = lru {
+                        if let Some(out) = lru.get(&ag.handle) {
+                            lru.touch(&cached_inputs(&ag.args));
+                            preloaded.insert(ag.handle.clone(), out);
+                            dependencies.insert(ag.handle.clone(), None);
+                            continue
+                        }
+                        keep.insert(ag.handle.clone());
+                    }
+                }
                 dependencies.insert(ag.handle.clone(), ag.args.clone());
                 if let Some(ref fns) = ag.args {
                     match fns {
@@ -106,9 +207,13 @@ impl DAG {
                 }
             }
         }
+        drop(lru);
         DAG {
             tasks: tasks,
-            dependencies: dependencies
+            dependencies: dependencies,
+            preloaded,
+            keep,
+            cache
         }
     }
 }
@@ -161,6 +266,50 @@ fn build_dep_graph(graph: &DAG) -> (DepGraph, DepGraph) {
     (inbound, outbound)
 }
 
+// Looks for a cycle in the dependency graph by repeatedly removing nodes without any
+// remaining dependencies.  Nodes left over afterwards are part of, or depend on, a
+// cycle; one of them is returned for reporting.
+fn find_cycle(inbound: &DepGraph) -> Option<Arc<Handle>> {
+    let mut outbound: HashMap<&Arc<Handle>, Vec<&Arc<Handle>>> = HashMap::new();
+    let mut remaining = HashMap::with_capacity(inbound.len());
+    let mut ready = Vec::new();
+    for (node, deps) in inbound.iter() {
+        for d in deps.iter() {
+            outbound.entry(d).or_default().push(node);
+        }
+        remaining.insert(node, deps.len());
+        if deps.is_empty() {
+            ready.push(node);
+        }
+    }
+
+    while let Some(node) = ready.pop() {
+        remaining.remove(node);
+        if let Some(nodes) = outbound.get(node) {
+            for n in nodes.iter() {
+                if let Some(count) = remaining.get_mut(*n) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push(*n);
+                    }
+                }
+            }
+        }
+    }
+    remaining.keys().next().map(|h| (*h).clone())
+}
+
+// Refuses graphs with cycles, which would otherwise never finish computing
+fn check_acyclic(inbound: &DepGraph) -> bool {
+    match find_cycle(inbound) {
+        Some(h) => {
+            error!("Graph contains a cycle through {:?}, refusing to compute it", h);
+            false
+        },
+        None => true
+    }
+}
+
 // Constructs a set of nodes that have no dependencies between them
 fn generate_levels(collapsed: ChainGraph) -> Vec<Vec<Vec<Arc<Handle>>>> {
     // Create outbound
@@ -221,6 +370,16 @@ fn generate_levels(collapsed: ChainGraph) -> Vec<Vec<Vec<Arc<Handle>>>> {
     levels
 }
 
+// Runs a chain, catching any panic so it can be rethrown on the scheduling thread 
+// rather than silently killing a worker.
+fn run_task_guarded(
+    graph: &DAG, 
+    chain: &[Arc<Handle>], 
+    dsam: Arc<Mutex<DataStore<Arc<Handle>, Arc<BASS>>>> 
+) -> thread::Result<()> {
+    panic::catch_unwind(AssertUnwindSafe(|| run_task(graph, chain, dsam)))
+}
+
 fn run_task(
     graph: &DAG, 
     chain: &[Arc<Handle>], 
@@ -240,6 +399,10 @@ fn run_task(
 
     for handle in chain {
         trace!("Processing handle: {:?}", handle);
+        if let Some(out) = graph.preloaded.get(handle) {
+            largs = Some(Limbo::One(out.clone()));
+            continue
+        }
         let out = match graph.tasks.get(handle) {
             Some(ref task) => {
                 let task_ref: &Task = &task;
@@ -261,7 +424,16 @@ fn run_task(
             None => None
         };
         if let Some(bass) = out {
-            largs = Some(Limbo::One(Arc::new(bass)));
+            let out = Arc::new(bass);
+            if graph.keep.contains(handle) {
+                if let Some(ref cache) = graph.cache {
+                    let sources = graph.dependencies.get(handle)
+                        .map(cached_inputs)
+                        .unwrap_or_default();
+                    cache.lock().unwrap().insert(handle.clone(), out.clone(), &sources);
+                }
+            }
+            largs = Some(Limbo::One(out));
         }
     }
 
@@ -350,7 +522,71 @@ fn collapse_graph<K: Hash + Eq + Debug + Clone>(
 /// This has some small benefits when it to reproducibility: it natually is more
 /// deterministic than the GreedyScheduler, though potentially slower in some cases 
 /// a set of tasks on a level are slower.
-pub struct LeveledScheduler;
+///
+/// Since 0.2 the scheduler holds configuration, so it is created with
+/// `LeveledScheduler::new()` rather than used as the unit value `LeveledScheduler`.
+pub struct LeveledScheduler {
+    threads: usize,
+    progress: Option<Progress>
+}
+
+impl LeveledScheduler {
+
+    /// Creates a new LeveledScheduler, running each level with one thread per core.
+    pub fn new() -> Self {
+        LeveledScheduler::with_threads(num_cpus::get())
+    }
+
+    /// Creates a new LeveledScheduler which runs the tasks within a level across
+    /// `n_threads` threads.  Since tasks within a level are independent, the results
+    /// are the same regardless of the number of threads.
+    ///
+    /// ```
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::LeveledScheduler;
+    ///
+    /// let a = Deferred::lift(1usize, None);
+    /// let b = Deferred::lift(2usize, None);
+    /// let c = a.join(&b, |x, y| x + y);
+    /// assert_eq!(c.run(&LeveledScheduler::with_threads(1)), Some(3));
+    /// assert_eq!(c.run(&LeveledScheduler::with_threads(4)), Some(3));
+    /// ```
+    pub fn with_threads(n_threads: usize) -> Self {
+        assert!(n_threads > 0, "LeveledScheduler requires at least one thread");
+        LeveledScheduler { threads: n_threads, progress: None }
+    }
+
+    /// Registers a callback which is invoked with `(completed, total)` as tasks finish.
+    /// `total` is the number of nodes to run, determined at the start of `compute`.
+    /// Chains of single-input tasks run together, so the callback is invoked once per
+    /// chain, with `completed` advancing by the length of the chain.
+    ///
+    /// ```
+    /// use std::sync::{Arc,Mutex};
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::LeveledScheduler;
+    ///
+    /// let seen = Arc::new(Mutex::new(Vec::new()));
+    /// let s2 = seen.clone();
+    /// let s = LeveledScheduler::new()
+    ///     .with_progress(move |done, total| s2.lock().unwrap().push((done, total)));
+    ///
+    /// let a = Deferred::lift(1usize, None);
+    /// let b = Deferred::lift(2usize, None);
+    /// assert_eq!(a.join(&b, |x, y| x + y).run(&s), Some(3));
+    /// assert_eq!(seen.lock().unwrap().last(), Some(&(3, 3)));
+    /// ```
+    pub fn with_progress<F: 'static + Send + Sync + Fn(usize, usize)>(mut self, cb: F) -> Self {
+        self.progress = Some(Box::new(cb));
+        self
+    }
+}
+
+impl Default for LeveledScheduler {
+    fn default() -> Self {
+        LeveledScheduler::new()
+    }
+}
 
 impl Scheduler for LeveledScheduler{
 
@@ -364,10 +600,15 @@ impl Scheduler for LeveledScheduler{
         debug!("Number of Tasks Specified: {}", dag.tasks.len());
 
         let (inbound, _outbound) = build_dep_graph(&dag);
+        if !check_acyclic(&inbound) {
+            return None
+        }
 
         let collapsed = collapse_graph(inbound);
 
-        debug!("Number of Tasks to Run: {}", collapsed.len());
+        let total_jobs = collapsed.len();
+        let total_nodes = collapsed.keys().map(|chain| chain.len()).sum();
+        debug!("Number of Tasks to Run: {}", total_jobs);
         
         // Build the counts
         let mut counts: HashMap<Arc<Handle>,_> = HashMap::new();
@@ -388,18 +629,120 @@ impl Scheduler for LeveledScheduler{
         let raw_ds: DataStore<Arc<Handle>, Arc<BASS>> = DataStore::new(data, counts);
         let dsam = Arc::new(Mutex::new(raw_ds));
 
+        let mut nodes_done = 0usize;
         for (i, level) in levels.into_iter().enumerate() {
-            let mut pool = JobPool::new(num_cpus::get());
+            let mut pool = JobPool::new(self.threads);
+            let (tx, rx) = mpsc::channel();
             debug!("Running level: {}", i);
+            let n_chains = level.len();
             for chain in level {
                 let g = dag.clone();
                 let c = chain.clone();
                 let d = dsam.clone();
-                pool.queue(move || { run_task(&g, &c, d); });
+                let thread_tx = tx.clone();
+                pool.queue(move || { 
+                    let res = run_task_guarded(&g, &c, d); 
+                    thread_tx.send((c.len(), res)).expect("Error sending thread!");
+                });
             }
 
             // block until all are done
+            let mut failure = None;
+            for _ in 0..n_chains {
+                let (chain_len, res) = rx.recv().unwrap();
+                if let Err(e) = res {
+                    failure = failure.or(Some(e));
+                    continue
+                }
+                nodes_done += chain_len;
+                if let Some(ref cb) = self.progress {
+                    cb(nodes_done, total_nodes);
+                }
+            }
             pool.shutdown();
+
+            // Propagate task panics to the caller
+            if let Some(e) = failure {
+                panic::resume_unwind(e);
+            }
+        }
+
+        debug!("Finished");
+        let ret = {
+            dsam.lock().unwrap().get(&out_handle)
+        };
+        ret
+    }
+}
+
+/// RayonScheduler runs each level of the graph within a `rayon::scope`, leaving the
+/// scheduling of tasks within a level to rayon's work-stealing pool.  Like the
+/// LeveledScheduler, it waits for a level to finish before starting the next.
+///
+/// Requires the `rayon` feature.
+#[cfg(feature = "rayon")]
+pub struct RayonScheduler;
+
+#[cfg(feature = "rayon")]
+impl RayonScheduler {
+
+    /// Creates a new RayonScheduler, using rayon's global thread pool.
+    pub fn new() -> Self {
+        RayonScheduler
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl Default for RayonScheduler {
+    fn default() -> Self {
+        RayonScheduler::new()
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl Scheduler for RayonScheduler {
+
+    fn compute(
+        &self, 
+        graph: Arc<Graph>
+    ) -> Option<Arc<BASS>> {
+        
+        let out_handle = graph.handle.clone();
+        let dag = DAG::new(graph);
+        debug!("Number of Tasks Specified: {}", dag.tasks.len());
+
+        let (inbound, _outbound) = build_dep_graph(&dag);
+        if !check_acyclic(&inbound) {
+            return None
+        }
+
+        let collapsed = collapse_graph(inbound);
+        debug!("Number of Tasks to Run: {}", collapsed.len());
+        
+        // Build the counts
+        let mut counts: HashMap<Arc<Handle>,_> = HashMap::new();
+        for (_k, vs) in collapsed.iter() {
+            for v in vs.iter() {
+                let e = counts.entry(v.clone()).or_insert(0usize);
+                *e += 1;
+            }
+        }
+
+        let levels = generate_levels(collapsed);
+        
+        let raw_ds: DataStore<Arc<Handle>, Arc<BASS>> = DataStore::new(HashMap::new(), counts);
+        let dsam = Arc::new(Mutex::new(raw_ds));
+
+        // Panics within a scope are propagated to the caller once the scope finishes
+        for (i, level) in levels.into_iter().enumerate() {
+            debug!("Running level: {}", i);
+            let g = &dag;
+            rayon::scope(|s| {
+                for chain in level {
+                    let d = dsam.clone();
+                    s.spawn(move |_| run_task(g, &chain, d));
+                }
+            });
         }
 
         debug!("Finished");
@@ -415,18 +758,105 @@ impl Scheduler for LeveledScheduler{
 /// biasing toward reduction.  That is, joins are preferred over an apply since it reduces
 /// the number of thunks by one.  Inputs are preferred last.
 ///
-pub struct GreedyScheduler(usize);
+pub struct GreedyScheduler {
+    threads: usize,
+    progress: Option<Progress>,
+    depth_first: bool,
+    cache: Option<Arc<Mutex<LruCache>>>
+}
 
 impl GreedyScheduler {
 
     /// Creates a new GreedyScheduler with the default number of threads.
     pub fn new() -> Self {
-        GreedyScheduler(num_cpus::get())
+        GreedyScheduler { threads: num_cpus::get(), progress: None, depth_first: false, cache: None }
+    }
+
+    /// Creates a GreedyScheduler which runs tasks depth first: the most recently unblocked
+    /// task always runs next, so that a partition's lineage is computed and released
+    /// before moving on to the next partition.  This keeps peak memory low for wide
+    /// graphs, at the cost of some parallelism.  Panics if `n_threads` is zero.
+    ///
+    /// ```
+    /// use tange::deferred::{Deferred, tree_reduce};
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let parts: Vec<_> = (0..10usize)
+    ///     .map(|x| Deferred::lift(x, None).apply(|x| vec![*x; 1000]).apply(|v| v.len()))
+    ///     .collect();
+    /// let total = tree_reduce(&parts, |x, y| x + y).unwrap();
+    /// assert_eq!(total.run(&GreedyScheduler::depth_first(2)), Some(10000));
+    /// ```
+    pub fn depth_first(n_threads: usize) -> Self {
+        assert!(n_threads > 0, "GreedyScheduler requires at least one thread");
+        GreedyScheduler { threads: n_threads, progress: None, depth_first: true, cache: None }
     }
 
     /// Sets the number of threads to use.  By default, uses one thread per core.
     pub fn set_threads(&mut self, n_threads: usize) -> () {
-         self.0 = n_threads;
+         self.threads = n_threads;
+    }
+
+    /// Registers a callback which is invoked with `(completed, total)` as tasks finish.
+    /// `total` is the number of nodes to run, determined at the start of `compute`.
+    /// Chains of single-input tasks run together, so the callback is invoked once per
+    /// chain, with `completed` advancing by the length of the chain.
+    ///
+    /// ```
+    /// use std::sync::{Arc,Mutex};
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let seen = Arc::new(Mutex::new(Vec::new()));
+    /// let s2 = seen.clone();
+    /// let s = GreedyScheduler::new()
+    ///     .with_progress(move |done, total| s2.lock().unwrap().push((done, total)));
+    ///
+    /// let a = Deferred::lift(1usize, None);
+    /// let b = Deferred::lift(2usize, None);
+    /// assert_eq!(a.join(&b, |x, y| x + y).run(&s), Some(3));
+    /// assert_eq!(seen.lock().unwrap().last(), Some(&(3, 3)));
+    /// ```
+    pub fn with_progress<F: 'static + Send + Sync + Fn(usize, usize)>(mut self, cb: F) -> Self {
+        self.progress = Some(Box::new(cb));
+        self
+    }
+
+    /// Keeps the outputs of nodes marked with `Deferred::cache` between runs, holding on
+    /// to at most `capacity` of them.  When the cache is full, the least recently used
+    /// output is evicted and will be recomputed the next time it's needed.  Using an
+    /// output also counts as using the cached outputs it reads directly, just after it,
+    /// so that an output is evicted before the ones it would be recomputed from, though
+    /// a newly computed output is evicted last.  Outputs
+    /// are added to the cache as they are computed, so the capacity also bounds how many
+    /// cached outputs are held during a run.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize,Ordering};
+    /// use tange::deferred::Deferred;
+    /// use tange::scheduler::GreedyScheduler;
+    ///
+    /// let calls = Arc::new(AtomicUsize::new(0));
+    /// let c2 = calls.clone();
+    /// let s = GreedyScheduler::new().with_cache_capacity(1);
+    /// let squared = Deferred::lift(3usize, None)
+    ///     .apply(move |x| { c2.fetch_add(1, Ordering::SeqCst); x * x })
+    ///     .cache();
+    ///
+    /// assert_eq!(squared.run(&s), Some(9));
+    /// assert_eq!(squared.apply(|x| x + 1).run(&s), Some(10));
+    /// assert_eq!(calls.load(Ordering::SeqCst), 1);
+    /// ```
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache = Some(Arc::new(Mutex::new(LruCache::new(capacity))));
+        self
+    }
+}
+
+impl Default for GreedyScheduler {
+    fn default() -> Self {
+        GreedyScheduler::new()
     }
 }
 
@@ -440,15 +870,19 @@ impl Scheduler for GreedyScheduler {
         let out_handle = graph.handle.clone();
 
         trace!("Building Dag...");
-        let dag = Arc::new(DAG::new(graph));
+        let dag = Arc::new(DAG::with_cache(graph, self.cache.clone()));
         
         debug!("Number of Tasks Specified: {}", dag.tasks.len());
 
         let (inbound, mut outbound) = build_dep_graph(&dag);
+        if !check_acyclic(&inbound) {
+            return None
+        }
 
         let collapsed = collapse_graph(inbound);
 
         let total_jobs = collapsed.len();
+        let total_nodes = collapsed.keys().map(|chain| chain.len()).sum();
         debug!("Number of Tasks to Run: {}", total_jobs);
         
         // Build the counts
@@ -488,9 +922,12 @@ impl Scheduler for GreedyScheduler {
         }
         debug!("Starting tasks...");
         let mut jobs_done = 0usize;
+        let mut nodes_done = 0usize;
+        // In depth first mode, later unblocked chains take priority over earlier ones
+        let mut unblocked = 0usize;
         {
-            let mut pool = JobPool::new(self.0);
-            let mut free_threads = self.0;
+            let mut pool = JobPool::new(self.threads);
+            let mut free_threads = self.threads;
             let (tx, rx) = mpsc::channel();
             loop {
                 // Queue up all free items
@@ -502,8 +939,8 @@ impl Scheduler for GreedyScheduler {
                         let d = dsam.clone();
                         let thread_tx = tx.clone();
                         pool.queue(move || {
-                            run_task(&g, &c, d);
-                            thread_tx.send(c[c.len() - 1].clone())
+                            let res = run_task_guarded(&g, &c, d);
+                            thread_tx.send((c[c.len() - 1].clone(), c.len(), res))
                                 .expect("Error sending thread!");
                         });
                         free_threads -= 1;
@@ -511,7 +948,13 @@ impl Scheduler for GreedyScheduler {
                 }
 
                 // Eat!
-                let handle = rx.recv().unwrap(); 
+                let (handle, chain_len, res) = rx.recv().unwrap(); 
+
+                // Wait for the in-flight tasks, then propagate the panic to the caller
+                if let Err(e) = res {
+                    pool.shutdown();
+                    panic::resume_unwind(e);
+                }
                 // Remove it as deps from remaining tasks
                 trace!("{:?} finished", handle);
                 free_threads += 1;
@@ -522,8 +965,14 @@ impl Scheduler for GreedyScheduler {
                             trace!("Updating {:?}", out_handle);
                             deps.remove(&handle);
                             if deps.is_empty() {
-                                trace!("Adding new chain: {:?}, Priority: {}", chain, p);
-                                queue.push(chain.clone(), *p);
+                                let priority = if self.depth_first {
+                                    unblocked += 1;
+                                    unblocked
+                                } else {
+                                    *p
+                                };
+                                trace!("Adding new chain: {:?}, Priority: {}", chain, priority);
+                                queue.push(chain.clone(), priority);
                             } else {
                                 trace!("Remaining Deps: {:?}", deps);
                             }
@@ -532,6 +981,10 @@ impl Scheduler for GreedyScheduler {
                 }
 
                 jobs_done += 1;
+                nodes_done += chain_len;
+                if let Some(ref cb) = self.progress {
+                    cb(nodes_done, total_nodes);
+                }
                 if total_jobs > 10 && jobs_done % (total_jobs as f64 / 10.) as usize == 0 {
                     debug!("Finished {}/{} of jobs", jobs_done, total_jobs);
                     if log_enabled!(Trace) {
@@ -541,7 +994,7 @@ impl Scheduler for GreedyScheduler {
 
                 }
                 // Are we done yet?
-                if free_threads == self.0 && queue.is_empty() {
+                if free_threads == self.threads && queue.is_empty() {
                     break
                 }
             }
@@ -567,6 +1020,10 @@ impl Scheduler for GreedyScheduler {
 #[cfg(test)]
 mod size_test {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use graph::Input;
+    use task::DynFn;
+    use deferred::{Deferred, tree_reduce};
 
     #[test]
     fn test_graph_collapse() {
@@ -637,4 +1094,299 @@ mod size_test {
         assert_eq!(out, res);
     }
 
+    fn assert_progress<S: Scheduler>(s: S, seen: Arc<Mutex<Vec<(usize, usize)>>>) {
+        // Each input and its apply collapse into a chain of two nodes
+        let v: Vec<_> = (0..50usize).map(|x| Deferred::lift(x, None).apply(|x| x + 1)).collect();
+        let agg = tree_reduce(&v, |x, y| x + y).unwrap();
+        assert_eq!(agg.run(&s), Some((1..51usize).sum()));
+
+        let calls = seen.lock().unwrap();
+        let total = agg.node_count();
+        assert_eq!(calls.last(), Some(&(total, total)));
+        assert!(calls.len() < total);
+        let mut prev = 0;
+        for &(done, t) in calls.iter() {
+            assert!(done > prev);
+            assert_eq!(t, total);
+            prev = done;
+        }
+    }
+
+    #[test]
+    fn test_greedy_progress() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let s2 = seen.clone();
+        let s = GreedyScheduler::new()
+            .with_progress(move |done, total| s2.lock().unwrap().push((done, total)));
+        assert_progress(s, seen);
+    }
+
+    #[test]
+    fn test_leveled_progress() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let s2 = seen.clone();
+        let s = LeveledScheduler::new()
+            .with_progress(move |done, total| s2.lock().unwrap().push((done, total)));
+        assert_progress(s, seen);
+    }
+
+    // Sums `width` tasks, where the first `wait_for` tasks wait up to a few seconds for
+    // each other to start.  Returns the sum along with the peak number of concurrent tasks.
+    fn peak_concurrency(s: &LeveledScheduler, width: usize, wait_for: usize) -> (Option<usize>, usize) {
+        use std::time::{Duration, Instant};
+
+        // Started, live and peak task counts
+        let gauge = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)));
+        let v: Vec<_> = (0..width).map(|x| {
+            let gauge = gauge.clone();
+            Deferred::lift(x, None).apply(move |x| {
+                let live = gauge.1.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+                gauge.2.fetch_max(live, AtomicOrdering::SeqCst);
+                gauge.0.fetch_add(1, AtomicOrdering::SeqCst);
+                let deadline = Instant::now() + Duration::from_secs(5);
+                while gauge.0.load(AtomicOrdering::SeqCst) < wait_for && Instant::now() < deadline {
+                    ::std::thread::sleep(Duration::from_millis(1));
+                }
+                gauge.1.fetch_sub(1, AtomicOrdering::SeqCst);
+                x + 1
+            })
+        }).collect();
+        let out = tree_reduce(&v, |x, y| x + y).unwrap().run(s);
+        (out, gauge.2.load(AtomicOrdering::SeqCst))
+    }
+
+    #[test]
+    fn test_leveled_threads() {
+        assert_eq!(peak_concurrency(&LeveledScheduler::with_threads(1), 8, 1), (Some(36), 1));
+        assert_eq!(peak_concurrency(&LeveledScheduler::with_threads(4), 8, 4), (Some(36), 4));
+    }
+
+    #[test]
+    #[should_panic(expected = "task failed")]
+    fn test_greedy_panic() {
+        let a = Deferred::lift(1usize, None).apply(|_x| -> usize { panic!("task failed") });
+        a.apply(|x| x + 1).run(&GreedyScheduler::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "task failed")]
+    fn test_leveled_panic() {
+        let a = Deferred::lift(1usize, None).apply(|_x| -> usize { panic!("task failed") });
+        let b = Deferred::lift(2usize, None);
+        a.join(&b, |x, y| x + y).run(&LeveledScheduler::new());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_rayon_scheduler() {
+        let v: Vec<_> = (0..10000usize).map(|x| Deferred::lift(x, None)).collect();
+        let agg = tree_reduce(&v, |x, y| x + y).unwrap();
+        let expected = agg.run(&LeveledScheduler::new());
+        assert_eq!(expected, Some((0..10000usize).sum()));
+        assert_eq!(agg.run(&RayonScheduler::new()), expected);
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        struct Num(usize);
+        impl Input for Num {
+            fn read(&self) -> BASS {
+                Box::new(self.0)
+            }
+        }
+        let handles: Vec<_> = (0..3usize)
+            .map(|x| Graph::create_input(Num(x), "Num").handle.clone())
+            .collect();
+
+        // 0 <- 1 <- 2
+        let mut inbound: DepGraph = HashMap::new();
+        inbound.insert(handles[0].clone(), HashSet::new());
+        inbound.insert(handles[1].clone(), vec![handles[0].clone()].into_iter().collect());
+        inbound.insert(handles[2].clone(), vec![handles[1].clone()].into_iter().collect());
+        assert_eq!(find_cycle(&inbound), None);
+        assert!(check_acyclic(&inbound));
+
+        // 0 <- 1 <- 2 <- 0
+        inbound.insert(handles[0].clone(), vec![handles[2].clone()].into_iter().collect());
+        assert!(find_cycle(&inbound).is_some());
+        assert!(!check_acyclic(&inbound));
+    }
+
+    // Builds `top <- mid <- top`: the last node reuses top's handle, which closes the loop
+    fn cyclic_graph() -> Arc<Graph> {
+        struct Num(usize);
+        impl Input for Num {
+            fn read(&self) -> BASS {
+                Box::new(self.0)
+            }
+        }
+        let input = Graph::create_input(Num(1), "Num");
+        let top_handle = Graph::create_input(Num(0), "Top").handle.clone();
+        let alias = Arc::new(Graph {
+            handle: top_handle.clone(),
+            task: Arc::new(Task::Function(Box::new(DynFn::new(|x: &usize| x + 1)))),
+            args: Some(FnArgs::Single(input)),
+            cached: false
+        });
+        let mid = Graph::create_task(FnArgs::Single(alias), DynFn::new(|x: &usize| x + 1), "Mid");
+        Arc::new(Graph {
+            handle: top_handle,
+            task: Arc::new(Task::Function(Box::new(DynFn::new(|x: &usize| x + 1)))),
+            args: Some(FnArgs::Single(mid)),
+            cached: false
+        })
+    }
+
+    #[test]
+    fn test_compute_rejects_cycle() {
+        assert!(GreedyScheduler::new().compute(cyclic_graph()).is_none());
+        assert!(GreedyScheduler::depth_first(2).compute(cyclic_graph()).is_none());
+        assert!(LeveledScheduler::new().compute(cyclic_graph()).is_none());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_rayon_rejects_cycle() {
+        assert!(RayonScheduler::new().compute(cyclic_graph()).is_none());
+    }
+
+    // Tracks the number of live allocations, recording the peak
+    struct Gauged(Arc<(AtomicUsize, AtomicUsize)>);
+
+    impl Gauged {
+        fn new(gauge: &Arc<(AtomicUsize, AtomicUsize)>) -> Self {
+            let live = gauge.0.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+            gauge.1.fetch_max(live, AtomicOrdering::SeqCst);
+            Gauged(gauge.clone())
+        }
+    }
+
+    impl Clone for Gauged {
+        fn clone(&self) -> Self {
+            Gauged::new(&self.0)
+        }
+    }
+
+    impl Drop for Gauged {
+        fn drop(&mut self) {
+            (self.0).0.fetch_sub(1, AtomicOrdering::SeqCst);
+        }
+    }
+
+    fn peak_allocations<S: Scheduler>(s: &S) -> usize {
+        let gauge = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)));
+        let parts: Vec<_> = (0..16usize).map(|i| {
+            let g = gauge.clone();
+            let big = Deferred::lift(i, None).apply(move |_x| Gauged::new(&g));
+            // Two consumers keep the allocation in its own task
+            let left = big.apply(|_g| 1usize);
+            let right = big.apply(|_g| 1usize);
+            left.join(&right, |l, r| l + r)
+        }).collect();
+        let total = tree_reduce(&parts, |x, y| x + y).unwrap();
+        assert_eq!(total.run(s), Some(32));
+        gauge.1.load(AtomicOrdering::SeqCst)
+    }
+
+    #[test]
+    fn test_depth_first() {
+        let depth = peak_allocations(&GreedyScheduler::depth_first(1));
+        let breadth = peak_allocations(&LeveledScheduler::with_threads(1));
+        assert_eq!(depth, 1);
+        assert_eq!(breadth, 16);
+    }
+
+    // Runs a chain of cached allocations, each only needed by the next one
+    fn peak_cached_allocations<S: Scheduler>(s: &S) -> usize {
+        let gauge = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)));
+        let mut acc = Deferred::lift(0usize, None);
+        for _ in 0..8 {
+            let g = gauge.clone();
+            acc = acc.apply(move |x| (*x, Gauged::new(&g))).cache().apply(|x| x.0 + 1);
+        }
+        assert_eq!(acc.run(s), Some(8));
+        gauge.1.load(AtomicOrdering::SeqCst)
+    }
+
+    #[test]
+    fn test_cached_outputs_released() {
+        // Without a cache, cached nodes are released once consumed
+        assert_eq!(peak_cached_allocations(&LeveledScheduler::with_threads(1)), 1);
+        assert_eq!(peak_cached_allocations(&GreedyScheduler::new()), 1);
+
+        // With a cache, the capacity bounds what is held during the run
+        assert_eq!(peak_cached_allocations(&GreedyScheduler::new().with_cache_capacity(1)), 2);
+    }
+
+    #[test]
+    fn test_cache_eviction() {
+        let counts = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)));
+        let (c1, c2) = (counts.clone(), counts.clone());
+        let a = Deferred::lift(1usize, None)
+            .apply(move |x| { c1.0.fetch_add(1, AtomicOrdering::SeqCst); x + 10 })
+            .cache();
+        let b = Deferred::lift(2usize, None)
+            .apply(move |x| { c2.1.fetch_add(1, AtomicOrdering::SeqCst); x + 20 })
+            .cache();
+        let calls = || (counts.0.load(AtomicOrdering::SeqCst), counts.1.load(AtomicOrdering::SeqCst));
+
+        let s = GreedyScheduler::new().with_cache_capacity(1);
+        assert_eq!(a.run(&s), Some(11));
+        assert_eq!(a.apply(|x| x * 2).run(&s), Some(22));
+        assert_eq!(calls(), (1, 0));
+
+        // b evicts a, which is recomputed on demand
+        assert_eq!(b.run(&s), Some(22));
+        assert_eq!(a.join(&b, |x, y| x + y).run(&s), Some(33));
+        assert_eq!(calls(), (2, 1));
+
+        // a was computed last, so b was evicted
+        assert_eq!(b.apply(|x| x + 1).run(&s), Some(23));
+        assert_eq!(calls(), (2, 2));
+    }
+
+    #[test]
+    fn test_cache_keeps_most_recent() {
+        let counts = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)));
+        let (c1, c2) = (counts.clone(), counts.clone());
+        let a = Deferred::lift(1usize, None)
+            .apply(move |x| { c1.0.fetch_add(1, AtomicOrdering::SeqCst); x + 10 })
+            .cache();
+        let b = a.apply(move |x| { c2.1.fetch_add(1, AtomicOrdering::SeqCst); x * 2 })
+            .cache();
+        let calls = || (counts.0.load(AtomicOrdering::SeqCst), counts.1.load(AtomicOrdering::SeqCst));
+
+        // b is newly computed, so it's kept over a and is reused without a
+        let s = GreedyScheduler::new().with_cache_capacity(1);
+        assert_eq!(b.run(&s), Some(22));
+        assert_eq!(b.run(&s), Some(22));
+        assert_eq!(calls(), (1, 1));
+
+        // a was evicted, so it is recomputed when asked for directly
+        assert_eq!(a.run(&s), Some(11));
+        assert_eq!(calls(), (2, 1));
+
+        // With room for both, neither is recomputed
+        let s = GreedyScheduler::new().with_cache_capacity(2);
+        assert_eq!(b.run(&s), Some(22));
+        assert_eq!(a.run(&s), Some(11));
+        assert_eq!(b.run(&s), Some(22));
+        assert_eq!(calls(), (3, 2));
+    }
+
+    #[test]
+    fn test_cache_iterative_chain() {
+        // Each step only reads the previous one, so a single cached entry suffices
+        let calls = Arc::new(AtomicUsize::new(0));
+        let s = GreedyScheduler::new().with_cache_capacity(1);
+        let mut x = Deferred::lift(0usize, None);
+        for _ in 0..10 {
+            let c = calls.clone();
+            x = x.apply(move |v| { c.fetch_add(1, AtomicOrdering::SeqCst); v + 1 }).cache();
+            x.run(&s);
+        }
+        assert_eq!(x.run(&s), Some(10));
+        assert_eq!(calls.load(AtomicOrdering::SeqCst), 10);
+    }
+
 }
@@ -1,7 +1,16 @@
 use std::any::Any;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 pub type BASS = Box<Any + Send + Sync>;
+
+/// Downcasts a task output to `A`, looking through the `Arc` that shared inputs, like
+/// those from `Deferred::lift_arc`, are stored behind.
+pub fn downcast_shared<A: Any>(v: &BASS) -> Option<&A> {
+    v.downcast_ref::<A>().or_else(|| v.downcast_ref::<Arc<A>>().map(|a| &**a))
+}
+
+#[derive(Clone,Copy)]
 pub enum DynArgs<'a> {
     One(&'a BASS),
     Two(&'a BASS, &'a BASS)
@@ -9,6 +18,12 @@ pub enum DynArgs<'a> {
 
 pub trait DynRun: Send + Sync {
     fn eval(&self, val: DynArgs) -> Option<BASS>;
+
+    /// Number of arguments the task consumes, or 0 if unknown.  Tasks with an unknown
+    /// arity aren't checked against their inputs when added to a graph.
+    fn arity(&self) -> usize {
+        0
+    }
 }
 
 pub struct DynFn<A,B,F: Fn(&A) -> B>(F,PhantomData<A>,PhantomData<B>);
@@ -23,7 +38,7 @@ impl <A: Any + Send + Sync, B: Any + Send + Sync, F: Send + Sync + Fn(&A) -> B>
 
     fn eval(&self, val: DynArgs) -> Option<BASS> {
         match val {
-            DynArgs::One(v) => v.downcast_ref::<A>().map(|a| {
+            DynArgs::One(v) => downcast_shared::<A>(v).map(|a| {
                 let b = self.0(a);
                 let bx: BASS = Box::new(b);
                 bx
@@ -31,6 +46,10 @@ impl <A: Any + Send + Sync, B: Any + Send + Sync, F: Send + Sync + Fn(&A) -> B>
             _ => None
         }
     }
+
+    fn arity(&self) -> usize {
+        1
+    }
 }
 
 pub struct DynFn2<A,B,C,F: Fn(&A, &B) -> C>(F,PhantomData<A>,PhantomData<B>,PhantomData<C>);
@@ -46,8 +65,8 @@ impl <A: Any + Send + Sync, B: Any + Send + Sync, C: Any + Send + Sync, F: Send
     fn eval(&self, val: DynArgs) -> Option<BASS> {
         match val {
             DynArgs::Two(a, b) => {
-                a.downcast_ref::<A>().and_then(|a| {
-                    b.downcast_ref::<B>().map(|b| {
+                downcast_shared::<A>(a).and_then(|a| {
+                    downcast_shared::<B>(b).map(|b| {
                         let c = self.0(a, b);
                         let cx: BASS = Box::new(c);
                         cx
@@ -57,6 +76,8 @@ impl <A: Any + Send + Sync, B: Any + Send + Sync, C: Any + Send + Sync, F: Send
             _ => None
         }
     }
-}
-
 
+    fn arity(&self) -> usize {
+        2
+    }
+}